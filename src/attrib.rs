@@ -5,6 +5,7 @@ use std::str::FromStr;
 use std::string::ToString;
 use std::convert::Into;
 use std::fmt;
+use std::time::Duration;
 
 use quick_xml::Reader;
 use quick_xml::events::Event;
@@ -67,7 +68,7 @@ impl fmt::Display for AttribType {
     }
 }
 
-#[derive(Clone,PartialEq,Debug)]
+#[derive(Clone,PartialEq,Debug,Serialize,Deserialize)]
 ///This type hold attributes (including the attribute value)
 pub enum Attribute {
     Ignore,
@@ -81,8 +82,8 @@ pub enum Attribute {
     Confidence(f64),
     N(String),
     DateTime(NaiveDateTime),
-    BeginTime(String),
-    EndTime(String),
+    BeginTime(Duration),
+    EndTime(Duration),
     Src(String),
     Speaker(String),
     Textclass(String),
@@ -145,7 +146,7 @@ impl Attribute {
     pub fn as_str(&self) -> Result<&str,FoliaError> {
         match self {
             Attribute::Id(s) | Attribute::Set(s) | Attribute::Class(s) | Attribute::Annotator(s) |
-            Attribute::N(s) | Attribute::BeginTime(s) | Attribute::EndTime(s) |
+            Attribute::N(s) |
             Attribute::Src(s) | Attribute::Speaker(s) | Attribute::Textclass(s) | Attribute::Metadata(s) | Attribute::Idref(s) |
             Attribute::Processor(s) | Attribute::Href(s) | Attribute::Format(s) | Attribute::Subset(s) | Attribute::Text(s)| Attribute::Type(s) | Attribute::Ref(s) | Attribute::Original(s) | Attribute::Auth(s) | Attribute::XLinkType(s) | Attribute::PageNr(s)
                 => Ok(&s),
@@ -169,6 +170,7 @@ impl Attribute {
             Attribute::Offset(n) => Ok(n.to_string()),
             Attribute::LineNr(n) => Ok(n.to_string()),
             Attribute::DateTime(dt) => Ok(dt.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            Attribute::BeginTime(t) | Attribute::EndTime(t) => Ok(format_time(t)),
             Attribute::Ignore => Err(FoliaError::TypeError("Ignore attribute can't be serialised".to_string())),
             _ =>  {
                 if let Ok(s) = self.as_str() {
@@ -244,140 +246,181 @@ impl Attribute {
     ///Parse an XML attribute into a FoLiA Attribute
     pub fn parse<R: BufRead>(reader: &Reader<R>, attrib: &quick_xml::events::attributes::Attribute) -> Result<Attribute,FoliaError> {
         if let Ok(value) = attrib.unescape_and_decode_value(&reader) {
-            match attrib.key {
-                b"xml:id" => {
-                    Ok(Attribute::Id(value))
-                },
-                b"set" => {
-                    Ok(Attribute::Set(value))
-                },
-                b"class" => {
-                    Ok(Attribute::Class(value))
-                },
-                b"processor" => {
-                    Ok(Attribute::Processor(value))
-                },
-                b"annotator" => {
-                    Ok(Attribute::Annotator(value))
-                },
-                b"annotatortype" => {
-                    match value.as_str() {
-                        "auto" => Ok(Attribute::AnnotatorType(ProcessorType::Auto)),
-                        "manual" => Ok(Attribute::AnnotatorType(ProcessorType::Manual)),
-                        "generator" => Ok(Attribute::AnnotatorType(ProcessorType::Generator)),
-                        "datasource" => Ok(Attribute::AnnotatorType(ProcessorType::DataSource)),
-                        other => Err(FoliaError::ParseError(format!("Invalid value for annotatortype: {}", other)))
-                    }
-                },
-                b"subset" => {
-                    Ok(Attribute::Subset(value))
-                },
-                b"format" => {
-                    Ok(Attribute::Format(value))
-                },
-                b"xlink:href" => {
-                    Ok(Attribute::Href(value))
-                },
-                b"xlink:type" => {
-                    Ok(Attribute::XLinkType(value))
-                },
-                b"speaker" => {
-                    Ok(Attribute::Speaker(value))
-                },
-                b"src" => {
-                    Ok(Attribute::Src(value))
-                },
-                b"n" => {
-                    Ok(Attribute::N(value))
-                },
-                b"t" => {
-                    Ok(Attribute::Text(value))
-                },
-                b"datetime" => {
-                    match NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M:%S") {
-                        Ok(dt) => Ok(Attribute::DateTime(dt)),
-                        Err(e) => Err(FoliaError::ParseError(format!("Unable to parse datetime {} -> {}",value, e)))
-                    }
-                },
-                b"begintime" => {
-                    Ok(Attribute::BeginTime(value))
-                },
-                b"endtime" => {
-                    Ok(Attribute::EndTime(value))
-                },
-                b"textclass" => {
-                    Ok(Attribute::Textclass(value))
-                },
-                b"metadata" => {
-                    Ok(Attribute::Metadata(value))
-                },
-                b"id" => {
-                    Ok(Attribute::Idref(value))
-                },
-                b"type" => {
-                    Ok(Attribute::Type(value))
-                },
-                b"auth" => {
-                    Ok(Attribute::Auth(value))
-                },
-                b"original" => {
-                    Ok(Attribute::Original(value))
-                },
-                b"pagenr" => {
-                    Ok(Attribute::PageNr(value))
-                },
-                b"offset" => {
-                    if let Ok(value) = u16::from_str(&value) {
-                        Ok(Attribute::Offset(value))
-                    } else {
-                        Err(FoliaError::ParseError(format!("Invalid offset value: '{}'", value)))
-                    }
-                },
-                b"linenr" => {
-                    if let Ok(value) = u16::from_str(&value) {
-                        Ok(Attribute::LineNr(value))
-                    } else {
-                        Err(FoliaError::ParseError(format!("Invalid line number value: '{}'", value)))
-                    }
-                },
-                b"newpage" => {
-                    match value.as_str() {
-                        "yes" | "true" => Ok(Attribute::NewPage(true)),
-                        "no" | "false" => Ok(Attribute::NewPage(false)),
-                        _ => Err(FoliaError::ParseError(format!("Invalid newpage value: '{}'", value)))
-                    }
-                },
-                b"ref" => {
-                    Ok(Attribute::Ref(value))
-                },
-                b"confidence" => {
-                    if let Ok(value) = f64::from_str(&value) {
-                        Ok(Attribute::Confidence(value))
-                    } else {
-                        Err(FoliaError::ParseError(format!("Invalid confidence value: '{}'", value)))
-                    }
-                },
-                b"space" => {
-                    match value.as_str() {
-                        "yes" | "true" => Ok(Attribute::Space(true)),
-                        "no" | "false" => Ok(Attribute::Space(false)),
-                        _ => Err(FoliaError::ParseError(format!("Invalid space value: '{}'", value)))
-                    }
-                },
-                b"typegroup" => { //used for explicit mode, does not need to be parsed
+            Attribute::from_key_value(attrib.key, value)
+        } else {
+            Err(FoliaError::ParseError("Unable to parse attribute value (invalid utf-8?)".to_string()))
+        }
+    }
+
+    ///Instantiates a FoLiA Attribute from an attribute name (as in XML) and an unescaped value
+    pub fn from_key_value(key: &[u8], value: String) -> Result<Attribute,FoliaError> {
+        match key {
+            b"xml:id" => {
+                Ok(Attribute::Id(value))
+            },
+            b"set" => {
+                Ok(Attribute::Set(value))
+            },
+            b"class" => {
+                Ok(Attribute::Class(value))
+            },
+            b"processor" => {
+                Ok(Attribute::Processor(value))
+            },
+            b"annotator" => {
+                Ok(Attribute::Annotator(value))
+            },
+            b"annotatortype" => {
+                match value.as_str() {
+                    "auto" => Ok(Attribute::AnnotatorType(ProcessorType::Auto)),
+                    "manual" => Ok(Attribute::AnnotatorType(ProcessorType::Manual)),
+                    "generator" => Ok(Attribute::AnnotatorType(ProcessorType::Generator)),
+                    "datasource" => Ok(Attribute::AnnotatorType(ProcessorType::DataSource)),
+                    other => Err(FoliaError::ParseError(format!("Invalid value for annotatortype: {}", other)))
+                }
+            },
+            b"subset" => {
+                Ok(Attribute::Subset(value))
+            },
+            b"format" => {
+                Ok(Attribute::Format(value))
+            },
+            b"xlink:href" => {
+                Ok(Attribute::Href(value))
+            },
+            b"xlink:type" => {
+                Ok(Attribute::XLinkType(value))
+            },
+            b"speaker" => {
+                Ok(Attribute::Speaker(value))
+            },
+            b"src" => {
+                Ok(Attribute::Src(value))
+            },
+            b"n" => {
+                Ok(Attribute::N(value))
+            },
+            b"t" => {
+                Ok(Attribute::Text(value))
+            },
+            b"datetime" => {
+                match NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M:%S") {
+                    Ok(dt) => Ok(Attribute::DateTime(dt)),
+                    Err(e) => Err(FoliaError::ParseError(format!("Unable to parse datetime {} -> {}",value, e)))
+                }
+            },
+            b"begintime" => {
+                Ok(Attribute::BeginTime(parse_time(&value)?))
+            },
+            b"endtime" => {
+                Ok(Attribute::EndTime(parse_time(&value)?))
+            },
+            b"textclass" => {
+                Ok(Attribute::Textclass(value))
+            },
+            b"metadata" => {
+                Ok(Attribute::Metadata(value))
+            },
+            b"id" => {
+                Ok(Attribute::Idref(value))
+            },
+            b"type" => {
+                Ok(Attribute::Type(value))
+            },
+            b"auth" => {
+                Ok(Attribute::Auth(value))
+            },
+            b"original" => {
+                Ok(Attribute::Original(value))
+            },
+            b"pagenr" => {
+                Ok(Attribute::PageNr(value))
+            },
+            b"offset" => {
+                if let Ok(value) = u16::from_str(&value) {
+                    Ok(Attribute::Offset(value))
+                } else {
+                    Err(FoliaError::ParseError(format!("Invalid offset value: '{}'", value)))
+                }
+            },
+            b"linenr" => {
+                if let Ok(value) = u16::from_str(&value) {
+                    Ok(Attribute::LineNr(value))
+                } else {
+                    Err(FoliaError::ParseError(format!("Invalid line number value: '{}'", value)))
+                }
+            },
+            b"newpage" => {
+                match value.as_str() {
+                    "yes" | "true" => Ok(Attribute::NewPage(true)),
+                    "no" | "false" => Ok(Attribute::NewPage(false)),
+                    _ => Err(FoliaError::ParseError(format!("Invalid newpage value: '{}'", value)))
+                }
+            },
+            b"ref" => {
+                Ok(Attribute::Ref(value))
+            },
+            b"confidence" => {
+                if let Ok(value) = f64::from_str(&value) {
+                    Ok(Attribute::Confidence(value))
+                } else {
+                    Err(FoliaError::ParseError(format!("Invalid confidence value: '{}'", value)))
+                }
+            },
+            b"space" => {
+                match value.as_str() {
+                    "yes" | "true" => Ok(Attribute::Space(true)),
+                    "no" | "false" => Ok(Attribute::Space(false)),
+                    _ => Err(FoliaError::ParseError(format!("Invalid space value: '{}'", value)))
+                }
+            },
+            b"typegroup" => { //used for explicit mode, does not need to be parsed
+                Ok(Attribute::Ignore)
+            },
+            b"xmlns" => { //default namespace declaration, e.g. on the root element of a fragment
+                Ok(Attribute::Ignore)
+            },
+            attrib_key => {
+                if attrib_key.contains(&58) { //58 is a colon, we assume alien namespaces and ignore it
                     Ok(Attribute::Ignore)
-                },
-                attrib_key => {
-                    if attrib_key.contains(&58) { //58 is a colon, we assume alien namespaces and ignore it
-                        Ok(Attribute::Ignore)
-                    } else {
-                        Err(FoliaError::ParseError(format!("Unknown attribute: '{}'", std::str::from_utf8(attrib.key).expect("unable to parse attribute name"))))
-                    }
+                } else {
+                    Err(FoliaError::ParseError(format!("Unknown attribute: '{}'", String::from_utf8_lossy(key))))
                 }
             }
-        } else {
-            Err(FoliaError::ParseError("Unable to parse attribute value (invalid utf-8?)".to_string()))
         }
     }
 }
 
+
+///Parses a FoLiA timestamp (``HH:MM:SS.MMM``, as used by the ``begintime`` and ``endtime`` attributes) into a duration. The
+///hours and the fractional seconds may be omitted.
+pub fn parse_time(value: &str) -> Result<Duration,FoliaError> {
+    let fields: Vec<&str> = value.trim().split(':').collect();
+    if fields.len() < 2 || fields.len() > 3 {
+        return Err(FoliaError::ParseError(format!("Invalid time value: '{}'", value)));
+    }
+    let mut seconds: u64 = 0;
+    for field in fields[..fields.len()-1].iter() {
+        let n = u64::from_str(field).map_err(|_| FoliaError::ParseError(format!("Invalid time value: '{}'", value)))?;
+        seconds = seconds * 60 + n;
+    }
+    let (wholeseconds, fraction) = match fields[fields.len()-1].find('.') {
+        Some(index) => fields[fields.len()-1].split_at(index),
+        None => (fields[fields.len()-1], ""),
+    };
+    let wholeseconds = u64::from_str(wholeseconds).map_err(|_| FoliaError::ParseError(format!("Invalid time value: '{}'", value)))?;
+    let mut nanos: u32 = 0;
+    if fraction.len() > 1 {
+        //take at most nanosecond precision and pad to nine digits
+        let digits: String = fraction[1..].chars().take(9).collect();
+        let n = u32::from_str(&digits).map_err(|_| FoliaError::ParseError(format!("Invalid time value: '{}'", value)))?;
+        nanos = n * 10u32.pow(9 - digits.len() as u32);
+    }
+    Ok(Duration::new(seconds * 60 + wholeseconds, nanos))
+}
+
+///Formats a duration as a FoLiA timestamp (``HH:MM:SS.MMM``)
+pub fn format_time(time: &Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{:02}:{:02}:{:02}.{:03}", seconds / 3600, (seconds / 60) % 60, seconds % 60, time.subsec_millis())
+}
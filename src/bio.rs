@@ -0,0 +1,177 @@
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::query::*;
+use crate::select::*;
+use crate::text::*;
+use crate::document::*;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+///Determines how lines of tagged input are matched to the words of a document
+pub enum TokenMatching {
+    ///The lines correspond to the words of the document in order, the first column (if there is
+    ///more than one) holds the token text, which must match the text of the word
+    Order,
+    ///The first column holds the ID of the word
+    Id,
+}
+
+#[derive(Clone)]
+///Options for ``Document::annotate_bio()``
+pub struct BioOptions {
+    ///The span annotation type to create, defaults to ``Entity``
+    pub elementtype: ElementType,
+    pub set: Option<String>,
+    ///The ID of the processor to associate with the annotations
+    pub processor: Option<String>,
+    pub matching: TokenMatching,
+    ///The (0-indexed) column holding the tags, defaults to the last column
+    pub column: Option<usize>,
+}
+
+impl Default for BioOptions {
+    fn default() -> Self {
+        Self {
+            elementtype: ElementType::Entity,
+            set: None,
+            processor: None,
+            matching: TokenMatching::Order,
+            column: None,
+        }
+    }
+}
+
+impl BioOptions {
+    pub fn with_elementtype(mut self, elementtype: ElementType) -> Self {
+        self.elementtype = elementtype;
+        self
+    }
+
+    pub fn with_set(mut self, set: &str) -> Self {
+        self.set = Some(set.to_string());
+        self
+    }
+
+    pub fn with_processor(mut self, processor: &str) -> Self {
+        self.processor = Some(processor.to_string());
+        self
+    }
+
+    pub fn with_matching(mut self, matching: TokenMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn with_column(mut self, column: usize) -> Self {
+        self.column = Some(column);
+        self
+    }
+}
+
+///A span that is being collected from consecutive tags
+struct BioSpan {
+    class: Option<String>,
+    ids: Vec<String>,
+}
+
+impl Document {
+    ///Adds span annotations (entities by default) from token-per-line BIO-tagged input to this
+    ///(already tokenised) document. Every run of a ``B-`` tag and subsequent ``I-`` tags of the
+    ///same class becomes a span annotation, added through ``annotate_span()``. Empty lines end any
+    ///open span, lines starting with ``#`` and ``-DOCSTART-`` lines are ignored. All input is
+    ///validated before anything is added, so on error the document is left untouched. Returns the
+    ///keys of the added spans.
+    pub fn annotate_bio(&mut self, data: &str, options: &BioOptions) -> Result<Vec<ElementKey>,FoliaError> {
+        if !ElementGroup::Span.contains(options.elementtype) {
+            return Err(FoliaError::TypeError(format!("Element type {} is not a span annotation type", options.elementtype)));
+        }
+        //the words in document order, only needed when matching by order
+        let mut words: Vec<(String,String)> = Vec::new();
+        if options.matching == TokenMatching::Order {
+            for word in self.select(Selector::elements().element(Cmp::Is(ElementType::Word)), Recursion::Always) {
+                let id = word.id().ok_or(FoliaError::IncompleteError("Words must have an ID to be part of a span".to_string()))?;
+                let text = word.text(&TextParameters::default()).unwrap_or_default();
+                words.push((id.to_string(), text));
+            }
+        }
+        let mut words = words.into_iter();
+
+        let mut spans: Vec<BioSpan> = Vec::new();
+        let mut current: Option<BioSpan> = None;
+        for (linenr, line) in data.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                spans.extend(current.take());
+                continue;
+            } else if line.starts_with('#') || line.starts_with("-DOCSTART-") {
+                continue;
+            }
+            let columns: Vec<&str> = if line.contains('\t') { line.split('\t').collect() } else { line.split_whitespace().collect() };
+            let tag = match options.column {
+                Some(column) => columns.get(column).ok_or(FoliaError::ParseError(format!("Line {} has no column {}", linenr + 1, column)))?,
+                None => columns.last().expect("non-empty line"),
+            };
+            let id: String = match options.matching {
+                TokenMatching::Order => {
+                    let (id, text) = words.next().ok_or(FoliaError::ValidationError(format!("Line {} has no corresponding word, the document has fewer words", linenr + 1)))?;
+                    if columns.len() > 1 && columns[0] != text {
+                        return Err(FoliaError::ValidationError(format!("Token '{}' on line {} does not match the text of word {} ('{}')", columns[0], linenr + 1, id, text)));
+                    }
+                    id
+                },
+                TokenMatching::Id => {
+                    if columns.len() < 2 {
+                        return Err(FoliaError::ParseError(format!("Line {} has no ID column", linenr + 1)));
+                    }
+                    if self.get_element_key_by_id(columns[0]).is_none() {
+                        return Err(FoliaError::ValidationError(format!("Line {} refers to a non-existing word: {}", linenr + 1, columns[0])));
+                    }
+                    columns[0].to_string()
+                }
+            };
+            let (prefix, class) = match tag.split_once('-') {
+                Some((prefix, class)) => (prefix, Some(class.to_string())),
+                None => (*tag, None),
+            };
+            match prefix {
+                "O" => spans.extend(current.take()),
+                "B" => {
+                    spans.extend(current.take());
+                    current = Some(BioSpan { class, ids: vec!(id) });
+                },
+                "I" => match current.as_mut() {
+                    Some(span) if span.class == class => span.ids.push(id),
+                    //an I- tag without a preceding span of the same class also starts a span (IOB1)
+                    _ => {
+                        spans.extend(current.take());
+                        current = Some(BioSpan { class, ids: vec!(id) });
+                    }
+                },
+                _ => return Err(FoliaError::ParseError(format!("Invalid BIO tag on line {}: {}", linenr + 1, tag)))
+            }
+        }
+        spans.extend(current.take());
+        //check everything before adding anything, so that a failure leaves the document untouched
+        let span_ids: Vec<&[String]> = spans.iter().map(|span| span.ids.as_slice()).collect();
+        self.check_spans_addable(options.elementtype, options.set.as_deref(), options.processor.as_deref(), &span_ids)?;
+
+        let mut keys: Vec<ElementKey> = Vec::new();
+        for span in spans {
+            let mut element = ElementData::new(options.elementtype);
+            if let Some(set) = &options.set {
+                element.set_attrib(Attribute::Set(set.clone()));
+            }
+            if let Some(class) = span.class {
+                element.set_attrib(Attribute::Class(class));
+            }
+            if let Some(processor) = &options.processor {
+                element.set_attrib(Attribute::Processor(processor.clone()));
+            }
+            let ids: Vec<&str> = span.ids.iter().map(|id| id.as_str()).collect();
+            keys.push(self.annotate_span(element.with_span(&ids))?);
+        }
+        Ok(keys)
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::query::*;
+use crate::select::*;
+use crate::text::*;
+use crate::plaintext::*;
+use crate::document::*;
+
+#[derive(Clone,Default)]
+///Options for BRAT standoff export (``Document::to_brat()``) and import (``Document::annotate_brat()``)
+pub struct BratOptions {
+    ///The set of the entities to export (any set if unset) and to create on import
+    pub entity_set: Option<String>,
+    ///The set of the relations to export (any set if unset) and to create on import
+    pub relation_set: Option<String>,
+    ///The ID of the processor to associate with imported annotations
+    pub processor: Option<String>,
+    ///Determines which text is used to compute the character offsets
+    pub textparameters: TextParameters,
+}
+
+impl BratOptions {
+    pub fn with_entity_set(mut self, set: &str) -> Self {
+        self.entity_set = Some(set.to_string());
+        self
+    }
+
+    pub fn with_relation_set(mut self, set: &str) -> Self {
+        self.relation_set = Some(set.to_string());
+        self
+    }
+
+    pub fn with_processor(mut self, processor: &str) -> Self {
+        self.processor = Some(processor.to_string());
+        self
+    }
+
+    pub fn with_textparameters(mut self, textparameters: TextParameters) -> Self {
+        self.textparameters = textparameters;
+        self
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+///The BRAT standoff representation of a document: the text (``.txt``) and the annotations (``.ann``)
+pub struct BratDocument {
+    pub text: String,
+    pub annotations: String,
+    ///The keys of the relations that could not be expressed in BRAT (relations that are not binary
+    ///or that refer to something other than the exported entities), these are left out
+    pub skipped: Vec<ElementKey>,
+}
+
+impl BratDocument {
+    ///Writes the text and annotations to ``{basename}.txt`` and ``{basename}.ann``
+    pub fn write(&self, basename: &str) -> Result<(),FoliaError> {
+        fs::write(format!("{}.txt", basename), &self.text)?;
+        fs::write(format!("{}.ann", basename), &self.annotations)?;
+        Ok(())
+    }
+}
+
+///The text of a document as used for BRAT, with the character offsets of every word
+struct BratText {
+    text: String,
+    ///Key, begin and end offset (in characters) of each word, in document order
+    words: Vec<(ElementKey,usize,usize)>,
+}
+
+impl BratText {
+    fn word_index(&self, key: ElementKey) -> Option<usize> {
+        self.words.iter().position(|(word_key, _, _)| *word_key == key)
+    }
+
+    ///Returns the text between two character offsets
+    fn slice(&self, begin: usize, end: usize) -> String {
+        self.text.chars().skip(begin).take(end - begin).collect()
+    }
+}
+
+///An entity (``T``) as read from BRAT standoff
+struct BratEntity {
+    class: String,
+    fragments: Vec<(usize,usize)>,
+    text: Option<String>,
+}
+
+///A relation (``R``) as read from BRAT standoff
+struct BratRelation {
+    class: String,
+    arguments: Vec<String>,
+}
+
+fn has_set(element: &Element, set: &Option<String>) -> bool {
+    set.is_none() || element.set() == set.as_deref()
+}
+
+///Checks that a value can be used as a BRAT type or attribute (these are space-delimited)
+fn brat_value<'a>(value: &'a str, element: &Element) -> Result<&'a str,FoliaError> {
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        Err(FoliaError::ValidationError(format!("Value '{}' of {} {} can not be expressed in BRAT as it is empty or contains whitespace", value, element.elementtype(), element.id().unwrap_or("(no ID)"))))
+    } else {
+        Ok(value)
+    }
+}
+
+impl Document {
+    ///Builds the text as used in BRAT: sentences on separate lines, an empty line between
+    ///paragraphs and words separated by a space unless they have ``space="no"``
+    fn brat_text(&self, textparameters: &TextParameters) -> Result<BratText,FoliaError> {
+        let mut text = String::new();
+        let mut length = 0;
+        let mut words: Vec<(ElementKey,usize,usize)> = Vec::new();
+        let mut previous: Option<(Option<ElementKey>,Option<ElementKey>,bool)> = None;
+        for word in self.select(Selector::elements().element(Cmp::Is(ElementType::Word)), Recursion::Always) {
+            let sentence = word.get_ancestors(ElementType::Sentence, Cmp::Any).next().and_then(|sentence| sentence.key());
+            let paragraph = word.get_ancestors(ElementType::Paragraph, Cmp::Any).next().and_then(|paragraph| paragraph.key());
+            let delimiter = match previous {
+                None => "",
+                Some((previous_sentence, previous_paragraph, space)) => {
+                    if previous_paragraph != paragraph {
+                        "\n\n"
+                    } else if previous_sentence != sentence {
+                        "\n"
+                    } else if space {
+                        " "
+                    } else {
+                        ""
+                    }
+                }
+            };
+            text.push_str(delimiter);
+            length += delimiter.len();
+            let wordtext = element_text(&word, textparameters)?;
+            let begin = length;
+            length += wordtext.chars().count();
+            text.push_str(&wordtext);
+            words.push((word.key().ok_or(FoliaError::InternalError("Word has no key".to_string()))?, begin, length));
+            previous = Some((sentence, paragraph, word.attrib(AttribType::SPACE) != Some(&Attribute::Space(false))));
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        Ok(BratText { text, words })
+    }
+
+    ///Exports the entities and relations (including those in span relations) of the document to
+    ///BRAT standoff format. Entities become ``T`` annotations, with character offsets computed from
+    ///the text (discontinuous entities have multiple fragments), their features become ``A``
+    ///annotations and relations between two entities become ``R`` annotations. Other relations can't
+    ///be expressed and are reported in ``BratDocument::skipped``. Classes and features containing
+    ///whitespace are rejected.
+    pub fn to_brat(&self, options: &BratOptions) -> Result<BratDocument,FoliaError> {
+        let brattext = self.brat_text(&options.textparameters)?;
+        let mut out = String::new();
+        let mut entity_ids: HashMap<ElementKey,String> = HashMap::new();
+        let mut attribute_count = 0;
+        for entity in self.select(Selector::elements().element(Cmp::Is(ElementType::Entity)), Recursion::Always) {
+            if !has_set(&entity, &options.entity_set) {
+                continue;
+            }
+            let mut indices: Vec<usize> = entity.span_words().iter().filter_map(|word| word.key().and_then(|key| brattext.word_index(key))).collect();
+            if indices.is_empty() {
+                continue;
+            }
+            indices.sort_unstable();
+            indices.dedup();
+            //consecutive words form a single fragment
+            let mut fragments: Vec<(usize,usize)> = Vec::new();
+            let mut previous_index: Option<usize> = None;
+            for index in indices {
+                let (_, begin, end) = brattext.words[index];
+                match (previous_index, fragments.last_mut()) {
+                    (Some(previous_index), Some(fragment)) if previous_index + 1 == index => fragment.1 = end,
+                    _ => fragments.push((begin, end)),
+                }
+                previous_index = Some(index);
+            }
+            let id = format!("T{}", entity_ids.len() + 1);
+            let offsets: Vec<String> = fragments.iter().map(|(begin, end)| format!("{} {}", begin, end)).collect();
+            let texts: Vec<String> = fragments.iter().map(|(begin, end)| brattext.slice(*begin, *end)).collect();
+            writeln!(out, "{}\t{} {}\t{}", id, brat_value(entity.class().unwrap_or("Entity"), &entity)?, offsets.join(";"), texts.join(" ")).expect("writing to string");
+            attribute_count += write_brat_attributes(&mut out, &entity, &id, attribute_count)?;
+            entity_ids.insert(entity.key().expect("entity key"), id);
+        }
+
+        let mut relation_count = 0;
+        let mut skipped: Vec<ElementKey> = Vec::new();
+        for relation in self.select(Selector::elements().element(Cmp::Is(ElementType::Relation)), Recursion::Always) {
+            if !has_set(&relation, &options.relation_set) {
+                continue;
+            }
+            //every reference has to be an exported entity, otherwise the relation is skipped
+            let arguments: Option<Vec<&String>> = relation.select(Selector::elements().element(Cmp::Is(ElementType::LinkReference)), Recursion::No).map(|xref| {
+                xref.elementdata().idref().and_then(|id| self.get_element_key_by_id(id)).and_then(|key| entity_ids.get(&key))
+            }).collect();
+            let mut arguments = arguments.unwrap_or_default();
+            if arguments.len() == 1 {
+                //a relation with a single reference is embedded in its source
+                if let Some(source) = relation.get_parent().and_then(|parent| parent.key()).and_then(|key| entity_ids.get(&key)) {
+                    arguments.insert(0, source);
+                }
+            }
+            if arguments.len() != 2 {
+                //only binary relations between exported entities can be expressed
+                skipped.extend(relation.key());
+                continue;
+            }
+            relation_count += 1;
+            let id = format!("R{}", relation_count);
+            writeln!(out, "{}\t{} Arg1:{} Arg2:{}", id, brat_value(relation.class().unwrap_or("Relation"), &relation)?, arguments[0], arguments[1]).expect("writing to string");
+            attribute_count += write_brat_attributes(&mut out, &relation, &id, attribute_count)?;
+        }
+        Ok(BratDocument {
+            text: brattext.text,
+            annotations: out,
+            skipped,
+        })
+    }
+
+    ///Adds the entities and relations from BRAT standoff annotations (the contents of a ``.ann``
+    ///file) to this (already tokenised) document. The character offsets are interpreted against the
+    ///text as exported by ``to_brat()``, entities cover all words that overlap with their fragments
+    ///and become span annotations, relations become relations (with a reference to each argument)
+    ///in the sentence of their first argument, attributes become features. Other annotations (events,
+    ///normalisations, notes) are ignored. Everything is checked before anything is added, so on
+    ///error the document is left untouched. Returns the keys of the added entities and relations.
+    pub fn annotate_brat(&mut self, data: &str, options: &BratOptions) -> Result<Vec<ElementKey>,FoliaError> {
+        let brattext = self.brat_text(&options.textparameters)?;
+        let textlength = brattext.text.chars().count();
+
+        //first collect everything, attributes refer to other annotations
+        let mut entities: Vec<(String,BratEntity)> = Vec::new();
+        let mut relations: Vec<(String,BratRelation)> = Vec::new();
+        let mut attributes: HashMap<String,Vec<(String,String)>> = HashMap::new();
+        for (linenr, line) in data.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let invalid = || FoliaError::ParseError(format!("Invalid BRAT annotation on line {}: {}", linenr + 1, line));
+            let id = fields[0];
+            let annotation = fields.get(1).ok_or_else(invalid)?;
+            if id.starts_with('T') {
+                let (class, offsets) = annotation.split_once(' ').ok_or_else(invalid)?;
+                let mut fragments: Vec<(usize,usize)> = Vec::new();
+                for fragment in offsets.split(';') {
+                    let (begin, end) = fragment.trim().split_once(' ').ok_or_else(invalid)?;
+                    let begin: usize = begin.parse().map_err(|_| invalid())?;
+                    let end: usize = end.parse().map_err(|_| invalid())?;
+                    if begin > end || end > textlength {
+                        return Err(FoliaError::ValidationError(format!("Offsets on line {} are out of range of the text", linenr + 1)));
+                    }
+                    fragments.push((begin, end));
+                }
+                entities.push((id.to_string(), BratEntity { class: class.to_string(), fragments, text: fields.get(2).map(|text| text.to_string()) }));
+            } else if id.starts_with('R') {
+                let mut parts = annotation.split(' ');
+                let class = parts.next().ok_or_else(invalid)?.to_string();
+                let mut arguments: Vec<String> = Vec::new();
+                for argument in parts {
+                    let (_role, target) = argument.split_once(':').ok_or_else(invalid)?;
+                    arguments.push(target.to_string());
+                }
+                relations.push((id.to_string(), BratRelation { class, arguments }));
+            } else if id.starts_with('A') || id.starts_with('M') {
+                let parts: Vec<&str> = annotation.split(' ').collect();
+                if parts.len() < 2 {
+                    return Err(invalid());
+                }
+                let value = parts.get(2).copied().unwrap_or("true");
+                attributes.entry(parts[1].to_string()).or_default().push((parts[0].to_string(), value.to_string()));
+            }
+        }
+
+        //resolve and check everything before adding anything, so that a failure leaves the document untouched
+        let mut entity_words: Vec<Vec<String>> = Vec::new();
+        for (bratid, entity) in entities.iter() {
+            let mut ids: Vec<String> = Vec::new();
+            for (begin, end) in entity.fragments.iter() {
+                for (key, word_begin, word_end) in brattext.words.iter() {
+                    if word_begin < end && word_end > begin {
+                        let id = self.get_element(*key).and_then(|word| word.id().map(|id| id.to_string()))
+                                    .ok_or(FoliaError::IncompleteError("Words must have an ID to be part of a span".to_string()))?;
+                        if !ids.contains(&id) {
+                            ids.push(id);
+                        }
+                    }
+                }
+            }
+            if ids.is_empty() {
+                return Err(FoliaError::ValidationError(format!("Entity {} does not cover any words", bratid)));
+            }
+            if let Some(text) = &entity.text {
+                let texts: Vec<String> = entity.fragments.iter().map(|(begin, end)| brattext.slice(*begin, *end)).collect();
+                if texts.join(" ") != *text {
+                    return Err(FoliaError::ValidationError(format!("Text of entity {} ('{}') does not match the text of the document at its offsets", bratid, text)));
+                }
+            }
+            entity_words.push(ids);
+        }
+        let spans: Vec<&[String]> = entity_words.iter().map(|ids| ids.as_slice()).collect();
+        self.check_spans_addable(ElementType::Entity, options.entity_set.as_deref(), options.processor.as_deref(), &spans)?;
+        //the parent of each relation: the sentence (or other structure) of the first word of its first argument
+        let mut relation_parents: Vec<ElementKey> = Vec::new();
+        for (bratid, relation) in relations.iter() {
+            let mut first_word: Option<&String> = None;
+            for argument in relation.arguments.iter() {
+                let index = entities.iter().position(|(entity_id, _)| entity_id == argument).ok_or(
+                    FoliaError::ValidationError(format!("Relation {} refers to an unknown entity: {}", bratid, argument))
+                )?;
+                first_word = first_word.or_else(|| entity_words[index].first());
+            }
+            let word = first_word.and_then(|id| self.get_element_by_id(id)).ok_or(
+                FoliaError::ValidationError(format!("Relation {} has no arguments", bratid))
+            )?;
+            relation_parents.push(word.get_ancestors(ElementType::Sentence, Cmp::Any).next().and_then(|sentence| sentence.key())
+                .or_else(|| word.get_parent().and_then(|parent| parent.key()))
+                .expect("word has a parent"));
+        }
+        if !relations.is_empty() {
+            self.check_declarable(AnnotationType::RELATION, &options.relation_set, options.processor.as_deref())?;
+        }
+
+        let mut keys: Vec<ElementKey> = Vec::new();
+        let mut entity_ids: HashMap<String,String> = HashMap::new();
+        for ((bratid, entity), ids) in entities.iter().zip(entity_words.iter()) {
+            let id = self.brat_element_id(bratid);
+            let mut element = ElementData::new(ElementType::Entity).with_attrib(Attribute::Id(id.clone())).with_attrib(Attribute::Class(entity.class.clone()));
+            if let Some(set) = &options.entity_set {
+                element.set_attrib(Attribute::Set(set.clone()));
+            }
+            if let Some(processor) = &options.processor {
+                element.set_attrib(Attribute::Processor(processor.clone()));
+            }
+            for (subset, class) in attributes.get(bratid).into_iter().flatten() {
+                element = element.with_feature(subset, class);
+            }
+            let spanids: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+            keys.push(self.annotate_span(element.with_span(&spanids))?);
+            entity_ids.insert(bratid.clone(), id);
+        }
+
+        for ((bratid, relation), parent_key) in relations.iter().zip(relation_parents) {
+            let mut element = ElementData::new(ElementType::Relation).with_attrib(Attribute::Id(self.brat_element_id(bratid))).with_attrib(Attribute::Class(relation.class.clone()));
+            if let Some(set) = &options.relation_set {
+                element.set_attrib(Attribute::Set(set.clone()));
+            }
+            if let Some(processor) = &options.processor {
+                element.set_attrib(Attribute::Processor(processor.clone()));
+            }
+            for (subset, class) in attributes.get(bratid).into_iter().flatten() {
+                element = element.with_feature(subset, class);
+            }
+            for argument in relation.arguments.iter() {
+                element = element.add_element(ElementData::new(ElementType::LinkReference).with_attribs(vec![
+                    Attribute::Idref(entity_ids[argument].clone()),
+                    Attribute::Type(ElementType::Entity.to_string()),
+                ]));
+            }
+            keys.push(self.annotate(parent_key, element)?);
+        }
+        Ok(keys)
+    }
+
+    ///Reads a BRAT ``.ann`` file and adds its annotations to the document, see ``annotate_brat()``
+    pub fn annotate_brat_file(&mut self, filename: &str, options: &BratOptions) -> Result<Vec<ElementKey>,FoliaError> {
+        let data = fs::read_to_string(filename)?;
+        self.annotate_brat(&data, options)
+    }
+
+    ///Returns a new, unused, ID for an element imported from BRAT
+    fn brat_element_id(&self, bratid: &str) -> String {
+        let id = format!("{}.brat.{}", self.id(), bratid);
+        let mut candidate = id.clone();
+        let mut n = 1;
+        while self.get_element_key_by_id(&candidate).is_some() {
+            n += 1;
+            candidate = format!("{}.{}", id, n);
+        }
+        candidate
+    }
+}
+
+///Writes the features of an annotation as BRAT attributes, returns the number of attributes written
+fn write_brat_attributes(out: &mut String, element: &Element, id: &str, offset: usize) -> Result<usize,FoliaError> {
+    let mut features: Vec<(String,Vec<String>)> = element.features().into_iter().collect();
+    features.sort();
+    let mut count = 0;
+    for (subset, classes) in features {
+        for class in classes {
+            count += 1;
+            if class == "true" {
+                //binary attribute
+                writeln!(out, "A{}\t{} {}", offset + count, brat_value(&subset, element)?, id).expect("writing to string");
+            } else {
+                writeln!(out, "A{}\t{} {} {}", offset + count, brat_value(&subset, element)?, id, brat_value(&class, element)?).expect("writing to string");
+            }
+        }
+    }
+    Ok(count)
+}
@@ -7,3 +7,9 @@ pub const GENERATOR: &str = "folia-rust";
 pub const DEFAULT_TEXT_SET: &str = "https://raw.githubusercontent.com/proycon/folia/master/setdefinitions/text.foliaset.ttl";
 pub const DEFAULT_PHON_SET: &str = "https://raw.githubusercontent.com/proycon/folia/master/setdefinitions/phon.foliaset.ttl";
 
+///Parses a version string (e.g. ``2.1.0``) into its major, minor and patch components, missing or
+///invalid components are interpreted as 0
+pub fn parse_version(version: &str) -> (u32,u32,u32) {
+    let mut parts = version.trim().split('.').map(|part| part.parse::<u32>().unwrap_or(0));
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
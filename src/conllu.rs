@@ -0,0 +1,403 @@
+use std::fmt::Write;
+use std::fs;
+
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::query::*;
+use crate::select::*;
+use crate::text::*;
+use crate::metadata::*;
+use crate::coreference::*;
+use crate::document::*;
+
+///The set used for UPOS (and FEATS) on import, if no other set is configured
+pub const UD_POS_SET: &str = "https://universaldependencies.org/u/pos/";
+///The set used for dependency relations on import, if no other set is configured
+pub const UD_DEPREL_SET: &str = "https://universaldependencies.org/u/dep/";
+
+#[derive(Clone,Default)]
+///Determines which sets are mapped to which CoNLL-U columns. If no set is configured for UPOS
+///(and FEATS) then ``UD_POS_SET`` is used, XPOS is left empty if no set is configured. For the
+///other columns the first annotation of the right type is used regardless of its set.
+pub struct ConlluOptions {
+    ///The set of the part-of-speech annotation that provides UPOS and FEATS (defaults to ``UD_POS_SET``)
+    pub upos_set: Option<String>,
+    ///The set of the part-of-speech annotation that provides XPOS
+    pub xpos_set: Option<String>,
+    ///The set of the lemma annotation that provides LEMMA
+    pub lemma_set: Option<String>,
+    ///The set of the dependency annotation that provides HEAD and DEPREL
+    pub dependency_set: Option<String>,
+    ///Determines how the text of words (FORM) and sentences is obtained
+    pub textparameters: TextParameters,
+}
+
+impl ConlluOptions {
+    pub fn with_upos_set(mut self, set: &str) -> Self {
+        self.upos_set = Some(set.to_string());
+        self
+    }
+
+    pub fn with_xpos_set(mut self, set: &str) -> Self {
+        self.xpos_set = Some(set.to_string());
+        self
+    }
+
+    pub fn with_lemma_set(mut self, set: &str) -> Self {
+        self.lemma_set = Some(set.to_string());
+        self
+    }
+
+    pub fn with_dependency_set(mut self, set: &str) -> Self {
+        self.dependency_set = Some(set.to_string());
+        self
+    }
+
+    pub fn with_textparameters(mut self, textparameters: TextParameters) -> Self {
+        self.textparameters = textparameters;
+        self
+    }
+}
+
+fn set_cmp(set: &Option<String>) -> Cmp<String> {
+    match set {
+        Some(set) => Cmp::Is(set.clone()),
+        None => Cmp::Any,
+    }
+}
+
+///Returns the value for a CoNLL-U column, empty values and whitespace are not allowed
+fn conllu_value(value: Option<&str>) -> String {
+    match value {
+        Some(value) if !value.is_empty() => value.replace(['\t', '\n'], " "),
+        _ => "_".to_string(),
+    }
+}
+
+impl<'a> Element<'a> {
+    ///Returns the dependency of the configured set in which this word is the dependent, if any
+    fn conllu_dependency(&self, options: &ConlluOptions) -> Option<Element<'a>> {
+        self.dependencies_by_role(ElementType::DependencyDependent).into_iter().find(|dependency| {
+            match &options.dependency_set {
+                Some(set) => dependency.set() == Some(set.as_str()),
+                None => true,
+            }
+        })
+    }
+
+    ///Returns the text of a word for the FORM column, hidden words have no text of their own in
+    ///``text()`` so their text content is used directly
+    fn conllu_form(&self, options: &ConlluOptions) -> Result<String,FoliaError> {
+        if self.elementtype() == ElementType::Hiddenword {
+            let doc = self.document().ok_or(FoliaError::KeyError("Element has no associated document".to_string()))?;
+            let (set, textclass) = doc.text_keys(&options.textparameters)?;
+            match self.get_textcontent(set, textclass) {
+                Some(textcontent) => textcontent.text(&options.textparameters),
+                None => Err(FoliaError::NoTextError("No such text".to_string())),
+            }
+        } else {
+            self.text(&options.textparameters)
+        }
+    }
+
+    ///Serialises this element (usually a sentence) to CoNLL-U, all words under it form a single
+    ///sentence block (ending in an empty line). A word that is directly followed by hidden words
+    ///is a multiword token, the hidden words are its syntactic words.
+    pub fn to_conllu(&self, options: &ConlluOptions) -> Result<String,FoliaError> {
+        let mut out = String::new();
+        let tokens: Vec<Element> = self.select(Selector::elements().elementgroup(Cmp::Is(ElementGroup::Structure)), Recursion::Always)
+                                      .map(|item| item.element)
+                                      .filter(|element| element.elementtype() == ElementType::Word || element.elementtype() == ElementType::Hiddenword)
+                                      .collect();
+
+        //multiword tokens, indexed by their first word: (index of the last word, token)
+        let mut words: Vec<Element> = Vec::new();
+        let mut multiwords: Vec<Option<(usize,Element)>> = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if token.elementtype() == ElementType::Word {
+                let hiddenwords = tokens[i+1..].iter().take_while(|next| next.elementtype() == ElementType::Hiddenword && next.parent_key() == token.parent_key()).count();
+                if hiddenwords > 0 {
+                    multiwords.push(Some((words.len() + hiddenwords - 1, *token)));
+                    continue;
+                }
+            }
+            if multiwords.len() == words.len() {
+                multiwords.push(None);
+            }
+            words.push(*token);
+        }
+        let heads: Vec<Option<Element>> = words.iter().map(|word| word.conllu_dependency(options)).collect();
+        let upos_set = options.upos_set.as_deref().unwrap_or(UD_POS_SET);
+        let mut multiword_end: Option<usize> = None;
+
+        if let Some(id) = self.id() {
+            writeln!(out, "# sent_id = {}", id).expect("writing to string");
+        }
+        if let Ok(text) = self.text(&options.textparameters) {
+            if !text.is_empty() {
+                writeln!(out, "# text = {}", text.replace('\n', " ")).expect("writing to string");
+            }
+        }
+        for (i, (word, dependency)) in words.iter().zip(heads.iter()).enumerate() {
+            let form = word.conllu_form(options)?;
+            let lemma = word.get_annotation(AnnotationType::LEMMA, set_cmp(&options.lemma_set), Recursion::No);
+            let upos = word.get_annotation(AnnotationType::POS, Cmp::Is(upos_set.to_string()), Recursion::No);
+            let xpos = match &options.xpos_set {
+                Some(set) => word.get_annotation(AnnotationType::POS, Cmp::Is(set.clone()), Recursion::No),
+                None => None,
+            };
+            let feats: String = match &upos {
+                Some(upos) => {
+                    let mut features: Vec<(String,Vec<String>)> = upos.features().into_iter().collect();
+                    features.sort_by_key(|(name, _)| name.to_lowercase());
+                    features.iter().map(|(name, values)| format!("{}={}", name, values.join(","))).collect::<Vec<String>>().join("|")
+                },
+                None => String::new(),
+            };
+            let (head, deprel) = match dependency {
+                Some(dependency) => {
+                    //a dependency without a head is the root
+                    let head = match dependency.role_words(ElementType::Headspan).into_iter().next() {
+                        Some(head) => words.iter().position(|word| *word == head).map(|index| (index + 1).to_string()),
+                        None => Some("0".to_string()),
+                    };
+                    (head, dependency.class().map(|s| s.to_string()))
+                },
+                None => (None, None),
+            };
+            if let Some((last, token)) = &multiwords[i] {
+                let misc = match token.attrib(AttribType::SPACE) {
+                    Some(Attribute::Space(false)) => "SpaceAfter=No",
+                    _ => "_",
+                };
+                writeln!(out, "{}-{}\t{}\t_\t_\t_\t_\t_\t_\t_\t{}", i + 1, last + 1, conllu_value(Some(token.text(&options.textparameters)?.as_str())), misc).expect("writing to string");
+                multiword_end = Some(*last);
+            }
+            //spacing is expressed on the multiword token rather than on the words it consists of
+            let misc = match word.attrib(AttribType::SPACE) {
+                Some(Attribute::Space(false)) if multiword_end.map(|end| i > end).unwrap_or(true) => "SpaceAfter=No",
+                _ => "_",
+            };
+            writeln!(out, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t_\t{}",
+                i + 1,
+                conllu_value(Some(form.as_str())),
+                conllu_value(lemma.as_ref().and_then(|lemma| lemma.class())),
+                conllu_value(upos.as_ref().and_then(|upos| upos.class())),
+                conllu_value(xpos.as_ref().and_then(|xpos| xpos.class())),
+                conllu_value(Some(feats.as_str())),
+                conllu_value(head.as_deref()),
+                conllu_value(deprel.as_deref()),
+                misc
+            ).expect("writing to string");
+        }
+        out.push('\n');
+        Ok(out)
+    }
+}
+
+impl Document {
+    ///Serialises the document to CoNLL-U, with one sentence block per sentence
+    pub fn to_conllu(&self, options: &ConlluOptions) -> Result<String,FoliaError> {
+        let mut out = String::new();
+        for sentence in self.select(Selector::elements().element(Cmp::Is(ElementType::Sentence)), Recursion::Always) {
+            out.push_str(&sentence.to_conllu(options)?);
+        }
+        Ok(out)
+    }
+
+    ///Builds a new document from CoNLL-U data. Each sentence becomes a sentence with words and
+    ///their POS, features, lemma and dependency annotations (the root becomes a dependency without
+    ///a head). A multiword token becomes a word with the surface form, directly followed by hidden
+    ///words for the syntactic words it consists of; these carry the annotations. Sentence IDs must be unique. XPOS is
+    ///only imported if an XPOS set is configured, empty nodes are skipped. Declarations are made
+    ///automatically, if no processor is passed in the properties then this library is used as
+    ///the generating processor.
+    pub fn from_conllu(id: &str, data: &str, options: &ConlluOptions, properties: DocumentProperties) -> Result<Self,FoliaError> {
+        let properties = if properties.processor.is_none() {
+            properties.with_processor(Processor::this_library())
+        } else {
+            properties
+        };
+        let mut doc = Document::new(id, properties)?;
+        doc.declare(AnnotationType::TOKEN, &None, &None, &None)?;
+        doc.declare(AnnotationType::SENTENCE, &None, &None, &None)?;
+        let body_key: ElementKey = 0;
+        let mut parent_key = body_key;
+        let mut sentence_count = 0;
+        let mut paragraph_count = 0;
+        let mut block: Vec<(usize,&str)> = Vec::new();
+        for (linenr, line) in data.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                if !block.is_empty() {
+                    sentence_count += 1;
+                    doc.add_conllu_sentence(&mut parent_key, body_key, &block, sentence_count, &mut paragraph_count, options)?;
+                    block.clear();
+                }
+            } else {
+                block.push((linenr + 1, line));
+            }
+        }
+        if !block.is_empty() {
+            sentence_count += 1;
+            doc.add_conllu_sentence(&mut parent_key, body_key, &block, sentence_count, &mut paragraph_count, options)?;
+        }
+        Ok(doc)
+    }
+
+    ///Builds a new document from a CoNLL-U file, see ``from_conllu()``
+    pub fn from_conllu_file(id: &str, filename: &str, options: &ConlluOptions, properties: DocumentProperties) -> Result<Self,FoliaError> {
+        let data = fs::read_to_string(filename)?;
+        Self::from_conllu(id, &data, options, properties)
+    }
+
+    ///Adds a single CoNLL-U sentence block (as numbered lines) to the document
+    fn add_conllu_sentence(&mut self, parent_key: &mut ElementKey, body_key: ElementKey, block: &[(usize,&str)], sentence_count: usize, paragraph_count: &mut usize, options: &ConlluOptions) -> Result<(),FoliaError> {
+        let mut sentence_id: Option<String> = None;
+        let mut tokens: Vec<(usize,Vec<&str>)> = Vec::new();
+        for (linenr, line) in block.iter() {
+            if let Some(comment) = line.strip_prefix('#') {
+                let comment = comment.trim();
+                if comment.starts_with("newpar") {
+                    *paragraph_count += 1;
+                    self.declare(AnnotationType::PARAGRAPH, &None, &None, &None)?;
+                    let paragraph = ElementData::new(ElementType::Paragraph).with_attrib(Attribute::Id(format!("{}.p.{}", self.id(), paragraph_count)));
+                    *parent_key = self.add_element_to(body_key, paragraph)?;
+                } else if let Some(value) = comment.strip_prefix("sent_id") {
+                    let value = value.trim_start().trim_start_matches('=').trim();
+                    //sentence IDs are only retained if they are valid XML IDs
+                    if value.starts_with(|c: char| c.is_alphabetic() || c == '_') && value.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.') {
+                        sentence_id = Some(value.to_string());
+                    }
+                }
+            } else {
+                let columns: Vec<&str> = line.split('\t').collect();
+                if columns.len() != 10 {
+                    return Err(FoliaError::ParseError(format!("CoNLL-U line {} has {} columns, expected 10", linenr, columns.len())));
+                }
+                tokens.push((*linenr, columns));
+            }
+        }
+        let sentence_id = sentence_id.unwrap_or_else(|| format!("{}.s.{}", self.id(), sentence_count));
+        if self.get_element_key_by_id(&sentence_id).is_some() {
+            return Err(FoliaError::ValidationError(format!("Duplicate sentence ID in CoNLL-U data: {}", sentence_id)));
+        }
+        let sentence_key = self.add_element_to(*parent_key, ElementData::new(ElementType::Sentence).with_attrib(Attribute::Id(sentence_id.clone())))?;
+
+        //first pass: add the words, the syntactic words of a multiword token become hidden words
+        //following the word for the token itself
+        let mut word_keys: Vec<ElementKey> = Vec::new();
+        let mut word_ids: Vec<String> = Vec::new();
+        let mut multiword_end: usize = 0;
+        for (linenr, columns) in tokens.iter() {
+            let spaceafter = !columns[9].split('|').any(|item| item == "SpaceAfter=No");
+            if let Some((begin, end)) = columns[0].split_once('-') {
+                let (begin, end) = match (begin.parse::<usize>(), end.parse::<usize>()) {
+                    (Ok(begin), Ok(end)) if begin == word_keys.len() + 1 && begin <= end => (begin, end),
+                    _ => return Err(FoliaError::ParseError(format!("Invalid multiword token range on CoNLL-U line {}: {}", linenr, columns[0])))
+                };
+                if multiword_end == 0 {
+                    self.declare(AnnotationType::HIDDENTOKEN, &None, &None, &None)?;
+                }
+                let mut word = ElementData::new(ElementType::Word).with_attrib(Attribute::Id(format!("{}.w.{}-{}", sentence_id, begin, end))).with_text(columns[1].to_string());
+                if !spaceafter {
+                    word.set_attrib(Attribute::Space(false));
+                }
+                self.add_element_to(sentence_key, word)?;
+                multiword_end = end;
+            } else if columns[0].contains('.') {
+                //empty nodes are not supported
+                continue;
+            } else {
+                let index: usize = columns[0].parse().map_err(|_| FoliaError::ParseError(format!("Invalid word ID on CoNLL-U line {}: {}", linenr, columns[0])))?;
+                if index != word_keys.len() + 1 {
+                    return Err(FoliaError::ParseError(format!("Unexpected word ID on CoNLL-U line {}: {}", linenr, columns[0])));
+                }
+                let word_id = format!("{}.w.{}", sentence_id, index);
+                //inside a multiword token, spacing is determined by the token
+                let word = if index <= multiword_end {
+                    ElementData::new(ElementType::Hiddenword)
+                } else if !spaceafter {
+                    ElementData::new(ElementType::Word).with_attrib(Attribute::Space(false))
+                } else {
+                    ElementData::new(ElementType::Word)
+                };
+                let word = word.with_attrib(Attribute::Id(word_id.clone())).with_text(columns[1].to_string());
+                word_keys.push(self.add_element_to(sentence_key, word)?);
+                word_ids.push(word_id);
+            }
+        }
+        if multiword_end > word_keys.len() {
+            return Err(FoliaError::ParseError(format!("Multiword token in sentence {} ends beyond the last word", sentence_id)));
+        }
+
+        //second pass: add the annotations
+        let upos_set = options.upos_set.as_deref().unwrap_or(UD_POS_SET);
+        let dependency_set = options.dependency_set.as_deref().unwrap_or(UD_DEPREL_SET);
+        let mut index = 0;
+        for (linenr, columns) in tokens.iter() {
+            if columns[0].contains('-') || columns[0].contains('.') {
+                continue;
+            }
+            let word_key = word_keys[index];
+            index += 1;
+            if columns[2] != "_" {
+                let mut lemma = ElementData::new(ElementType::LemmaAnnotation).with_attrib(Attribute::Class(columns[2].to_string()));
+                if let Some(set) = &options.lemma_set {
+                    lemma.set_attrib(Attribute::Set(set.clone()));
+                }
+                self.annotate(word_key, lemma)?;
+            }
+            //features are kept on a part-of-speech annotation without a class if there is no UPOS
+            if columns[3] != "_" || columns[5] != "_" {
+                let mut pos = ElementData::new(ElementType::PosAnnotation).with_attrib(Attribute::Set(upos_set.to_string()));
+                if columns[3] != "_" {
+                    pos.set_attrib(Attribute::Class(columns[3].to_string()));
+                }
+                if columns[5] != "_" {
+                    for feature in columns[5].split('|') {
+                        let (name, values) = feature.split_once('=').ok_or(FoliaError::ParseError(format!("Invalid feature on CoNLL-U line {}: {}", linenr, feature)))?;
+                        for value in values.split(',') {
+                            pos = pos.with_feature(name, value);
+                        }
+                    }
+                }
+                self.annotate(word_key, pos)?;
+            }
+            if let (Some(set), true) = (&options.xpos_set, columns[4] != "_") {
+                self.annotate(word_key, ElementData::new(ElementType::PosAnnotation)
+                                .with_attrib(Attribute::Set(set.clone()))
+                                .with_attrib(Attribute::Class(columns[4].to_string())))?;
+            }
+        }
+        let mut index = 0;
+        for (linenr, columns) in tokens.iter() {
+            if columns[0].contains('-') || columns[0].contains('.') {
+                continue;
+            }
+            let word_key = word_keys[index];
+            index += 1;
+            if columns[6] == "_" || (columns[6] == "0" && columns[7] == "_") {
+                continue;
+            }
+            let mut dependency = ElementData::new(ElementType::Dependency).with_attrib(Attribute::Set(dependency_set.to_string()));
+            //an unknown relation is a dependency without a class
+            if columns[7] != "_" {
+                dependency.set_attrib(Attribute::Class(columns[7].to_string()));
+            }
+            //the root is a dependency without a head
+            if columns[6] != "0" {
+                let head_id = columns[6].parse::<usize>().ok().and_then(|head| head.checked_sub(1)).and_then(|head| word_ids.get(head)).ok_or(
+                    FoliaError::ParseError(format!("Invalid head on CoNLL-U line {}: {}", linenr, columns[6]))
+                )?;
+                dependency = dependency.with_role(ElementType::Headspan, &[head_id.as_str()]);
+            }
+            let dependency = dependency.with_role(ElementType::DependencyDependent, &[word_ids[index - 1].as_str()]);
+            self.annotate(word_key, dependency)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::query::*;
+use crate::select::*;
+use crate::document::*;
+
+impl<'a> Element<'a> {
+    ///Returns the coreference chains that mention this word
+    pub fn coreference_chains(&self) -> Vec<Element<'a>> {
+        let mut chains: Vec<Element<'a>> = Vec::new();
+        if let Some(document) = self.document {
+            for item in self.data.data.iter() {
+                if let DataType::SpanReference(span_key) = item {
+                    if let Some(span) = document.get_element(*span_key) {
+                        if span.elementtype() == ElementType::CoreferenceChain && !chains.contains(&span) {
+                            chains.push(span);
+                        }
+                    }
+                }
+            }
+        }
+        chains
+    }
+
+    ///Returns the mentions (coreference links) of a coreference chain, in order
+    pub fn mentions(&self) -> Vec<Element<'a>> {
+        let mut mentions: Vec<Element<'a>> = Vec::new();
+        if let Some(document) = self.document {
+            for item in self.data.data.iter() {
+                if let DataType::Element(key) = item {
+                    if let Some(child) = document.get_element(*key) {
+                        if child.elementtype() == ElementType::CoreferenceLink {
+                            mentions.push(child);
+                        }
+                    }
+                }
+            }
+        }
+        mentions
+    }
+
+    ///Returns the words referenced by a span or span role (e.g. a coreference link), in order
+    pub fn span_words(&self) -> Vec<Element<'a>> {
+        let mut words: Vec<Element<'a>> = Vec::new();
+        if let Some(document) = self.document {
+            for item in self.data.data.iter() {
+                if let DataType::Element(key) = item {
+                    if let Some(child) = document.get_element(*key) {
+                        if child.elementtype() == ElementType::WordReference {
+                            if let Some(Attribute::Idref(id)) = child.data.attrib(AttribType::IDREF) {
+                                if let Some(word) = document.get_element_by_id(id) {
+                                    words.push(word);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        words
+    }
+
+    ///Returns all other mentions (coreference links) that corefer with this word, i.e. the
+    ///mentions in the chains this word takes part in, except those that include the word itself
+    pub fn coreferent_mentions(&self) -> Vec<Element<'a>> {
+        let mut mentions: Vec<Element<'a>> = Vec::new();
+        for chain in self.coreference_chains() {
+            for mention in chain.mentions() {
+                if !mention.span_words().contains(self) {
+                    mentions.push(mention);
+                }
+            }
+        }
+        mentions
+    }
+}
+
+impl Document {
+    ///Returns all coreference chains in the document, of the specified set
+    pub fn coreference_chains(&self, set: Cmp<String>) -> SelectElementsIterator<'_> {
+        let query = Query::select().element(Cmp::Is(ElementType::CoreferenceChain)).set(set);
+        self.select(Selector::from_query(self, &query).expect("Compiling query for coreference_chains()"), Recursion::Always)
+    }
+
+    ///High-level method to add a coreference chain. Each mention is specified as a list of word
+    ///IDs. The chain is added through ``annotate()``, so it ends up in the coreference layer of
+    ///the closest common ancestor that can hold one (which is created if needed). If no set is
+    ///provided, the default set is used.
+    pub fn add_coreference_chain(&mut self, mentions: &[&[&str]], set: Option<&str>) -> Result<ElementKey,FoliaError> {
+        let first_key = mentions.iter().flat_map(|mention| mention.iter()).next().and_then(|id| self.get_element_key_by_id(id)).ok_or(
+            FoliaError::IncompleteError("Coreference chain must have at least one mention with an existing word".to_string())
+        )?;
+        for id in mentions.iter().flat_map(|mention| mention.iter()) {
+            if self.get_element_key_by_id(id).is_none() {
+                return Err(FoliaError::IncompleteError(format!("Mention refers to a non-existing element: {}", id)));
+            }
+        }
+        let mut chain = ElementData::new(ElementType::CoreferenceChain);
+        if let Some(set) = set {
+            chain.set_attrib(Attribute::Set(set.to_string()));
+        }
+        for mention in mentions.iter() {
+            chain = chain.with_role(ElementType::CoreferenceLink, mention);
+        }
+        self.annotate(first_key, chain)
+    }
+}
@@ -0,0 +1,198 @@
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::elementstore::*;
+use crate::store::*;
+use crate::query::*;
+use crate::select::*;
+use crate::document::*;
+
+#[derive(Clone,Debug,PartialEq)]
+///A node in a dependency tree: a word along with its head and the dependency relation
+pub struct DependencyNode {
+    ///The key of the word
+    pub word: ElementKey,
+    ///The key of the head word, ``None`` for the root(s) of the tree
+    pub head: Option<ElementKey>,
+    ///The dependency relation (the class of the dependency)
+    pub deprel: Option<String>,
+    ///The key of the dependency element
+    pub dependency: Option<ElementKey>,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+///A view on the dependency relations in a structure element (usually a sentence), obtained
+///through ``Element::dependency_tree()``. Nodes are in word order.
+pub struct DependencyTree {
+    nodes: Vec<DependencyNode>,
+}
+
+impl DependencyTree {
+    ///Returns all nodes, in word order
+    pub fn nodes(&self) -> &[DependencyNode] {
+        &self.nodes
+    }
+
+    ///Returns the node for the specified word
+    pub fn node(&self, word: ElementKey) -> Option<&DependencyNode> {
+        self.nodes.iter().find(|node| node.word == word)
+    }
+
+    ///Returns the head of the specified word
+    pub fn head(&self, word: ElementKey) -> Option<ElementKey> {
+        self.node(word).and_then(|node| node.head)
+    }
+
+    ///Returns the dependency relation of the specified word
+    pub fn deprel(&self, word: ElementKey) -> Option<&str> {
+        self.node(word).and_then(|node| node.deprel.as_deref())
+    }
+
+    ///Returns the dependents of the specified word, in word order
+    pub fn dependents(&self, word: ElementKey) -> Vec<ElementKey> {
+        self.nodes.iter().filter(|node| node.head == Some(word)).map(|node| node.word).collect()
+    }
+
+    ///Returns all words that have no head
+    pub fn roots(&self) -> Vec<ElementKey> {
+        self.nodes.iter().filter(|node| node.head.is_none()).map(|node| node.word).collect()
+    }
+
+    ///Returns the root of the tree, i.e. the first word that has no head but does have dependents
+    pub fn root(&self) -> Option<ElementKey> {
+        self.roots().into_iter().find(|word| !self.dependents(*word).is_empty())
+    }
+
+    ///Returns the path from the specified word up to the root (both inclusive)
+    pub fn path_to_root(&self, word: ElementKey) -> Result<Vec<ElementKey>,FoliaError> {
+        let mut path: Vec<ElementKey> = vec!(word);
+        let mut current = word;
+        while let Some(head) = self.head(current) {
+            if path.contains(&head) {
+                return Err(FoliaError::ValidationError(format!("Cycle in dependency tree at element {}", head)));
+            }
+            path.push(head);
+            current = head;
+        }
+        Ok(path)
+    }
+}
+
+impl<'a> Element<'a> {
+    ///Returns the dependency in which this word is the dependent, if any
+    pub fn dependency(&self) -> Option<Element<'a>> {
+        self.dependencies_by_role(ElementType::DependencyDependent).into_iter().next()
+    }
+
+    ///Returns the head word of this word, if any
+    pub fn dependency_head(&self) -> Option<Element<'a>> {
+        let dependency = self.dependency()?;
+        dependency.role_words(ElementType::Headspan).into_iter().next()
+    }
+
+    ///Returns the dependency relation (i.e. the class of the dependency) of this word, if any
+    pub fn deprel(&self) -> Option<&'a str> {
+        let document = self.document?;
+        let dependency = self.dependency()?.data;
+        let declaration = document.get_declaration(dependency.declaration_key().ok()??)?;
+        declaration.get_class(dependency.class_key().ok()??)
+    }
+
+    ///Returns all words that have this word as their head, in the order of the dependencies
+    pub fn dependents(&self) -> Vec<Element<'a>> {
+        self.dependencies_by_role(ElementType::Headspan).into_iter().flat_map(|dependency| {
+            dependency.role_words(ElementType::DependencyDependent)
+        }).collect()
+    }
+
+    ///Builds a dependency tree over all words in this element (usually a sentence)
+    pub fn dependency_tree(&self) -> Result<DependencyTree,FoliaError> {
+        let document = self.document.ok_or(FoliaError::InternalError("Element has no associated document".to_string()))?;
+        let mut nodes: Vec<DependencyNode> = Vec::new();
+        let selector = Selector::elements().element(Cmp::Is(ElementType::Word));
+        for word in self.select(selector, Recursion::Always) {
+            let word_key = word.key().ok_or(FoliaError::InternalError("Word has no key".to_string()))?;
+            let word = document.get_element(word_key).expect("getting word");
+            let dependency = word.dependency();
+            nodes.push(DependencyNode {
+                word: word_key,
+                head: word.dependency_head().and_then(|head| head.key()),
+                deprel: word.deprel().map(|s| s.to_string()),
+                dependency: dependency.and_then(|dependency| dependency.key()),
+            });
+        }
+        Ok(DependencyTree { nodes })
+    }
+
+    ///Returns the dependencies that reference this word from the specified role
+    pub(crate) fn dependencies_by_role(&self, roletype: ElementType) -> Vec<Element<'a>> {
+        let mut dependencies: Vec<Element<'a>> = Vec::new();
+        if let (Some(document), Some(id)) = (self.document, self.data.id()) {
+            for item in self.data.data.iter() {
+                if let DataType::SpanReference(span_key) = item {
+                    if let Some(span) = document.get_element(*span_key) {
+                        if span.elementtype() == ElementType::Dependency && span.role_ids(roletype).contains(&id) && !dependencies.iter().any(|d| d.key() == span.key()) {
+                            dependencies.push(span);
+                        }
+                    }
+                }
+            }
+        }
+        dependencies
+    }
+
+    ///Returns the IDs referenced by the wrefs in the roles of the specified type
+    fn role_ids(&self, roletype: ElementType) -> Vec<&'a str> {
+        let mut ids: Vec<&'a str> = Vec::new();
+        if let Some(document) = self.document {
+            for item in self.data.data.iter() {
+                if let DataType::Element(role_key) = item {
+                    if let Some(role) = document.get_elementdata(*role_key) {
+                        if role.elementtype == roletype {
+                            for item in role.data.iter() {
+                                if let DataType::Element(wref_key) = item {
+                                    if let Some(Attribute::Idref(id)) = document.get_elementdata(*wref_key).and_then(|wref| wref.attrib(AttribType::IDREF)) {
+                                        ids.push(id.as_str());
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ids
+    }
+
+    ///Returns the words referenced by the roles of the specified type
+    pub(crate) fn role_words(&self, roletype: ElementType) -> Vec<Element<'a>> {
+        match self.document {
+            Some(document) => self.role_ids(roletype).into_iter().filter_map(|id| document.get_element_by_id(id)).collect(),
+            None => Vec::new()
+        }
+    }
+}
+
+impl Document {
+    ///High-level method to add a dependency relation between two words. It builds the
+    ///dependency along with its head and dependent roles and adds it through ``annotate()``,
+    ///so the dependency layer is created if needed. If no set is provided, the default set is used.
+    pub fn annotate_dependency(&mut self, head_key: ElementKey, dependent_key: ElementKey, deprel: &str, set: Option<&str>) -> Result<ElementKey,FoliaError> {
+        let head_id = self.get_elementdata(head_key).and_then(|head| head.id()).ok_or(
+            FoliaError::IncompleteError("The head of a dependency must have an ID".to_string())
+        )?.to_string();
+        let dependent_id = self.get_elementdata(dependent_key).and_then(|dependent| dependent.id()).ok_or(
+            FoliaError::IncompleteError("The dependent of a dependency must have an ID".to_string())
+        )?.to_string();
+        let mut dependency = ElementData::new(ElementType::Dependency).with_attrib(Attribute::Class(deprel.to_string()));
+        if let Some(set) = set {
+            dependency.set_attrib(Attribute::Set(set.to_string()));
+        }
+        let dependency = dependency
+            .with_role(ElementType::Headspan, &[head_id.as_str()])
+            .with_role(ElementType::DependencyDependent, &[dependent_id.as_str()]);
+        self.annotate(dependent_key, dependency)
+    }
+}
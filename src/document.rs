@@ -45,7 +45,7 @@ pub struct Document {
     pub submetadata: HashMap<String,Metadata>,
     ///The curerntly active processor
     pub active_processor: Option<ProcKey>,
-
+    ///Declare annotation types automatically when they are used (see ``DocumentProperties::with_autodeclare()``)
     pub autodeclare: bool,
 }
 
@@ -75,6 +75,11 @@ impl DocumentProperties {
         self.bodytype = bodytype;
         self
     }
+    ///Enables or disables automatic declaration of annotation types. When disabled, adding an
+    ///element that carries a set, class or processor of an annotation type (and set) that has not been
+    ///declared explicitly fails with an ``EncodeError``. This also applies when parsing FoLiA v2
+    ///documents, so undeclared annotations there are rejected. Elements without any of these
+    ///attributes are accepted, and FoLiA v1 documents are always declared automatically.
     pub fn with_autodeclare(mut self, value: bool) -> DocumentProperties {
         self.autodeclare = value;
         self
@@ -155,6 +160,29 @@ impl Document {
     ///Returns the ID of the document
     pub fn id(&self) -> &str { &self.id }
 
+    ///Returns the type of the body of this document (text or speech)
+    pub fn bodytype(&self) -> BodyType {
+        match self.get_elementdata(0).map(|body| body.elementtype) {
+            Some(ElementType::Speech) => BodyType::Speech,
+            _ => BodyType::Text,
+        }
+    }
+
+    ///Loads the external metadata files referenced by ``src`` (for the document metadata and any
+    ///submetadata). Relative paths are resolved against the directory of the document.
+    pub fn load_metadata_src(&mut self) -> Result<(),FoliaError> {
+        let basedir = self.filename.as_ref().and_then(|filename| Path::new(filename).parent().map(|p| p.to_path_buf()));
+        if self.metadata.src.is_some() {
+            self.metadata.load_src(basedir.as_deref())?;
+        }
+        for submetadata in self.submetadata.values_mut() {
+            if submetadata.src.is_some() {
+                submetadata.load_src(basedir.as_deref())?;
+            }
+        }
+        Ok(())
+    }
+
     ///Returns the filename associated with this document (i.e. the file from which it was loaded)
     pub fn filename(&self) -> Option<&str> { self.filename.as_ref().map(String::as_str) } //String::as_str equals  |x| &**x
 
@@ -172,8 +200,18 @@ impl Document {
     ///Add an element to the document (but the element will be an orphan unless it is the very
     ///first one, you may want to use ``add_element_to`` or ``annotate`` instead)
     pub fn add_element(&mut self, element: ElementData) -> Result<ElementKey, FoliaError> {
-        let (element,_) = self.add_children(element)?;
-        <Self as Store<ElementData,ElementKey>>::add(self, element, None)
+        let (element,added_subelements) = self.add_children(element)?;
+        let key = <Self as Store<ElementData,ElementKey>>::add(self, element, None)?;
+        self.add_features(key)?;
+        if let Some(added_subelements) = added_subelements {
+            for subchild_key in added_subelements.iter() {
+                if let Some(subchilddata) = self.get_mut_elementdata(*subchild_key) {
+                    subchilddata.set_parent_key(Some(key));
+                }
+            }
+            self.post_add_descendants(key)?;
+        }
+        Ok(key)
     }
 
     ///Add a declaration. It is strongly recommended to use ``declare()`` instead
@@ -199,15 +237,18 @@ impl Document {
         let (element,added_subelements) = self.add_children(element)?;
         match <Self as Store<ElementData,ElementKey>>::add(self, element, Some(parent_key)) {
             Ok(child_key) => {
+                self.add_features(child_key)?;
                 if let Some(added_subelements) = added_subelements {
                     for subchild_key in added_subelements.iter() {
                         if let Some(subchilddata) = self.get_mut_elementdata(*subchild_key) {
                             subchilddata.set_parent_key(Some(child_key));
                         }
-                        self.post_add(*subchild_key, None)?;
                     }
                 }
                 self.attach_element(parent_key, child_key)?;
+                //postprocessing is done only now that the whole subtree is attached, as it may need to
+                //consult ancestors (e.g. wrefs nested in span roles)
+                self.post_add_descendants(child_key)?;
                 self.post_add(child_key, None)?;
                 Ok(child_key)
             },
@@ -230,21 +271,25 @@ impl Document {
             let mut new_data: Vec<DataType> = Vec::new();
             for child in element.data {
                 if let DataType::AddElement(child_elementdata) = child {
+                    if child_elementdata.elementtype == ElementType::Feature {
+                        //features are kept back until the element itself is added (see add_features())
+                        new_data.push(DataType::AddElement(child_elementdata));
+                        continue;
+                    }
                     //first we do a recursion step to add the grandchildren, if any
                     let child_key = match self.add_children(child_elementdata) {
                         Ok((child_elementdata_new, Some(added_grandchildren))) => {
                             let child_key = <Self as Store<ElementData,ElementKey>>::add(self, child_elementdata_new, None)?;
+                            self.add_features(child_key)?;
                             for grandchild_key in added_grandchildren {
                                 if let Some(subchilddata) = self.get_mut_elementdata(grandchild_key) {
                                     subchilddata.set_parent_key(Some(child_key));
                                 }
-                                self.post_add(grandchild_key, None)?;
                             }
                             child_key
                         },
                         Ok((child_elementdata_new, None)) => {
-                            let child_key = <Self as Store<ElementData,ElementKey>>::add(self, child_elementdata_new, None)?;
-                            child_key
+                            <Self as Store<ElementData,ElementKey>>::add(self, child_elementdata_new, None)?
                         }
                         Err(e) => {
                             return Err(e);
@@ -263,6 +308,49 @@ impl Document {
         }
     }
 
+    ///Adds the hitherto 'unborn' features of an element that was just added. Features are encoded
+    ///in the context of their parent (they share its declaration), so they can only be added after it.
+    pub(crate) fn add_features(&mut self, key: ElementKey) -> Result<(),FoliaError> {
+        let has_features = self.get_elementdata(key).map(|elementdata| elementdata.data.iter().any(|item| {
+            matches!(item, DataType::AddElement(feature) if feature.elementtype == ElementType::Feature)
+        })).unwrap_or(false);
+        if has_features {
+            let data = std::mem::take(&mut self.get_mut_elementdata(key).expect("getting element").data);
+            let mut new_data: Vec<DataType> = Vec::with_capacity(data.len());
+            for item in data {
+                match item {
+                    DataType::AddElement(feature) if feature.elementtype == ElementType::Feature => {
+                        let feature_key = <Self as Store<ElementData,ElementKey>>::add(self, feature, Some(key))?;
+                        if let Some(featuredata) = self.get_mut_elementdata(feature_key) {
+                            featuredata.set_parent_key(Some(key));
+                        }
+                        new_data.push(DataType::Element(feature_key));
+                    },
+                    item => new_data.push(item)
+                }
+            }
+            self.get_mut_elementdata(key).expect("getting element").data = new_data;
+        }
+        Ok(())
+    }
+
+    ///Performs postprocessing for all descendants of the specified element (depth-first, children before their parents)
+    pub(crate) fn post_add_descendants(&mut self, key: ElementKey) -> Result<(),FoliaError> {
+        let children: Vec<ElementKey> = if let Some(elementdata) = self.get_elementdata(key) {
+            elementdata.data.iter().filter_map(|item| match item {
+                DataType::Element(child_key) => Some(*child_key),
+                _ => None
+            }).collect()
+        } else {
+            Vec::new()
+        };
+        for child_key in children {
+            self.post_add_descendants(child_key)?;
+            self.post_add(child_key, None)?;
+        }
+        Ok(())
+    }
+
     ///Adds the child element to the parent element, automatically takes care
     ///of removing the old parent (if any).
     pub fn attach_element(&mut self, parent_key: ElementKey, child_key: ElementKey) -> Result<(),FoliaError> {
@@ -456,28 +544,12 @@ impl Document {
     ///associated with the declaration.
     pub fn declare(&mut self, annotationtype: AnnotationType, set: &Option<String>, alias: &Option<String>, format: &Option<String>) -> Result<DecKey,FoliaError> {
         //first we simply check the index
-        if let Some(found_key) = <Self as Store<Declaration,DecKey>>::id_to_key(self,Declaration::index_id(annotationtype, &set.as_ref().map(String::as_str)  ).as_str()) {
+        if let Some(found_key) = <Self as Store<Declaration,DecKey>>::id_to_key(self,Declaration::index_id(annotationtype, &set.as_ref().map(String::as_str)).as_str()) {
             return Ok(found_key);
         }
 
         //If not found, we search for a default
-        let mut declaration_key: Option<DecKey> = None;
-        if let Some(default_key) = self.declarationstore.get_default_key(annotationtype) {
-            if let Some(declaration) = self.get_declaration(default_key) {
-                if set.is_some() {
-                    //there is an explicit set defined, only return the default if the sets are not
-                    //in conflict
-                    if let Some(declared_set) = &declaration.set {
-                        if *declared_set == *set.as_ref().unwrap() {
-                            declaration_key = Some(default_key);
-                        }
-                    }
-                } else {
-                    //no set defined, that means we inherit the default set
-                    declaration_key = Some(default_key);
-                }
-            }
-        }
+        let mut declaration_key: Option<DecKey> = self.find_default_declaration(annotationtype, set);
 
         if declaration_key.is_none() {
             //we have no defaults and add a new declaration
@@ -496,6 +568,26 @@ impl Document {
         Ok(declaration_key.expect("get_deckey"))
     }
 
+    ///Returns the key of the existing declaration that an annotation of the specified type and set
+    ///would use, if any
+    pub fn find_declaration(&self, annotationtype: AnnotationType, set: &Option<String>) -> Option<DecKey> {
+        <Self as Store<Declaration,DecKey>>::id_to_key(self,Declaration::index_id(annotationtype, &set.as_ref().map(String::as_str)).as_str())
+            .or_else(|| self.find_default_declaration(annotationtype, set))
+    }
+
+    ///Returns the key of the default declaration for the annotation type, if it is compatible with the set (if any)
+    fn find_default_declaration(&self, annotationtype: AnnotationType, set: &Option<String>) -> Option<DecKey> {
+        let default_key = self.declarationstore.get_default_key(annotationtype)?;
+        let declaration = self.get_declaration(default_key)?;
+        match set {
+            //there is an explicit set defined, only return the default if the sets are not in conflict
+            Some(set) if declaration.set.as_ref() == Some(set) => Some(default_key),
+            Some(_) => None,
+            //no set defined, that means we inherit the default set
+            None => Some(default_key),
+        }
+    }
+
     //************** High-level method for adding annotations ********************
 
     ///This is a high-level function that adds an annotation to an element, and does all necessary validation. It will simply call `add_element_to` for token annotation elements that fit within the scope and validate. For span annotation, it will create and find or create the proper annotation layer and insert the element there.
@@ -538,34 +630,25 @@ impl Document {
                 if span_keys.is_empty() {
                     Err(FoliaError::IncompleteError(format!("Span is empty, can not be added from a wrefable parent")))
                 } else {
-                    let query = Query::select().elementgroup(Cmp::Is(ElementGroup::Structure));
-                    let common_ancestors = self.common_ancestors(Selector::from_query(&self,&query).expect("selector"), &span_keys);
-                    for ancestor_key in common_ancestors.iter() {
-                        let mut suitable = false; //is the ancestor suitable to hold a layer according to the specification?
-                        if let Some(ancestor) = self.get_element(*ancestor_key) {
-                            let props = self.props(ancestor.elementtype());
-                            suitable = props.accepted_data.contains(&AcceptedData::AcceptElementGroup(ElementGroup::Layer)) || props.accepted_data.contains(&AcceptedData::AcceptElementType(layertype));
-                        };
-                        if suitable {
-                            let mut layer_key: Option<ElementKey> = self.get_layer_key(parent_key, element.elementtype.annotationtype().expect("annotation type"), set.as_ref().map(|s| s.as_str()) )?;
-                            if layer_key.is_none() {
-                                //no layer found yet, add a new one
-                                let layerdata = match set {
-                                    Some(set) => ElementData::new(layertype).with_attrib(Attribute::Set(set.clone())),
-                                    None => ElementData::new(layertype)
-                                };
-                                self.check_element_addable(*ancestor_key, &layerdata)?;
-                                match self.add_element_to(*ancestor_key, layerdata) {
-                                    Ok(key) => layer_key = Some(key),
-                                    Err(e) => return Err(e)
-                                }
+                    if let Some(ancestor_key) = self.span_layer_ancestor(layertype, &span_keys) {
+                        let mut layer_key: Option<ElementKey> = self.get_layer_key(ancestor_key, element.elementtype.annotationtype().expect("annotation type"), set.as_ref().map(|s| s.as_str()) )?;
+                        if layer_key.is_none() {
+                            //no layer found yet, add a new one
+                            let layerdata = match set {
+                                Some(set) => ElementData::new(layertype).with_attrib(Attribute::Set(set.clone())),
+                                None => ElementData::new(layertype)
                             };
-                            self.check_element_addable(layer_key.unwrap() , &element)?;
-                            //we only did one iteration, taking the closest common ancestor
-                            return self.add_element_to(layer_key.unwrap(), element);
-                        }
+                            self.check_element_addable(ancestor_key, &layerdata)?;
+                            match self.add_element_to(ancestor_key, layerdata) {
+                                Ok(key) => layer_key = Some(key),
+                                Err(e) => return Err(e)
+                            }
+                        };
+                        self.check_element_addable(layer_key.unwrap() , &element)?;
+                        self.add_element_to(layer_key.unwrap(), element)
+                    } else {
+                        Err(FoliaError::IncompleteError(format!("Unable to find suitable common ancestor to create annotation layer")))
                     }
-                    Err(FoliaError::IncompleteError(format!("Unable to find suitable common ancestor to create annotation layer")))
                 }
             } else if addspanfromstructure {
                 //invoked from the parent structure element that holds the layer (usually a sentence)
@@ -596,6 +679,71 @@ impl Document {
         }
     }
 
+    ///Returns the closest common structural ancestor of the spanned elements that may hold a
+    ///layer of the specified type, this is where ``annotate()`` places span annotations
+    pub(crate) fn span_layer_ancestor(&self, layertype: ElementType, span_keys: &[ElementKey]) -> Option<ElementKey> {
+        let query = Query::select().elementgroup(Cmp::Is(ElementGroup::Structure));
+        let common_ancestors = self.common_ancestors(Selector::from_query(self,&query).expect("selector"), span_keys);
+        common_ancestors.into_iter().find(|ancestor_key| {
+            //is the ancestor suitable to hold a layer according to the specification?
+            if let Some(ancestor) = self.get_element(*ancestor_key) {
+                let props = self.props(ancestor.elementtype());
+                props.accepted_data.contains(&AcceptedData::AcceptElementGroup(ElementGroup::Layer)) || props.accepted_data.contains(&AcceptedData::AcceptElementType(layertype))
+            } else {
+                false
+            }
+        })
+    }
+
+    ///Checks that annotations of the specified type, set and processor can be added without
+    ///declaring anything when automatic declaration is disabled
+    pub(crate) fn check_declarable(&self, annotationtype: AnnotationType, set: &Option<String>, processor: Option<&str>) -> Result<(), FoliaError> {
+        if let Some(processor) = processor {
+            if self.get_processor_key_by_id(processor).is_none() {
+                return Err(FoliaError::KeyError(format!("No such processor: {}", processor)));
+            }
+        }
+        if !self.autodeclare && self.find_declaration(annotationtype, set).is_none() {
+            return Err(FoliaError::EncodeError(format!("Annotation type {} (set {}) is not declared and automatic declaration is disabled", annotationtype, set.as_deref().unwrap_or("unspecified"))));
+        }
+        Ok(())
+    }
+
+    ///Checks that span annotations of the specified type, set and processor can be added over
+    ///each of the specified lists of IDs, without adding anything. Importers use this to either add
+    ///all of their annotations or leave the document untouched.
+    pub(crate) fn check_spans_addable(&self, elementtype: ElementType, set: Option<&str>, processor: Option<&str>, spans: &[&[String]]) -> Result<(), FoliaError> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+        let annotationtype = elementtype.annotationtype().ok_or(
+            FoliaError::TypeError(format!("Element type {} is not an annotation type", elementtype))
+        )?;
+        let layertype = annotationtype.layertype().ok_or(
+            FoliaError::InternalError(format!("No layer type found for specified span type {:?}", elementtype))
+        )?;
+        let set = set.or_else(|| self.get_default_set(annotationtype)).map(|set| set.to_string());
+        if set.is_none() {
+            return Err(FoliaError::IncompleteError("No set defined for the span annotations and none could be inferred".to_string()));
+        }
+        self.check_declarable(annotationtype, &set, processor)?;
+        for ids in spans.iter() {
+            let mut span_keys: Vec<ElementKey> = Vec::new();
+            for id in ids.iter() {
+                let key = self.get_element_key_by_id(id).ok_or(FoliaError::KeyError(format!("Spanned element does not exist: {}", id)))?;
+                let elementtype = self.get_element(key).expect("spanned element").elementtype();
+                if !self.props(elementtype).wrefable {
+                    return Err(FoliaError::ValidationError(format!("Element {} ({}) can not be part of a span", id, elementtype)));
+                }
+                span_keys.push(key);
+            }
+            if self.span_layer_ancestor(layertype, &span_keys).is_none() {
+                return Err(FoliaError::ValidationError(format!("Unable to find a suitable common ancestor to hold the span over {}", ids.join(", "))));
+            }
+        }
+        Ok(())
+    }
+
     pub fn annotate_span(&mut self, element: ElementData) -> Result<ElementKey, FoliaError> {
         if !ElementGroup::Span.contains(element.elementtype) {
             return Err(FoliaError::TypeError(format!("Element passed to annotate_span is not a span element")));
@@ -717,6 +865,13 @@ impl Document {
                             span_keys.push(k);
                         }
                     }
+                } else if ElementGroup::SpanRole.contains(ed.elementtype) {
+                    //span roles (e.g. the head and dependent of a dependency) hold part of the span
+                    for k in self.get_span_keys(ed) {
+                        if !span_keys.contains(&k) {
+                            span_keys.push(k);
+                        }
+                    }
                 }
             }
         }
@@ -789,6 +944,16 @@ impl Store<ElementData,ElementKey> for Document {
             return Ok(element);
         }
 
+        if !self.autodeclare {
+            //all annotation types must have been declared explicitly
+            if let Some(annotationtype) = element.elementtype.annotationtype() {
+                let set = element.set()?.map(|s| s.to_string());
+                if self.find_declaration(annotationtype, &set).is_none() {
+                    return Err(FoliaError::EncodeError(format!("Annotation type {} (set {}) is not declared and automatic declaration is disabled", annotationtype, set.as_deref().unwrap_or("unspecified"))));
+                }
+            }
+        }
+
         let mut declaration_key: Option<DecKey> = None;
         let mut class_key: Option<ClassKey> = None;
         let mut processor_key: Option<ProcKey> = None;
@@ -799,7 +964,8 @@ impl Store<ElementData,ElementKey> for Document {
         if let Some(annotationtype) = element.elementtype.annotationtype() {
             //Declare the element (either declares anew or just resolves the to the right
             //declaration.
-            let deckey = self.declare(annotationtype, &element.set().unwrap().map(|s| s.to_string()),  &None,&None)?;
+            let set = element.set().unwrap().map(|s| s.to_string());
+            let deckey = self.declare(annotationtype, &set,  &None,&None)?;
             declaration_key  = Some(deckey);
 
             if let Ok(Some(class)) = element.class() {
@@ -824,8 +990,14 @@ impl Store<ElementData,ElementKey> for Document {
                         //get the declaration key from the parent context:
                         let parent = self.get_elementdata(parent_key).ok_or( FoliaError::InternalError("Context for feature does not exist!".to_string()))?;
 
-                        let annotationtype = parent.elementtype.annotationtype().expect(format!("Unwrapping annotation type of parent {}", element.elementtype).as_str() );
-                        let deckey = self.declare(annotationtype, &element.set().unwrap().map(|s| s.to_string()),  &None, &None)?;
+                        //features share the declaration of their parent
+                        let deckey = match parent.declaration_key()? {
+                            Some(deckey) => deckey,
+                            None => {
+                                let annotationtype = parent.elementtype.annotationtype().expect(format!("Unwrapping annotation type of parent {}", element.elementtype).as_str() );
+                                self.declare(annotationtype, &element.set().unwrap().map(|s| s.to_string()),  &None, &None)?
+                            }
+                        };
                         declaration_key  = Some(deckey);
 
                         if let Some(declaration) = self.get_mut_declaration(deckey) {
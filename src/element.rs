@@ -8,6 +8,8 @@ use std::fmt;
 use std::iter::ExactSizeIterator;
 use std::convert::Into;
 use std::clone::Clone;
+use std::time::Duration;
+use std::collections::HashMap;
 
 use std::io::Write;
 use std::io::BufWriter;
@@ -25,6 +27,7 @@ use crate::metadata::*;
 use crate::query::*;
 use crate::select::*;
 use crate::text::*;
+use crate::serialiser::SerialiseOptions;
 use crate::document::{Document};
 
 
@@ -37,7 +40,7 @@ pub enum ValidationStrategy {
 }
 
 
-#[derive(Clone,PartialEq,Debug)]
+#[derive(Clone,PartialEq,Debug,Serialize,Deserialize)]
 ///This is the structure that represents any instance of a FoLiA element. The type of the structure
 ///is represented by ``elementtype``. An elements holds and owns attributes, encoded attributes (if
 ///it is encoded already),  data items (which may be text, comments or child elements (by key)), and a link
@@ -92,7 +95,8 @@ impl Storable<ElementKey> for ElementData {
                     return true
             }
         }
-        false
+        //inline annotations without any attributes (default set, no class) still need their declaration
+        ElementGroup::Inline.contains(self.elementtype) && !self.attribs.iter().any(|attrib| matches!(attrib, Attribute::DeclarationRef(_)))
     }
 
     ///Returns the key of the current element
@@ -363,6 +367,24 @@ pub trait ReadElement {
         self.elementdata().id()
     }
 
+    ///Get the begin time of the element (relative to the start of the associated audio/video), if any
+    fn begintime(&self) -> Option<Duration> {
+        if let Some(Attribute::BeginTime(time)) = self.attrib(AttribType::BEGINTIME) {
+            Some(*time)
+        } else {
+            None
+        }
+    }
+
+    ///Get the end time of the element (relative to the start of the associated audio/video), if any
+    fn endtime(&self) -> Option<Duration> {
+        if let Some(Attribute::EndTime(time)) = self.attrib(AttribType::ENDTIME) {
+            Some(*time)
+        } else {
+            None
+        }
+    }
+
     ///Get the class key, i.e. the encoded (numeric) form of the class
     fn class_key(&self) -> Option<ClassKey> {
         self.elementdata().class_key().expect("Unwrapping class key result")
@@ -444,7 +466,7 @@ pub trait ReadElement {
                 0 => Writer::new(Cursor::new(Vec::new())),
                 indent =>  Writer::new_with_indent(Cursor::new(Vec::new()), b' ', indent)
             };
-            doc.xml_elements(&mut writer, self.key().unwrap())?;
+            doc.xml_elements(&mut writer, self.key().unwrap(), &SerialiseOptions::default(), false, false)?;
             let result = writer.into_inner().into_inner();
             let result = from_utf8(&result).expect("encoding utf-8");
             Ok(result.to_string())
@@ -570,6 +592,38 @@ impl<'a> Element<'a> {
     }
 
 
+    ///Returns the foreign XML held by this element (only applies to ``ElementType::ForeignData``), verbatim
+    pub fn foreign_xml(&self) -> Option<String> {
+        let xml: Vec<&str> = self.data.data.iter().filter_map(|item| match item {
+            DataType::ForeignXml(foreign) => Some(foreign.xml.as_str()),
+            _ => None
+        }).collect();
+        if xml.is_empty() {
+            None
+        } else {
+            Some(xml.concat())
+        }
+    }
+
+    ///Returns all features of this element as a map from subset to classes
+    pub fn features(&self) -> HashMap<String,Vec<String>> {
+        let mut features: HashMap<String,Vec<String>> = HashMap::new();
+        if let Some(document) = self.document {
+            for item in self.data.data.iter() {
+                if let DataType::Element(key) = item {
+                    if let Some(feature) = document.get_element(*key) {
+                        if feature.elementtype() == ElementType::Feature {
+                            if let (Some(subset), Some(class)) = (feature.subset(), feature.class()) {
+                                features.entry(subset.to_string()).or_default().push(class.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        features
+    }
+
     ///High-level function to get a particular feature by annotation type and set, returns an
     ///single element (the first feature).
     pub fn get_feature(&self, subset: Cmp<String>) -> Option<Element> {
@@ -679,6 +733,20 @@ impl ElementData {
         self
     }
 
+    ///High-level builder method to add a span role (e.g. ``ElementType::Headspan``) that covers the
+    ///specified span
+    pub fn with_role(self, roletype: ElementType, span_ids: &[&str]) -> Self {
+        self.add_element(ElementData::new(roletype).with_span(span_ids))
+    }
+
+    ///High-level builder method to add a feature (subset and class)
+    pub fn with_feature(self, subset: &str, class: &str) -> Self {
+        self.add_element(ElementData::new(ElementType::Feature).with_attribs(vec![
+            Attribute::Subset(subset.to_string()),
+            Attribute::Class(class.to_string())
+        ]))
+    }
+
     ///High-level builder method to provide textcontent
     pub fn with_text(self, text: String) -> Self {
         self.add_element(ElementData::new(ElementType::TextContent).with(DataType::Text(text)))
@@ -0,0 +1,376 @@
+use std::fmt::Write;
+
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::query::*;
+use crate::select::*;
+use crate::text::*;
+use crate::metadata::*;
+use crate::plaintext::*;
+use crate::document::*;
+
+///The stylesheet embedded in standalone HTML output
+pub const HTML_STYLESHEET: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; line-height: 1.6; }
+header { border-bottom: 1px solid #ccc; margin-bottom: 1em; }
+header table.metadata th { text-align: left; padding-right: 1em; }
+ul.provenance { font-size: 0.9em; color: #555; }
+span.w[title] { cursor: help; }
+span.w:hover { background: #eef; }
+span.w.gloss { display: inline-block; vertical-align: top; text-align: center; margin: 0 0.2em 0.5em 0; }
+span.w.gloss span.t { display: block; }
+span.w.gloss span.a { display: block; font-size: 0.75em; color: #666; }
+mark.span { background: #ffd; border-bottom: 2px solid #cc4; }
+mark.span.entity { background: #def; border-bottom-color: #69c; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #ccc; padding: 0.2em 0.5em; }
+figure img { max-width: 100%; }";
+
+#[derive(Clone)]
+///Options for ``Document::to_html()`` and ``Element::to_html()``
+pub struct HtmlOptions {
+    ///Determines which text (set, textclass, corrections) is rendered
+    pub textparameters: TextParameters,
+    ///The token annotations (annotation type and optionally a set) to show for each word, all
+    ///inline annotations are shown if this is empty
+    pub annotations: Vec<(AnnotationType,Option<String>)>,
+    ///The span annotations to highlight, if this is empty all span annotations that directly
+    ///cover words (i.e. not through span roles) are highlighted
+    pub spans: Vec<(AnnotationType,Option<String>)>,
+    ///Show token annotations as interlinear glosses under the words instead of as tooltips
+    pub glosses: bool,
+    ///Summarise metadata and provenance in a header (``Document::to_html()`` only)
+    pub header: bool,
+    ///Produce a complete HTML page with a stylesheet instead of a fragment (``Document::to_html()`` only)
+    pub standalone: bool,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            textparameters: TextParameters::default(),
+            annotations: Vec::new(),
+            spans: Vec::new(),
+            glosses: false,
+            header: true,
+            standalone: true,
+        }
+    }
+}
+
+impl HtmlOptions {
+    pub fn with_textparameters(mut self, textparameters: TextParameters) -> Self {
+        self.textparameters = textparameters;
+        self
+    }
+
+    pub fn with_annotation(mut self, annotationtype: AnnotationType, set: Option<&str>) -> Self {
+        self.annotations.push((annotationtype, set.map(|s| s.to_string())));
+        self
+    }
+
+    pub fn with_span(mut self, annotationtype: AnnotationType, set: Option<&str>) -> Self {
+        self.spans.push((annotationtype, set.map(|s| s.to_string())));
+        self
+    }
+
+    pub fn with_glosses(mut self, glosses: bool) -> Self {
+        self.glosses = glosses;
+        self
+    }
+
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn with_standalone(mut self, standalone: bool) -> Self {
+        self.standalone = standalone;
+        self
+    }
+}
+
+///Checks whether an annotation is selected by a list of annotation types and sets (an empty list selects everything)
+fn is_selected(selection: &[(AnnotationType,Option<String>)], element: &Element) -> bool {
+    selection.is_empty() || selection.iter().any(|(annotationtype, set)| {
+        Some(*annotationtype) == element.elementtype().annotationtype() && (set.is_none() || set.as_deref() == element.set())
+    })
+}
+
+///Escapes text for use in HTML content and attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+///Returns the label of an annotation, i.e. its annotation type and class, including features if any
+fn annotation_label(annotation: &Element) -> Option<(&'static str, String)> {
+    let class = annotation.class()?;
+    let annotationtype = annotation.elementtype().annotationtype().map(|annotationtype| annotationtype.as_str()).unwrap_or("");
+    let mut features: Vec<String> = annotation.features().into_iter().map(|(subset, classes)| format!("{}={}", subset, classes.join("|"))).collect();
+    features.sort();
+    if features.is_empty() {
+        Some((annotationtype, class.to_string()))
+    } else {
+        Some((annotationtype, format!("{} ({})", class, features.join(", "))))
+    }
+}
+
+///Returns the HTML tag that represents a structural element (the level of headings depends on
+///their depth and is determined when rendering)
+fn html_tag(element: &Element) -> &'static str {
+    match element.elementtype() {
+        ElementType::Head => "h1",
+        ElementType::Text | ElementType::Speech | ElementType::Event | ElementType::Example | ElementType::Entry => "div",
+        ElementType::Division => "section",
+        ElementType::Paragraph => "p",
+        ElementType::List => "ul",
+        ElementType::ListItem => "li",
+        ElementType::Table => "table",
+        ElementType::TableHead => "thead",
+        ElementType::Row => "tr",
+        ElementType::Cell => "td",
+        ElementType::Figure => "figure",
+        ElementType::Caption => "figcaption",
+        ElementType::Quote => "q",
+        ElementType::Note => "aside",
+        ElementType::Definition => "dd",
+        ElementType::Term => "dt",
+        _ => "span",
+    }
+}
+
+impl<'a> Element<'a> {
+    fn render_html_start(&self, out: &mut String, tag: &str) {
+        write!(out, "<{} class=\"{}\"", tag, self.elementtype()).expect("writing to string");
+        if let Some(id) = self.id() {
+            write!(out, " id=\"{}\"", escape_html(id)).expect("writing to string");
+        }
+        out.push('>');
+    }
+
+    ///Returns the span annotations this word is part of that should be highlighted
+    fn html_spans(&self, options: &HtmlOptions) -> Vec<Element<'a>> {
+        let mut spans: Vec<Element<'a>> = Vec::new();
+        if let Some(document) = self.document {
+            for item in self.data.data.iter() {
+                if let DataType::SpanReference(span_key) = item {
+                    if let Some(span) = document.get_element(*span_key) {
+                        let selected = if options.spans.is_empty() {
+                            span.select(Selector::elements().element(Cmp::Is(ElementType::WordReference)), Recursion::No).any(|wref| wref.elementdata().idref() == self.id())
+                        } else {
+                            is_selected(&options.spans, &span)
+                        };
+                        if selected {
+                            spans.push(span);
+                        }
+                    }
+                }
+            }
+        }
+        spans
+    }
+
+    fn render_html_mark(out: &mut String, span: &Element) {
+        write!(out, "<mark class=\"span {}\"", span.elementtype()).expect("writing to string");
+        if let Some((annotationtype, label)) = annotation_label(span) {
+            write!(out, " title=\"{}: {}\"", annotationtype, escape_html(&label)).expect("writing to string");
+        }
+        out.push('>');
+    }
+
+    ///Renders a word, the highlighted spans around it are rendered by the parent so that
+    ///consecutive words of the same span share a single ``<mark>``
+    fn render_html_word(&self, out: &mut String, options: &HtmlOptions) -> Result<(),FoliaError> {
+        let text = element_text(self, &options.textparameters)?;
+        let mut labels: Vec<(&'static str, String)> = Vec::new();
+        for annotation in self.select(Selector::elements().elementgroup(Cmp::Is(ElementGroup::Inline)), Recursion::No) {
+            if is_selected(&options.annotations, &annotation) {
+                labels.extend(annotation_label(&annotation));
+            }
+        }
+        if options.glosses {
+            write!(out, "<span class=\"w gloss\"").expect("writing to string");
+        } else {
+            write!(out, "<span class=\"w\"").expect("writing to string");
+        }
+        if let Some(id) = self.id() {
+            write!(out, " id=\"{}\"", escape_html(id)).expect("writing to string");
+        }
+        if options.glosses {
+            write!(out, "><span class=\"t\">{}</span>", escape_html(&text)).expect("writing to string");
+            for (annotationtype, label) in labels.iter() {
+                write!(out, "<span class=\"a {}\">{}</span>", annotationtype, escape_html(label)).expect("writing to string");
+            }
+            out.push_str("</span>");
+        } else {
+            if !labels.is_empty() {
+                let title: Vec<String> = labels.iter().map(|(annotationtype, label)| format!("{}: {}", annotationtype, label)).collect();
+                write!(out, " title=\"{}\"", escape_html(&title.join("\n"))).expect("writing to string");
+            }
+            write!(out, ">{}</span>", escape_html(&text)).expect("writing to string");
+        }
+        Ok(())
+    }
+
+    fn render_html(&self, out: &mut String, options: &HtmlOptions) -> Result<(),FoliaError> {
+        match self.elementtype() {
+            ElementType::Word => self.render_html_word(out, options)?,
+            ElementType::Hiddenword => {},
+            ElementType::Linebreak => out.push_str("<br/>\n"),
+            ElementType::Whitespace => out.push_str("<br/><br/>\n"),
+            elementtype => {
+                let tag = if elementtype == ElementType::Head {
+                    let depth = self.get_ancestors(ElementType::Division, Cmp::Any).count().clamp(1, 6);
+                    ["h1", "h2", "h3", "h4", "h5", "h6"][depth - 1]
+                } else {
+                    html_tag(self)
+                };
+                self.render_html_start(out, tag);
+                if elementtype == ElementType::Figure {
+                    if let Some(Attribute::Src(src)) = self.attrib(AttribType::SRC) {
+                        write!(out, "<img src=\"{}\"/>", escape_html(src)).expect("writing to string");
+                    }
+                }
+                let children = structure_children(self, &options.textparameters);
+                if children.is_empty() {
+                    out.push_str(&escape_html(&element_text(self, &options.textparameters)?));
+                } else {
+                    let block = tag != "span" && tag != "q";
+                    //the separator to insert before the next inline child
+                    let mut separator = "";
+                    //the highlighted spans that are currently open
+                    let mut marks: Vec<Element> = Vec::new();
+                    for child in children.iter() {
+                        let mut spans = if child.elementtype() == ElementType::Word { child.html_spans(options) } else { Vec::new() };
+                        //close the spans that do not continue into this child
+                        let keep = marks.iter().take_while(|mark| spans.contains(mark)).count();
+                        while marks.len() > keep {
+                            marks.pop();
+                            out.push_str("</mark>");
+                        }
+                        if block && html_tag(child) != "span" {
+                            out.push('\n');
+                        } else {
+                            out.push_str(separator);
+                        }
+                        spans.retain(|span| !marks.contains(span));
+                        for span in spans {
+                            Self::render_html_mark(out, &span);
+                            marks.push(span);
+                        }
+                        child.render_html(out, options)?;
+                        separator = match (child.elementtype(), child.attrib(AttribType::SPACE)) {
+                            (ElementType::Linebreak, _) | (ElementType::Whitespace, _) | (_, Some(Attribute::Space(false))) => "",
+                            _ => " ",
+                        };
+                    }
+                    for _ in marks.iter() {
+                        out.push_str("</mark>");
+                    }
+                    if block {
+                        out.push('\n');
+                    }
+                }
+                write!(out, "</{}>", tag).expect("writing to string");
+            }
+        }
+        Ok(())
+    }
+
+    ///Renders this element and everything under it as an HTML fragment, showing structure,
+    ///token annotations and span annotations as configured in the options
+    pub fn to_html(&self, options: &HtmlOptions) -> Result<String,FoliaError> {
+        let mut out = String::new();
+        //a word on its own is rendered along with its highlighted spans
+        let spans = if self.elementtype() == ElementType::Word { self.html_spans(options) } else { Vec::new() };
+        for span in spans.iter() {
+            Self::render_html_mark(&mut out, span);
+        }
+        self.render_html(&mut out, options)?;
+        for _ in spans.iter() {
+            out.push_str("</mark>");
+        }
+        Ok(out)
+    }
+}
+
+impl Document {
+    fn render_html_processor(&self, out: &mut String, processor: &Processor) -> Result<(),FoliaError> {
+        write!(out, "<li>{}", escape_html(&processor.name)).expect("writing to string");
+        if !processor.version.is_empty() {
+            write!(out, " {}", escape_html(&processor.version)).expect("writing to string");
+        }
+        write!(out, " ({})", processor.processortype).expect("writing to string");
+        if let Some(dt) = processor.begindatetime {
+            write!(out, ", {}", dt.format("%Y-%m-%d %H:%M:%S")).expect("writing to string");
+        }
+        if !processor.processors.is_empty() {
+            out.push_str("<ul>");
+            for key in processor.processors.iter() {
+                if let Some(subprocessor) = self.get_processor(*key) {
+                    self.render_html_processor(out, subprocessor)?;
+                }
+            }
+            out.push_str("</ul>");
+        }
+        out.push_str("</li>\n");
+        Ok(())
+    }
+
+    ///Renders a header summarising the metadata and the provenance of the document
+    fn render_html_header(&self, out: &mut String) -> Result<(),FoliaError> {
+        let title = self.metadata.data.get("title").map(|title| title.as_str()).unwrap_or(self.id());
+        write!(out, "<header>\n<h1 class=\"title\">{}</h1>\n", escape_html(title)).expect("writing to string");
+        if !self.metadata.data.is_empty() {
+            let mut keys: Vec<&String> = self.metadata.data.keys().collect();
+            keys.sort();
+            out.push_str("<table class=\"metadata\">\n");
+            for key in keys {
+                writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", escape_html(key), escape_html(&self.metadata.data[key])).expect("writing to string");
+            }
+            out.push_str("</table>\n");
+        }
+        if !self.provenancestore.chain.is_empty() {
+            out.push_str("<ul class=\"provenance\">\n");
+            for key in self.provenancestore.chain.iter() {
+                if let Some(processor) = self.get_processor(*key) {
+                    self.render_html_processor(out, processor)?;
+                }
+            }
+            out.push_str("</ul>\n");
+        }
+        out.push_str("</header>\n");
+        Ok(())
+    }
+
+    ///Renders the document as HTML: a complete page (or a fragment if ``standalone`` is unset) with
+    ///a header summarising metadata and provenance, followed by the body (see ``Element::to_html()``)
+    pub fn to_html(&self, options: &HtmlOptions) -> Result<String,FoliaError> {
+        let body = self.get_element(0).ok_or(FoliaError::InternalError("Document has no body".to_string()))?;
+        let mut out = String::new();
+        if options.standalone {
+            write!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n", escape_html(self.id()), HTML_STYLESHEET).expect("writing to string");
+        }
+        if options.header {
+            self.render_html_header(&mut out)?;
+        }
+        body.render_html(&mut out, options)?;
+        out.push('\n');
+        if options.standalone {
+            out.push_str("</body>\n</html>\n");
+        }
+        Ok(out)
+    }
+}
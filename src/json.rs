@@ -0,0 +1,462 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::store::*;
+use crate::elementstore::*;
+use crate::metadata::*;
+use crate::document::*;
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+///JSON representation of a FoLiA document, obtained through ``Document::to_json()``. Everything
+///is decoded: annotation types, sets, classes and processors are referred to by their names and IDs.
+pub struct JsonDocument {
+    pub id: String,
+    pub version: String,
+    #[serde(default)]
+    pub metadata: JsonMetadata,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub submetadata: BTreeMap<String,JsonMetadata>,
+    #[serde(default)]
+    pub declarations: Vec<JsonDeclaration>,
+    ///The processors in the provenance chain, subprocessors are nested
+    #[serde(default)]
+    pub provenance: Vec<JsonProcessor>,
+    ///The body of the document (``text`` or ``speech``)
+    pub body: JsonElement,
+}
+
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+///JSON representation of document metadata
+pub struct JsonMetadata {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub metadatatype: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+    ///Native metadata (key/value pairs)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String,String>,
+    ///Foreign metadata, the raw XML of each block
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub foreigndata: Vec<JsonForeignXml>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+///JSON representation of an annotation declaration
+pub struct JsonDeclaration {
+    ///The annotation type as in the declaration element, e.g. ``pos`` for ``<pos-annotation>``
+    #[serde(rename = "type")]
+    pub annotationtype: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    ///IDs of the processors associated with the declaration
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processors: Vec<String>,
+}
+
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+///JSON representation of a processor, empty fields are omitted
+pub struct JsonProcessor {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub processortype: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub folia_version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub document_version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub host: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub begindatetime: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enddatetime: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub src: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub format: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub resourcelink: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String,String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processors: Vec<JsonProcessor>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+///JSON representation of an element
+pub struct JsonElement {
+    ///The element type as in XML, e.g. ``w``
+    #[serde(rename = "type")]
+    pub elementtype: String,
+    ///The (decoded) attributes, named as in XML, in the order in which they are serialised (a JSON object)
+    #[serde(default, skip_serializing_if = "Vec::is_empty", serialize_with = "serialize_attributes", deserialize_with = "deserialize_attributes")]
+    pub attributes: Vec<(String,String)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<JsonNode>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(untagged)]
+///JSON representation of the data of an element. Text is a plain JSON string, elements, comments
+///and foreign XML are objects.
+pub enum JsonNode {
+    Text(String),
+    Element(JsonElement),
+    Comment {
+        comment: String,
+    },
+    ForeignXml(JsonForeignXml),
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+///JSON representation of foreign XML
+pub struct JsonForeignXml {
+    ///The raw XML
+    pub foreign: String,
+    ///Namespace bindings (prefix to namespace) that the XML relies on but that were made outside of it
+    #[serde(default, skip_serializing_if = "Vec::is_empty", serialize_with = "serialize_attributes", deserialize_with = "deserialize_attributes")]
+    pub namespaces: Vec<(String,String)>,
+}
+
+impl JsonElement {
+    ///Returns the value of the attribute with the specified (XML) name
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+///Serialises attributes (or other name/value pairs) as a JSON object, in their original order
+fn serialize_attributes<S: serde::Serializer>(attributes: &[(String,String)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(attributes.iter().map(|(key, value)| (key, value)))
+}
+
+///Deserialises attributes (or other name/value pairs) from a JSON object, keeping them in the order of the input
+fn deserialize_attributes<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<(String,String)>, D::Error> {
+    struct AttributesVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for AttributesVisitor {
+        type Value = Vec<(String,String)>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a map of attributes")
+        }
+
+        fn visit_map<M: serde::de::MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+            let mut attributes: Vec<(String,String)> = Vec::new();
+            while let Some((key, value)) = map.next_entry::<String,String>()? {
+                attributes.push((key, value));
+            }
+            Ok(attributes)
+        }
+    }
+
+    deserializer.deserialize_map(AttributesVisitor)
+}
+
+fn to_json_error(err: serde_json::Error) -> FoliaError {
+    FoliaError::SerialisationError(format!("{}", err))
+}
+
+fn format_datetime(dt: &NaiveDateTime) -> String {
+    dt.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+fn parse_datetime(value: &str) -> Result<NaiveDateTime,FoliaError> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").map_err(|e| FoliaError::ParseError(format!("Unable to parse datetime {} -> {}", value, e)))
+}
+
+impl From<&ForeignXml> for JsonForeignXml {
+    fn from(foreign: &ForeignXml) -> Self {
+        Self {
+            foreign: foreign.xml.clone(),
+            namespaces: foreign.namespaces.clone(),
+        }
+    }
+}
+
+impl From<JsonForeignXml> for ForeignXml {
+    fn from(foreign: JsonForeignXml) -> Self {
+        Self {
+            xml: foreign.foreign,
+            namespaces: foreign.namespaces,
+        }
+    }
+}
+
+impl From<&Metadata> for JsonMetadata {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            metadatatype: metadata.metadatatype.clone(),
+            src: metadata.src.clone(),
+            data: metadata.data.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+            foreigndata: metadata.foreigndata.iter().map(JsonForeignXml::from).collect(),
+        }
+    }
+}
+
+impl From<JsonMetadata> for Metadata {
+    fn from(metadata: JsonMetadata) -> Self {
+        Self {
+            data: metadata.data.into_iter().collect(),
+            src: metadata.src,
+            metadatatype: metadata.metadatatype,
+            foreigndata: metadata.foreigndata.into_iter().map(ForeignXml::from).collect(),
+            srcdata: None,
+        }
+    }
+}
+
+impl JsonProcessor {
+    fn to_processor(&self) -> Result<Processor,FoliaError> {
+        let processortype = match self.processortype.as_str() {
+            "auto" => ProcessorType::Auto,
+            "manual" => ProcessorType::Manual,
+            "generator" => ProcessorType::Generator,
+            "datasource" => ProcessorType::DataSource,
+            other => return Err(FoliaError::ParseError(format!("Invalid processor type: {}", other)))
+        };
+        Ok(Processor {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            processortype,
+            version: self.version.clone(),
+            folia_version: self.folia_version.clone(),
+            document_version: self.document_version.clone(),
+            command: self.command.clone(),
+            host: self.host.clone(),
+            user: self.user.clone(),
+            begindatetime: self.begindatetime.as_deref().map(parse_datetime).transpose()?,
+            enddatetime: self.enddatetime.as_deref().map(parse_datetime).transpose()?,
+            src: self.src.clone(),
+            format: self.format.clone(),
+            resourcelink: self.resourcelink.clone(),
+            metadata: Metadata {
+                data: self.metadata.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+                ..Metadata::default()
+            },
+            ..Processor::default()
+        })
+    }
+}
+
+impl Document {
+    fn json_processor(&self, processor_key: ProcKey) -> Result<JsonProcessor,FoliaError> {
+        let processor = self.get_processor(processor_key).ok_or(FoliaError::InternalError(format!("Unable to resolve processor {}", processor_key)))?;
+        Ok(JsonProcessor {
+            id: processor.id.clone(),
+            name: processor.name.clone(),
+            processortype: processor.processortype.as_str().to_string(),
+            version: processor.version.clone(),
+            folia_version: processor.folia_version.clone(),
+            document_version: processor.document_version.clone(),
+            command: processor.command.clone(),
+            host: processor.host.clone(),
+            user: processor.user.clone(),
+            begindatetime: processor.begindatetime.as_ref().map(format_datetime),
+            enddatetime: processor.enddatetime.as_ref().map(format_datetime),
+            src: processor.src.clone(),
+            format: processor.format.clone(),
+            resourcelink: processor.resourcelink.clone(),
+            metadata: processor.metadata.data.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+            processors: processor.processors.iter().map(|key| self.json_processor(*key)).collect::<Result<Vec<_>,_>>()?,
+        })
+    }
+
+    fn json_element(&self, key: ElementKey) -> Result<JsonElement,FoliaError> {
+        let element = self.get_element(key).ok_or(FoliaError::InternalError(format!("Unable to resolve element {}", key)))?;
+        let mut attributes: Vec<(String,String)> = Vec::new();
+        for attrib in element.attribs().iter() {
+            if *attrib != Attribute::Ignore && !attrib.decodable() {
+                let name: &str = attrib.attribtype().into();
+                attributes.push((name.to_string(), attrib.to_string()?));
+            }
+        }
+        if element.elementtype() != ElementType::Feature {
+            //features share the set of their parent
+            if let Some(set) = element.set() {
+                attributes.push(("set".to_string(), set.to_string()));
+            }
+        }
+        if let Some(subset) = element.subset() {
+            attributes.push(("subset".to_string(), subset.to_string()));
+        }
+        if let Some(class) = element.class() {
+            attributes.push(("class".to_string(), class.to_string()));
+        }
+        if let Some(processor) = element.processor() {
+            attributes.push(("processor".to_string(), processor.to_string()));
+        }
+        let mut children: Vec<JsonNode> = Vec::new();
+        for item in element.elementdata().data.iter() {
+            match item {
+                DataType::Element(child_key) => children.push(JsonNode::Element(self.json_element(*child_key)?)),
+                DataType::Text(text) => children.push(JsonNode::Text(text.clone())),
+                DataType::Comment(comment) => children.push(JsonNode::Comment { comment: comment.clone() }),
+                DataType::ForeignXml(foreign) => children.push(JsonNode::ForeignXml(JsonForeignXml::from(foreign))),
+                DataType::SpanReference(_) | DataType::AddElement(_) => {
+                    //back-references are derived from the wrefs on import
+                },
+            }
+        }
+        Ok(JsonElement {
+            elementtype: element.elementtype().to_string(),
+            attributes,
+            children,
+        })
+    }
+
+    ///Returns a JSON representation of the entire document, covering metadata, declarations,
+    ///provenance and the element tree. This is the inverse of ``Document::from_json()``.
+    pub fn to_json(&self) -> Result<JsonDocument,FoliaError> {
+        let mut declarations: Vec<JsonDeclaration> = Vec::new();
+        for declaration in self.declarations().filter_map(|declaration| declaration.as_deref()) {
+            declarations.push(JsonDeclaration {
+                annotationtype: declaration.annotationtype.as_str().to_string(),
+                set: declaration.set.clone(),
+                alias: declaration.alias.clone(),
+                format: declaration.format.clone(),
+                processors: declaration.processors.iter().filter_map(|key| self.get_processor(*key)).map(|processor| processor.id.clone()).collect(),
+            });
+        }
+        Ok(JsonDocument {
+            id: self.id.clone(),
+            version: self.version.clone(),
+            metadata: JsonMetadata::from(&self.metadata),
+            submetadata: self.submetadata.iter().map(|(id, metadata)| (id.clone(), JsonMetadata::from(metadata))).collect(),
+            declarations,
+            provenance: self.provenancestore.chain.iter().map(|key| self.json_processor(*key)).collect::<Result<Vec<_>,_>>()?,
+            body: self.json_element(0)?,
+        })
+    }
+
+    ///Serialises the entire document to a JSON string, see ``Document::to_json()``
+    pub fn to_json_string(&self, pretty: bool) -> Result<String,FoliaError> {
+        let json = self.to_json()?;
+        if pretty {
+            serde_json::to_string_pretty(&json).map_err(to_json_error)
+        } else {
+            serde_json::to_string(&json).map_err(to_json_error)
+        }
+    }
+
+    fn add_json_processor(&mut self, parent_key: Option<ProcKey>, json: &JsonProcessor) -> Result<(),FoliaError> {
+        let processor = json.to_processor()?;
+        let processor_key = match parent_key {
+            Some(parent_key) => self.add_subprocessor(parent_key, processor)?,
+            None => self.add_processor(processor)?,
+        };
+        for subprocessor in json.processors.iter() {
+            self.add_json_processor(Some(processor_key), subprocessor)?;
+        }
+        Ok(())
+    }
+
+    fn json_attribs(json: &JsonElement) -> Result<Vec<Attribute>,FoliaError> {
+        let mut attribs: Vec<Attribute> = Vec::new();
+        for (name, value) in json.attributes.iter() {
+            let attrib = Attribute::from_key_value(name.as_bytes(), value.clone())
+                            .map_err(FoliaError::add_parseerror_string(format!("Error parsing attributes for {}", json.elementtype)))?;
+            if attrib != Attribute::Ignore {
+                attribs.push(attrib);
+            }
+        }
+        Ok(attribs)
+    }
+
+    ///Adds the data of a JSON element to an element that was already added (and attached)
+    fn add_json_children(&mut self, key: ElementKey, json: &JsonElement) -> Result<(),FoliaError> {
+        for child in json.children.iter() {
+            let datatype = match child {
+                JsonNode::Element(child) => {
+                    let elementtype = ElementType::from_str(&child.elementtype)?;
+                    let elementdata = ElementData::new(elementtype).with_attribs(Self::json_attribs(child)?);
+                    let child_key = self.add(elementdata, Some(key))
+                                        .map_err(FoliaError::add_parseerror_string(format!("Error adding {}", child.elementtype)))?;
+                    self.attach_element(key, child_key)?;
+                    self.add_json_children(child_key, child)?;
+                    self.post_add(child_key, None)?;
+                    continue;
+                },
+                JsonNode::Text(text) => DataType::Text(text.clone()),
+                JsonNode::Comment { comment } => DataType::Comment(comment.clone()),
+                JsonNode::ForeignXml(foreign) => DataType::ForeignXml(ForeignXml::from(foreign.clone())),
+            };
+            if let Some(elementdata) = self.get_mut_elementdata(key) {
+                elementdata.push(datatype);
+            }
+        }
+        Ok(())
+    }
+
+    ///Instantiates a document from its JSON representation (as produced by ``Document::to_json()``)
+    pub fn from_json(json: &JsonDocument, properties: DocumentProperties) -> Result<Self,FoliaError> {
+        let mut doc = Self {
+            id: json.id.clone(),
+            filename: None,
+            version: json.version.clone(),
+            elementstore: ElementStore::default(),
+            provenancestore: ProvenanceStore::default(),
+            declarationstore: DeclarationStore::default(),
+            metadata: Metadata::from(json.metadata.clone()),
+            submetadata: json.submetadata.iter().map(|(id, metadata)| (id.clone(), Metadata::from(metadata.clone()))).collect(),
+            autodeclare: properties.autodeclare,
+            active_processor: None,
+        };
+        for processor in json.provenance.iter() {
+            doc.add_json_processor(None, processor)?;
+        }
+        for declaration in json.declarations.iter() {
+            let annotationtype = AnnotationType::from_str(&declaration.annotationtype)
+                                    .ok_or(FoliaError::ParseError(format!("Unknown declaration: {}", declaration.annotationtype)))?;
+            let mut processors: Vec<ProcKey> = Vec::new();
+            for processor_id in declaration.processors.iter() {
+                processors.push(doc.get_processor_key_by_id(processor_id).ok_or(FoliaError::ParseError(format!("Declaration refers to a non-existing processor: {}", processor_id)))?);
+            }
+            let mut result = Declaration::new(annotationtype, declaration.set.clone(), declaration.alias.clone(), declaration.format.clone());
+            result.processors = processors;
+            doc.add_declaration(result).map_err(FoliaError::add_parseerror("Error adding declaration"))?;
+        }
+
+        let bodytype = ElementType::from_str(&json.body.elementtype)?;
+        if bodytype != ElementType::Text && bodytype != ElementType::Speech {
+            return Err(FoliaError::ParseError(format!("Expected text or speech as body, got: {}", json.body.elementtype)));
+        }
+        let body = ElementData::new(bodytype).with_attribs(Self::json_attribs(&json.body)?);
+        doc.add(body, None).map_err(FoliaError::add_parseerror("Error adding body"))?;
+        doc.apply_properties(properties).map_err(FoliaError::add_parseerror("Error applying properties"))?;
+        doc.add_json_children(0, &json.body)?;
+        Ok(doc)
+    }
+
+    ///Instantiates a document from a JSON string, see ``Document::from_json()``
+    pub fn from_json_str(data: &str, properties: DocumentProperties) -> Result<Self,FoliaError> {
+        let json: JsonDocument = serde_json::from_str(data).map_err(|e| FoliaError::ParseError(format!("Invalid JSON: {}", e)))?;
+        Self::from_json(&json, properties)
+    }
+
+    ///Loads a document from a JSON file, see ``Document::from_json()``
+    pub fn from_json_file(filename: &str, properties: DocumentProperties) -> Result<Self,FoliaError> {
+        let data = fs::read_to_string(filename)?;
+        let mut doc = Self::from_json_str(&data, properties)?;
+        doc.filename = Some(filename.to_string());
+        Ok(doc)
+    }
+}
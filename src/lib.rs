@@ -8,10 +8,12 @@ extern crate strum_macros;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 
 extern crate rand;
 extern crate hex;
 extern crate chrono;
+extern crate bincode;
 
 pub mod common;
 pub mod types;
@@ -24,6 +26,19 @@ pub mod metadata;
 pub mod query;
 pub mod select;
 pub mod text;
+pub mod dependency;
+pub mod syntax;
+pub mod coreference;
+pub mod conllu;
+pub mod tokeniser;
+pub mod plaintext;
+pub mod tokentable;
+pub mod bio;
+pub mod json;
+pub mod html;
+pub mod brat;
+pub mod snapshot;
+pub mod upgrade;
 pub mod document;
 pub mod parser;
 pub mod serialiser;
@@ -42,7 +57,21 @@ pub use query::*;
 pub use select::*;
 pub use specification::*;
 pub use text::*;
+pub use dependency::*;
+pub use syntax::*;
+pub use coreference::*;
+pub use conllu::*;
+pub use tokeniser::*;
+pub use plaintext::*;
+pub use tokentable::*;
+pub use bio::*;
+pub use json::*;
+pub use html::*;
+pub use brat::*;
+pub use snapshot::*;
+pub use upgrade::*;
 pub use metadata::*;
+pub use serialiser::*;
 
 
 
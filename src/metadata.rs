@@ -4,20 +4,26 @@ use std::borrow::Cow;
 use std::env;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
 use chrono::NaiveDateTime;
 use rand::prelude::*;
+use quick_xml::Reader;
+use quick_xml::events::Event;
 
 use crate::common::*;
 use crate::error::*;
 use crate::types::*;
 use crate::store::*;
 use crate::document::*;
+use crate::element::*;
+use crate::attrib::*;
 
 
 ///Represent a declaration for a particular annotation type, a set (optional), and associated with
 ///zero or more annotators or processors. Also holds and owns
 ///the class store for any classes in that set.
-#[derive(Clone)]
+#[derive(Clone,Serialize,Deserialize)]
 pub struct Declaration {
     pub key: Option<DecKey>,
     pub annotationtype: AnnotationType,
@@ -174,7 +180,7 @@ impl Storable<ClassKey> for Class {
     }
 }
 
-#[derive(Default,Clone)]
+#[derive(Default,Clone,Serialize,Deserialize)]
 ///The declaration store holds all classes that occur (e.g. in a document for a given set and
 ///annotation type). There are multiple class stores, which are owned by their respective ``Declaration`` (for a given set and
 ///annotation type).
@@ -205,7 +211,7 @@ impl StringStore<ClassKey> for ClassStore {
 }
 
 
-#[derive(Default,Clone)]
+#[derive(Default,Clone,Serialize,Deserialize)]
 ///The declaration store holds all classes that occur (e.g. in a document for a given set and
 ///annotation type). There are multiple class stores, which are owned by their respective ``Declaration`` (for a given set and
 ///annotation type).
@@ -325,7 +331,7 @@ pub struct ProvenanceStore {
 
 
 
-#[derive(Debug,PartialEq,Clone,Copy)]
+#[derive(Debug,PartialEq,Clone,Copy,Serialize,Deserialize)]
 ///Represents the type of a processor
 pub enum ProcessorType {
     Auto,
@@ -356,7 +362,7 @@ impl fmt::Display for ProcessorType {
     }
 }
 
-#[derive(Default,Clone)]
+#[derive(Default,Clone,Serialize,Deserialize)]
 ///Represents a processor
 pub struct Processor {
     pub id: String,
@@ -378,6 +384,7 @@ pub struct Processor {
     pub metadata: Metadata,
     pub key: Option<ProcKey>,
     ///This field is reserved for construction time only
+    #[serde(skip)]
     pub pending_processors: RefCell<Vec<Processor>>,
 }
 
@@ -535,7 +542,7 @@ impl Storable<ProcKey> for Processor {
     }
 }
 
-#[derive(Default,Clone)]
+#[derive(Default,Clone,Serialize,Deserialize)]
 ///A key/value store (``data``) containing arbitrary metadata (FoLiA native metadata)
 ///Instead of using the key/value store, it may also refer to an external metadata source
 ///(``src``).
@@ -543,6 +550,211 @@ pub struct Metadata {
     pub data: HashMap<String,String>,
     pub src: Option<String>,
     pub metadatatype: Option<String>,
+    ///Foreign metadata (e.g. CMDI), the raw XML of each ``<foreign-data>`` block
+    pub foreigndata: Vec<ForeignXml>,
+    ///The XML of the external metadata file referenced by ``src``, once loaded through
+    ///``load_src()`` (this is never serialised)
+    #[serde(skip)]
+    pub srcdata: Option<String>,
 }
 
+#[derive(Debug,Clone,PartialEq)]
+///The type of metadata, as expressed in the ``type`` attribute of ``<metadata>``
+pub enum MetadataType {
+    ///FoLiA native metadata (``<meta>`` key/value pairs)
+    Native,
+    ///CMDI (Component MetaData Infrastructure)
+    Cmdi,
+    ///IMDI (ISLE MetaData Initiative)
+    Imdi,
+    ///Dublin Core
+    DublinCore,
+    ///Any other metadata type
+    Other(String),
+}
+
+impl std::str::FromStr for MetadataType {
+    type Err = FoliaError;
+
+    ///Parses a metadata type, unknown types are held as ``MetadataType::Other``, so this never fails
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "native" => MetadataType::Native,
+            "cmdi" => MetadataType::Cmdi,
+            "imdi" => MetadataType::Imdi,
+            "dc" | "dublincore" | "dublin-core" => MetadataType::DublinCore,
+            _ => MetadataType::Other(s.to_string()),
+        })
+    }
+}
+
+impl Metadata {
+    ///Returns the type of the metadata, metadata without an explicit type is native
+    pub fn get_type(&self) -> MetadataType {
+        match &self.metadatatype {
+            Some(metadatatype) => metadatatype.parse().expect("parsing metadata type"),
+            None => MetadataType::Native,
+        }
+    }
+
+    ///Returns the text of all elements in the foreign metadata that match the specified path. The
+    ///path consists of local element names (namespace prefixes are ignored) separated by slashes and
+    ///may match at any depth, e.g. ``title`` or ``Session/Name``.
+    pub fn query(&self, path: &str) -> Result<Vec<String>,FoliaError> {
+        let path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut results: Vec<String> = Vec::new();
+        for xml in self.foreigndata.iter().map(|foreign| &foreign.xml).chain(self.srcdata.iter()) {
+            let mut reader = Reader::from_str(xml);
+            reader.trim_text(true);
+            let mut buf = Vec::new();
+            let mut stack: Vec<String> = Vec::new();
+            loop {
+                match reader.read_event(&mut buf)? {
+                    Event::Start(e) => stack.push(String::from_utf8_lossy(e.local_name()).to_string()),
+                    Event::End(_) => { stack.pop(); },
+                    Event::Text(e) if !path.is_empty() && stack.len() >= path.len() && stack[stack.len()-path.len()..].iter().zip(path.iter()).all(|(a,b)| a == b) => {
+                        results.push(e.unescape_and_decode(&reader)?);
+                    },
+                    Event::Eof => break,
+                    _ => {}
+                }
+                buf.clear();
+            }
+        }
+        Ok(results)
+    }
+
+    ///Returns a metadata value by key. For native metadata this is a key from the key/value store,
+    ///for foreign metadata it is a path as accepted by ``query()``; the first match is returned.
+    pub fn get(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.data.get(key) {
+            Some(value.clone())
+        } else {
+            self.query(key).ok().and_then(|results| results.into_iter().next())
+        }
+    }
+
+    ///Returns the first value found for any of the keys/paths
+    fn get_first(&self, keys: &[&str]) -> Option<String> {
+        keys.iter().find_map(|key| self.get(key))
+    }
+
+    ///Returns the title of the resource, regardless of the metadata type
+    pub fn title(&self) -> Option<String> {
+        match self.get_type() {
+            MetadataType::Cmdi => self.get_first(&["Title", "ResourceTitle", "ResourceName", "Name"]),
+            MetadataType::Imdi => self.get_first(&["Session/Title", "Session/Name", "Corpus/Title", "Corpus/Name"]),
+            _ => self.get_first(&["title"]),
+        }
+    }
+
+    ///Returns the author of the resource, regardless of the metadata type
+    pub fn author(&self) -> Option<String> {
+        match self.get_type() {
+            MetadataType::Cmdi => self.get_first(&["Author", "Creator", "MdCreator"]),
+            MetadataType::Imdi => self.get_first(&["Actor/FullName", "Actor/Name"]),
+            _ => self.get_first(&["author", "creator"]),
+        }
+    }
+
+    ///Returns the date of the resource, regardless of the metadata type
+    pub fn date(&self) -> Option<String> {
+        match self.get_type() {
+            MetadataType::Cmdi => self.get_first(&["Date", "CreationDate", "PublicationDate", "MdCreationDate"]),
+            MetadataType::Imdi => self.get_first(&["Session/Date"]),
+            _ => self.get_first(&["date"]),
+        }
+    }
 
+    ///Loads the external metadata file referenced by ``src`` (only local paths are supported) into
+    ///``srcdata`` so it can be queried. Relative paths are resolved against ``basedir``, if provided.
+    pub fn load_src(&mut self, basedir: Option<&Path>) -> Result<(),FoliaError> {
+        let src = self.src.as_ref().ok_or(FoliaError::IncompleteError("Metadata has no src".to_string()))?;
+        let src = src.strip_prefix("file://").unwrap_or(src);
+        if src.contains("://") {
+            return Err(FoliaError::IncompleteError(format!("Only local metadata files can be loaded: {}", src)));
+        }
+        let path = match basedir {
+            Some(basedir) if Path::new(src).is_relative() => basedir.join(src),
+            _ => Path::new(src).to_path_buf(),
+        };
+        let xml = fs::read_to_string(path)?;
+        self.srcdata = Some(strip_prolog(&xml).to_string());
+        Ok(())
+    }
+}
+
+///Strips the XML declaration, processing instructions, doctype and comments preceding the root element
+fn strip_prolog(xml: &str) -> &str {
+    let mut xml = xml.trim();
+    loop {
+        let end = if xml.starts_with("<?") {
+            xml.find("?>").map(|i| i + 2)
+        } else if xml.starts_with("<!--") {
+            xml.find("-->").map(|i| i + 3)
+        } else if xml.starts_with("<!") {
+            xml.find('>').map(|i| i + 1)
+        } else {
+            None
+        };
+        match end {
+            Some(end) => xml = xml[end..].trim_start(),
+            None => return xml,
+        }
+    }
+}
+
+impl<'a> Element<'a> {
+    ///Returns the ID of the submetadata that applies to this element, either set on the element
+    ///itself or inherited from the nearest ancestor that has one
+    pub fn metadata_id(&self) -> Option<&'a str> {
+        let mut data: &'a ElementData = self.data;
+        loop {
+            if let Some(Attribute::Metadata(id)) = data.attrib(AttribType::METADATA) {
+                return Some(id.as_str());
+            }
+            data = self.document?.get_elementdata(data.parent_key()?)?;
+        }
+    }
+
+    ///Returns the metadata that applies to this element: the submetadata referenced by the element
+    ///or its nearest ancestor, or the document metadata if there is none. Returns ``None`` only if
+    ///the element is not associated with a document.
+    pub fn metadata(&self) -> Option<&'a Metadata> {
+        let document = self.document?;
+        match self.metadata_id().and_then(|id| document.submetadata.get(id)) {
+            Some(submetadata) => Some(submetadata),
+            None => Some(&document.metadata),
+        }
+    }
+}
+
+impl Document {
+    ///Adds a named submetadata block, which elements can then refer to through ``assign_submetadata()``
+    pub fn add_submetadata(&mut self, id: &str, metadata: Metadata) -> Result<(),FoliaError> {
+        if self.submetadata.contains_key(id) {
+            return Err(FoliaError::KeyError(format!("Submetadata with ID {} already exists", id)));
+        }
+        self.submetadata.insert(id.to_string(), metadata);
+        Ok(())
+    }
+
+    ///Assigns submetadata to an element and thereby to the entire subtree under it (unless a
+    ///descendant refers to other submetadata). Passing ``None`` removes the reference, so the
+    ///element inherits metadata again.
+    pub fn assign_submetadata(&mut self, key: ElementKey, metadata_id: Option<&str>) -> Result<(),FoliaError> {
+        if let Some(metadata_id) = metadata_id {
+            if !self.submetadata.contains_key(metadata_id) {
+                return Err(FoliaError::KeyError(format!("No such submetadata: {}", metadata_id)));
+            }
+        }
+        let element = self.get_mut_elementdata(key).ok_or(
+            FoliaError::KeyError(format!("Specified element key not found: {:?}", key))
+        )?;
+        match metadata_id {
+            Some(metadata_id) => element.set_attrib(Attribute::Metadata(metadata_id.to_string())),
+            None => { element.del_attrib(AttribType::METADATA); },
+        }
+        Ok(())
+    }
+}
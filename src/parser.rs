@@ -8,8 +8,10 @@ use std::string::ToString;
 use std::fmt::Display;
 use std::collections::HashMap;
 
-use quick_xml::Reader;
-use quick_xml::events::Event;
+use std::io::Cursor;
+
+use quick_xml::{Reader,Writer};
+use quick_xml::events::{Event,BytesStart};
 use chrono::NaiveDateTime;
 
 use crate::common::*;
@@ -82,21 +84,40 @@ impl Document {
             }
         };
 
+        //reject documents that need a newer FoLiA version than we support (patch versions don't matter)
+        let (major, minor, _) = parse_version(&doc.version);
+        let (supported_major, supported_minor, _) = parse_version(FOLIAVERSION);
+        if (major, minor) > (supported_major, supported_minor) {
+            return Err(FoliaError::ParseError(format!("Document requires FoLiA v{}, but this library only supports up to FoLiA v{}", doc.version, FOLIAVERSION)));
+        }
+        //FoLiA v1 did not require all annotation types to be declared
+        let autodeclare = doc.autodeclare;
+        if doc.is_legacy() {
+            doc.autodeclare = true;
+        }
+
         //parse metadata
         let mut parsedeclarations = false;
         let mut parseprovenance = false;
         let mut submetadata: Option<String> = None;
         let mut submetadata_type: Option<String> = None;
+        let mut submetadata_src: Option<String> = None;
         let mut text: Option<String> = None;
         let mut meta_id: Option<String> = None;
         let mut declaration_key: Option<DecKey> = None;
         let mut annotators: Vec<(DecKey,String)> = Vec::new(); //mapping of declaration keys to processor ids; temporary structure
         let mut processor_stack: Vec<ProcKey> = vec![];
+        let mut foreigndata: Option<ForeignXml> = None; //holds the raw XML while inside <foreign-data>
         loop {
             let e = reader.read_namespaced_event(&mut buf, &mut nsbuf)?;
             match e {
                 (ref ns, Event::Empty(ref e)) => {
                     match (*ns, e.local_name())  {
+                        (ns, _) if ns != Some(NSFOLIA) && foreigndata.is_some() => {
+                            let foreigndata = foreigndata.as_mut().expect("foreign data");
+                            let xml = parse_foreign_xml(reader, &e.to_owned(), ns.map(|ns| ns.to_vec()), true, &mut Vec::new(), &mut nsbuf, &mut foreigndata.namespaces)?;
+                            foreigndata.xml.push_str(&xml);
+                        },
                         (Some(ns), b"processor") if ns == NSFOLIA && parseprovenance => {
                             let processor = Processor::parse(&reader, &e)
                                                       .map_err(FoliaError::add_parseerror("Error parsing processor"))?;
@@ -131,12 +152,27 @@ impl Document {
                                 }
                             }
                         },
+                        (Some(ns), tag) if ns == NSFOLIA && parsedeclarations => {
+                            //declaration without any annotators
+                            let declaration = Declaration::parse(&reader, e, tag).map_err(FoliaError::add_parseerror("Error parsing declaration"))?;
+                            let result = doc.add_declaration(declaration).map_err(FoliaError::add_parseerror("Error adding declaration"))?;
+                            doc.parse_legacy_annotator(reader, e, result)?;
+                        },
                         _ => {
                         }
                     }
                 },
                 (ref ns, Event::Start(ref e)) => {
                     match (*ns, e.local_name())  {
+                        (ns, _) if ns != Some(NSFOLIA) && foreigndata.is_some() => {
+                            let (start, ns) = (e.to_owned(), ns.map(|ns| ns.to_vec()));
+                            let foreigndata = foreigndata.as_mut().expect("foreign data");
+                            let xml = parse_foreign_xml(reader, &start, ns, false, &mut Vec::new(), &mut nsbuf, &mut foreigndata.namespaces)?;
+                            foreigndata.xml.push_str(&xml);
+                        },
+                        (Some(ns), b"foreign-data") if ns == NSFOLIA => {
+                            foreigndata = Some(ForeignXml::default());
+                        },
                         (Some(ns), b"metadata") if ns == NSFOLIA => {
                             for attrib in e.attributes() {
                                 let attrib = attrib.expect("unwrapping metadata attribute");
@@ -172,13 +208,20 @@ impl Document {
                                         b"type" => {
                                             submetadata_type = Some(value.clone());
                                         },
+                                        b"src" => {
+                                            submetadata_src = Some(value.clone());
+                                        },
                                         otherwise => {
                                             eprintln!("WARNING: Unhandled attribute submetadata/@{:?}",str::from_utf8(otherwise).unwrap());
                                         }
                                     }
                                 }
                             }
-                            if submetadata.is_none() {
+                            if let Some(submetadata_id) = &submetadata {
+                                let submetadata = doc.submetadata.entry(submetadata_id.clone()).or_default();
+                                submetadata.metadatatype = submetadata_type.clone();
+                                submetadata.src = submetadata_src.take();
+                            } else {
                                 return Err(FoliaError::ParseError("Submetadata has no ID".to_string()));
                             }
                         },
@@ -212,6 +255,7 @@ impl Document {
                         (Some(ns), tag) if ns == NSFOLIA && parsedeclarations => {
                             let declaration = Declaration::parse(&reader, e, tag).map_err(FoliaError::add_parseerror("Error parsing declaration"))?;
                             let result = doc.add_declaration(declaration).map_err(FoliaError::add_parseerror("Error adding declaration"))?;
+                            doc.parse_legacy_annotator(reader, e, result)?;
                             declaration_key = Some(result);
                         },
                         (Some(ns), tag) if ns == NSFOLIA => {
@@ -234,8 +278,22 @@ impl Document {
                 },
                 (ref ns, Event::End(ref e)) => {
                     match (*ns, e.local_name())  {
+                        (Some(ns), b"foreign-data") if ns == NSFOLIA => {
+                            if let Some(foreigndata) = foreigndata.take() {
+                                if let Some(submetadata_id) = &submetadata {
+                                    let submetadata = doc.submetadata.entry(submetadata_id.clone()).or_default();
+                                    if submetadata.metadatatype.is_none() && submetadata_type.is_some() {
+                                        submetadata.metadatatype = submetadata_type.clone();
+                                    }
+                                    submetadata.foreigndata.push(foreigndata);
+                                } else {
+                                    doc.metadata.foreigndata.push(foreigndata);
+                                }
+                            }
+                        },
                         (Some(ns), b"submetadata") if ns == NSFOLIA => {
                             submetadata = None;
+                            submetadata_type = None;
                         },
                         (Some(ns), b"metadata") if ns == NSFOLIA => {
                             break;
@@ -331,6 +389,10 @@ impl Document {
             doc.add(body,None).map_err(FoliaError::add_parseerror("Error adding body"))?;
             doc.apply_properties(properties).map_err(FoliaError::add_parseerror("Error applying properties"))?;
             doc.parse_elements(reader, &mut buf, &mut nsbuf)?;
+            if doc.is_legacy() {
+                doc.upgrade()?;
+            }
+            doc.autodeclare = autodeclare;
             Ok(doc)
         } else {
             Err(FoliaError::ParseError("No body found".to_string()))
@@ -338,12 +400,43 @@ impl Document {
     }
 
     ///Parses all elementsm from XML, this in turn invokes all parsers for the subelements
-    pub(crate) fn parse_elements<R: BufRead>(&mut self, reader: &mut Reader<R>, mut buf: &mut Vec<u8>, mut nsbuf: &mut Vec<u8>) -> Result<(), FoliaError> {
+    pub(crate) fn parse_elements<R: BufRead>(&mut self, reader: &mut Reader<R>, buf: &mut Vec<u8>, nsbuf: &mut Vec<u8>) -> Result<(), FoliaError> {
+        self.parse_elements_under(reader, buf, nsbuf, 0) //0 is the root/body element, we always start with it
+    }
+
+    ///Parses elements and adds them under the specified parent, stops when the parent is closed
+    ///(or at the end of the input). The ``<fragment>`` wrapper and the ``<annotations>`` and
+    ///``<provenance>`` blocks of fragments are skipped.
+    pub(crate) fn parse_elements_under<R: BufRead>(&mut self, reader: &mut Reader<R>, mut buf: &mut Vec<u8>, mut nsbuf: &mut Vec<u8>, parent_key: ElementKey) -> Result<(), FoliaError> {
         if !self.elementstore.items.is_empty() {
-            let mut stack: Vec<ElementKey> = vec![0]; //0 is the root/body element, we always start with it
+            let mut stack: Vec<ElementKey> = vec![parent_key];
             loop {
                 let e = reader.read_namespaced_event(&mut buf, &mut nsbuf)?;
                 match e {
+                    (Some(ns), Event::Start(ref e)) if ns == NSFOLIA && stack.len() == 1 && e.local_name() == b"fragment" => {},
+                    (Some(ns), Event::Empty(ref e)) if ns == NSFOLIA && stack.len() == 1 && e.local_name() == b"annotations" => {},
+                    (Some(ns), Event::Empty(ref e)) if ns == NSFOLIA && stack.len() == 1 && e.local_name() == b"provenance" => {},
+                    (Some(ns), Event::Start(ref e)) if ns == NSFOLIA && stack.len() == 1 && (e.local_name() == b"annotations" || e.local_name() == b"provenance") => {
+                        let name = e.name().to_vec();
+                        reader.read_to_end(name, &mut Vec::new())?;
+                    },
+                    (ns, Event::Empty(ref e)) if ns != Some(NSFOLIA) && self.in_foreigndata(&stack) => {
+                        //foreign XML, kept verbatim
+                        let mut namespaces = Vec::new();
+                        let xml = parse_foreign_xml(reader, &e.to_owned(), ns.map(|ns| ns.to_vec()), true, &mut Vec::new(), nsbuf, &mut namespaces)?;
+                        if let Some(parent) = stack.last().and_then(|parent_key| self.get_mut_elementdata(*parent_key)) {
+                            parent.push(DataType::ForeignXml(ForeignXml { xml, namespaces }));
+                        }
+                    },
+                    (ns, Event::Start(ref e)) if ns != Some(NSFOLIA) && self.in_foreigndata(&stack) => {
+                        //foreign XML, kept verbatim
+                        let (start, ns) = (e.to_owned(), ns.map(|ns| ns.to_vec()));
+                        let mut namespaces = Vec::new();
+                        let xml = parse_foreign_xml(reader, &start, ns, false, &mut Vec::new(), nsbuf, &mut namespaces)?;
+                        if let Some(parent) = stack.last().and_then(|parent_key| self.get_mut_elementdata(*parent_key)) {
+                            parent.push(DataType::ForeignXml(ForeignXml { xml, namespaces }));
+                        }
+                    },
                     (Some(ns), Event::Empty(ref e)) if ns == NSFOLIA => {
                         //EMPTY TAG FOUND (<tag/>)
                         //eprintln!("EMPTY TAG: {}", str::from_utf8(e.local_name()).expect("Tag is not valid utf-8"));
@@ -410,7 +503,9 @@ impl Document {
                     },
                     (None, Event::Text(s)) => {
                         let text = s.unescape_and_decode(reader)?;
-                        if text.trim() != "" {
+                        //whitespace is only significant inside text containers (e.g. between text markup elements)
+                        let significant = text.trim() != "" || stack.last().and_then(|parent_key| self.get_elementdata(*parent_key)).map(|parent| self.props(parent.elementtype).textcontainer) == Some(true);
+                        if significant {
                             if let Some(parent_key) = stack.last() {
                                 self.get_mut_elementdata(*parent_key).map( |parent| {
                                     parent.push(DataType::Text(text));
@@ -451,6 +546,198 @@ impl Document {
     }
 }
 
+impl Document {
+    ///Handles the old-style (FoLiA v1) default annotator on a declaration, which becomes the
+    ///default processor of the declaration
+    fn parse_legacy_annotator<R: BufRead>(&mut self, reader: &Reader<R>, event: &BytesStart, declaration_key: DecKey) -> Result<(), FoliaError> {
+        let mut annotator: Option<String> = None;
+        let mut annotatortype: Option<ProcessorType> = None;
+        for attrib in event.attributes() {
+            let attrib = attrib.expect("unwrapping declaration attribute");
+            match attrib.key {
+                b"annotator" => annotator = Some(attrib.unescape_and_decode_value(reader)?),
+                b"annotatortype" => {
+                    if let Attribute::AnnotatorType(processortype) = Attribute::parse(reader, &attrib)? {
+                        annotatortype = Some(processortype);
+                    }
+                },
+                _ => {}
+            }
+        }
+        if annotator.is_some() || annotatortype.is_some() {
+            let name = annotator.unwrap_or_else(|| "unknown".to_string());
+            let processor_key = self.legacy_processor(&name, annotatortype.unwrap_or_default())?;
+            if let Some(declaration) = self.get_mut_declaration(declaration_key) {
+                if !declaration.processors.contains(&processor_key) {
+                    declaration.processors.push(processor_key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///Are we currently directly within a ``<foreign-data>`` element?
+    fn in_foreigndata(&self, stack: &[ElementKey]) -> bool {
+        stack.last().and_then(|key| self.get_elementdata(*key)).map(|element| element.elementtype == ElementType::ForeignData) == Some(true)
+    }
+
+    ///Parses an XML fragment (as produced by ``xml_fragment()``) and adds the elements in it under
+    ///the specified parent. Declarations and processors included in the fragment are added if they
+    ///don't exist yet, processors are matched by ID. References to processors that are not known
+    ///in this document are dropped. Returns the keys of the added top-level elements.
+    pub fn parse_fragment(&mut self, parent_key: ElementKey, data: &str) -> Result<Vec<ElementKey>, FoliaError> {
+        let existing: Vec<ElementKey> = self.get_elementdata(parent_key).ok_or(
+            FoliaError::KeyError(format!("Specified element key not found: {:?}", parent_key))
+        )?.data.iter().filter_map(|item| match item {
+            DataType::Element(key) => Some(*key),
+            _ => None
+        }).collect();
+
+        //first pass: declarations and provenance
+        let mut reader = Reader::from_str(data);
+        reader.trim_text(false);
+        let mut buf = Vec::new();
+        let mut nsbuf = Vec::new();
+        let mut in_annotations = false;
+        let mut in_provenance = false;
+        let mut declaration_key: Option<DecKey> = None;
+        let mut annotators: Vec<(DecKey,String)> = Vec::new(); //mapping of declaration keys to processor ids
+        let mut processor_stack: Vec<ProcKey> = Vec::new();
+        loop {
+            let e = reader.read_namespaced_event(&mut buf, &mut nsbuf)?;
+            let empty = matches!(e, (_, Event::Empty(_)));
+            match e {
+                (Some(ns), Event::Start(ref e)) if ns == NSFOLIA && e.local_name() == b"fragment" => {},
+                (Some(ns), Event::Start(ref e)) if ns == NSFOLIA && e.local_name() == b"annotations" => {
+                    in_annotations = true;
+                },
+                (Some(ns), Event::Start(ref e)) if ns == NSFOLIA && e.local_name() == b"provenance" => {
+                    in_provenance = true;
+                },
+                (Some(ns), Event::Start(ref e)) | (Some(ns), Event::Empty(ref e)) if ns == NSFOLIA && in_annotations => {
+                    if e.local_name().ends_with(b"-annotation") {
+                        let declaration = Declaration::parse(&reader, e, e.local_name()).map_err(FoliaError::add_parseerror("Error parsing declaration"))?;
+                        declaration_key = Some(self.declare(declaration.annotationtype, &declaration.set, &declaration.alias, &declaration.format)?);
+                    } else if let (b"annotator", Some(declaration_key)) = (e.local_name(), declaration_key) {
+                        for attrib in e.attributes().flatten() {
+                            if attrib.key == b"processor" {
+                                annotators.push((declaration_key, attrib.unescape_and_decode_value(&reader)?));
+                            }
+                        }
+                    }
+                },
+                (Some(ns), Event::Start(ref e)) | (Some(ns), Event::Empty(ref e)) if ns == NSFOLIA && in_provenance && e.local_name() == b"processor" => {
+                    let processor = Processor::parse(&reader, e).map_err(FoliaError::add_parseerror("Error parsing processor"))?;
+                    let processor_key = match (self.get_processor_key_by_id(&processor.id), processor_stack.last()) {
+                        (Some(processor_key), _) => processor_key,
+                        (None, Some(parent_key)) => self.add_subprocessor(*parent_key, processor).map_err(FoliaError::add_parseerror("Error adding subprocessor"))?,
+                        (None, None) => self.add_processor(processor).map_err(FoliaError::add_parseerror("Error adding processor"))?,
+                    };
+                    if !empty {
+                        processor_stack.push(processor_key);
+                    }
+                },
+                (Some(ns), Event::End(ref e)) if ns == NSFOLIA && in_provenance && e.local_name() == b"processor" => {
+                    processor_stack.pop();
+                },
+                (_, Event::Start(_)) | (_, Event::Empty(_)) if !in_annotations && !in_provenance => break,
+                (Some(ns), Event::End(ref e)) if ns == NSFOLIA && e.local_name() == b"annotations" => {
+                    in_annotations = false;
+                },
+                (Some(ns), Event::End(ref e)) if ns == NSFOLIA && e.local_name() == b"provenance" => break,
+                (_, Event::Eof) => break,
+                (_,_) => {}
+            }
+            buf.clear();
+        }
+        //assign the processors to the declarations now that all of them are known
+        for (declaration_key, processor_id) in annotators {
+            if let Some(processor_key) = self.get_processor_key_by_id(&processor_id) {
+                if let Some(declaration) = self.get_mut_declaration(declaration_key) {
+                    if !declaration.processors.contains(&processor_key) {
+                        declaration.processors.push(processor_key);
+                    }
+                }
+            }
+        }
+
+        //second pass: elements
+        let mut reader = Reader::from_str(data);
+        reader.trim_text(false);
+        let mut buf = Vec::new();
+        let mut nsbuf = Vec::new();
+        self.parse_elements_under(&mut reader, &mut buf, &mut nsbuf, parent_key)?;
+
+        let added: Vec<ElementKey> = self.get_elementdata(parent_key).expect("parent").data.iter().filter_map(|item| match item {
+            DataType::Element(key) if !existing.contains(key) => Some(*key),
+            _ => None
+        }).collect();
+        if added.is_empty() {
+            return Err(FoliaError::ParseError("Fragment contains no FoLiA elements (is the FoLiA namespace declared?)".to_string()));
+        }
+        Ok(added)
+    }
+}
+
+///Reads an XML subtree in a foreign (non-FoLiA) namespace and returns it verbatim as a string. The
+///opening tag (``start``) has already been read. Namespaces that the subtree relies on but that were
+///declared outside of it are recorded in ``namespaces`` (prefix to namespace), the serialiser declares
+///them on the wrapping ``<foreign-data>`` element.
+pub(crate) fn parse_foreign_xml<R: BufRead>(reader: &mut Reader<R>, start: &BytesStart, namespace: Option<Vec<u8>>, empty: bool, buf: &mut Vec<u8>, nsbuf: &mut Vec<u8>, namespaces: &mut Vec<(String,String)>) -> Result<String,FoliaError> {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    //prefixes declared within the subtree, per open element
+    let mut declared: Vec<Vec<Vec<u8>>> = Vec::new();
+    record_namespace(start, namespace.as_deref(), &mut declared, namespaces);
+    if empty {
+        writer.write_event(Event::Empty(start.to_owned())).map_err(|e| FoliaError::ParseError(format!("Unable to copy foreign XML: {}", e)))?;
+    } else {
+        writer.write_event(Event::Start(start.to_owned())).map_err(|e| FoliaError::ParseError(format!("Unable to copy foreign XML: {}", e)))?;
+        while !declared.is_empty() {
+            buf.clear();
+            let (ns, event) = reader.read_namespaced_event(buf, nsbuf)?;
+            match &event {
+                Event::Start(e) => record_namespace(e, ns, &mut declared, namespaces),
+                Event::Empty(e) => {
+                    record_namespace(e, ns, &mut declared, namespaces);
+                    declared.pop();
+                },
+                Event::End(_) => {
+                    declared.pop();
+                },
+                Event::Eof => return Err(FoliaError::ParseError("Premature end of document in foreign XML".to_string())),
+                _ => {},
+            };
+            writer.write_event(event).map_err(|e| FoliaError::ParseError(format!("Unable to copy foreign XML: {}", e)))?;
+        }
+    }
+    String::from_utf8(writer.into_inner().into_inner()).map_err(|e| FoliaError::ParseError(format!("Foreign XML is not valid UTF-8: {}", e)))
+}
+
+///Records the namespace binding of the prefix of the element if it was not declared in the subtree itself
+fn record_namespace(start: &BytesStart, namespace: Option<&[u8]>, declared: &mut Vec<Vec<Vec<u8>>>, namespaces: &mut Vec<(String,String)>) {
+    let mut prefixes: Vec<Vec<u8>> = Vec::new();
+    for attrib in start.attributes().flatten() {
+        if attrib.key == b"xmlns" {
+            prefixes.push(Vec::new());
+        } else if attrib.key.starts_with(b"xmlns:") {
+            prefixes.push(attrib.key[6..].to_vec());
+        }
+    }
+    let prefix: Vec<u8> = match start.name().iter().position(|c| *c == b':') {
+        Some(index) => start.name()[..index].to_vec(),
+        None => Vec::new(),
+    };
+    if let Some(namespace) = namespace {
+        if !prefixes.contains(&prefix) && !declared.iter().any(|level| level.contains(&prefix)) {
+            let prefix = String::from_utf8_lossy(&prefix).into_owned();
+            if !namespaces.iter().any(|(p, _)| *p == prefix) {
+                namespaces.push((prefix, String::from_utf8_lossy(namespace).into_owned()));
+            }
+        }
+    }
+    declared.push(prefixes);
+}
+
 fn get_declaration_type(tag: &str) -> Result<AnnotationType, FoliaError> {
     if let Some(index) = tag.find("-") {
         let (declaration_type_string, suffix) = tag.split_at(index);
@@ -544,8 +831,7 @@ impl ElementData {
                                                                 with_attribs(vec![
                                                                     Attribute::Subset(properties.subset.unwrap().to_string()),
                                                                     Attribute::Class(value.clone())
-                                                                ]).
-                                                                with_children(vec![DataType::Text(value.clone())]);
+                                                                ]);
                                         children.push(child);
                                         continue 'outerloop;
                                     }
@@ -0,0 +1,264 @@
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::query::*;
+use crate::select::*;
+use crate::text::*;
+use crate::document::*;
+
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+///Determines how sentences and words are laid out in plain text
+pub enum PlainTextLayout {
+    ///Running text, sentences in a paragraph follow each other on the same line
+    #[default]
+    Running,
+    ///Each sentence on a separate line
+    SentencePerLine,
+    ///Each word on a separate line, with an empty line after each sentence
+    TokenPerLine,
+}
+
+#[derive(Clone,Default)]
+///Options for ``Document::to_plaintext()``
+pub struct PlainTextOptions {
+    ///Determines which text (set, textclass, corrections) is rendered
+    pub textparameters: TextParameters,
+    pub layout: PlainTextLayout,
+    ///Prefix headings with ``#`` markers according to the depth of the division they are in
+    pub headingmarkers: bool,
+}
+
+impl PlainTextOptions {
+    pub fn with_textparameters(mut self, textparameters: TextParameters) -> Self {
+        self.textparameters = textparameters;
+        self
+    }
+
+    pub fn with_layout(mut self, layout: PlainTextLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_headingmarkers(mut self, headingmarkers: bool) -> Self {
+        self.headingmarkers = headingmarkers;
+        self
+    }
+}
+
+///Collects blocks of text (separated by blank lines in the output)
+#[derive(Default)]
+struct PlainTextWriter {
+    blocks: Vec<String>,
+    current: String,
+    ///The delimiter to insert before the next inline text
+    delimiter: &'static str,
+}
+
+impl PlainTextWriter {
+    fn inline(&mut self, text: &str, delimiter: &'static str) {
+        if !self.current.is_empty() && !self.current.ends_with('\n') {
+            self.current.push_str(self.delimiter);
+        }
+        self.current.push_str(text);
+        self.delimiter = delimiter;
+    }
+
+    fn line(&mut self, text: &str) {
+        self.end_line();
+        self.current.push_str(text);
+        self.current.push('\n');
+    }
+
+    fn end_line(&mut self) {
+        if !self.current.is_empty() && !self.current.ends_with('\n') {
+            self.current.push('\n');
+        }
+    }
+
+    fn end_block(&mut self) {
+        let block = self.current.trim_end_matches('\n');
+        if !block.is_empty() {
+            self.blocks.push(block.to_string());
+        }
+        self.current.clear();
+    }
+
+    fn finish(mut self) -> String {
+        self.end_block();
+        if self.blocks.is_empty() {
+            String::new()
+        } else {
+            self.blocks.join("\n\n") + "\n"
+        }
+    }
+}
+
+///Returns the text of an element, an element without text yields an empty string
+pub(crate) fn element_text(element: &Element, textparameters: &TextParameters) -> Result<String,FoliaError> {
+    match element.text(textparameters) {
+        Ok(text) => Ok(text),
+        Err(FoliaError::NoTextError(_)) => Ok(String::new()),
+        Err(err) => Err(err),
+    }
+}
+
+///Returns the structural children of an element, following the right branch of corrections
+pub(crate) fn structure_children<'a>(element: &Element<'a>, textparameters: &TextParameters) -> Vec<Element<'a>> {
+    let mut children: Vec<Element<'a>> = Vec::new();
+    if let Some(document) = element.document {
+        for item in element.elementdata().data.iter() {
+            if let DataType::Element(key) = item {
+                if let Some(child) = document.get_element(*key) {
+                    if ElementGroup::Structure.contains(child.elementtype()) {
+                        children.push(child);
+                    } else if child.elementtype() == ElementType::Correction {
+                        if let Some(branch) = child.get_correction_branch(textparameters.correctionhandling) {
+                            children.extend(structure_children(&branch, textparameters));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    children
+}
+
+impl<'a> Element<'a> {
+    fn render_plaintext(&self, writer: &mut PlainTextWriter, options: &PlainTextOptions) -> Result<(),FoliaError> {
+        match self.elementtype() {
+            ElementType::Linebreak => {
+                if options.layout == PlainTextLayout::Running && !writer.current.is_empty() {
+                    writer.current.push('\n');
+                } else {
+                    writer.end_line();
+                }
+            },
+            ElementType::Whitespace => writer.end_block(),
+            ElementType::Hiddenword => {},
+            ElementType::Head => {
+                writer.end_block();
+                let text = element_text(self, &options.textparameters)?;
+                if !text.is_empty() {
+                    if options.headingmarkers {
+                        let depth = self.get_ancestors(ElementType::Division, Cmp::Any).count().max(1);
+                        writer.line(&format!("{} {}", "#".repeat(depth), text));
+                    } else {
+                        writer.line(&text);
+                    }
+                }
+                writer.end_block();
+            },
+            ElementType::Sentence | ElementType::Utterance if options.layout == PlainTextLayout::TokenPerLine => {
+                writer.end_line();
+                let mut has_words = false;
+                for word in self.select(Selector::elements().element(Cmp::Is(ElementType::Word)), Recursion::Always) {
+                    has_words = true;
+                    let text = element_text(&word, &options.textparameters)?;
+                    if !text.is_empty() {
+                        writer.line(&text);
+                    }
+                }
+                if !has_words {
+                    //untokenised sentence
+                    let text = element_text(self, &options.textparameters)?;
+                    if !text.is_empty() {
+                        writer.line(&text);
+                    }
+                }
+                writer.end_block();
+            },
+            ElementType::Sentence | ElementType::Utterance if structure_children(self, &options.textparameters).iter().any(|child| child.elementtype() == ElementType::Linebreak) => {
+                //render word by word so the linebreaks inside the sentence are preserved
+                if options.layout == PlainTextLayout::SentencePerLine {
+                    writer.end_line();
+                }
+                for child in structure_children(self, &options.textparameters).iter() {
+                    match child.elementtype() {
+                        ElementType::Word | ElementType::Hiddenword => child.render_plaintext(writer, options)?,
+                        ElementType::Linebreak => writer.end_line(),
+                        _ => {
+                            let text = element_text(child, &options.textparameters)?;
+                            if !text.is_empty() {
+                                writer.inline(&text, " ");
+                            }
+                        }
+                    }
+                }
+                match options.layout {
+                    PlainTextLayout::SentencePerLine => writer.end_line(),
+                    _ => writer.delimiter = " ",
+                }
+            },
+            ElementType::Sentence | ElementType::Utterance => {
+                let text = element_text(self, &options.textparameters)?;
+                if !text.is_empty() {
+                    match options.layout {
+                        PlainTextLayout::SentencePerLine => writer.line(&text),
+                        _ => writer.inline(&text, " "),
+                    }
+                }
+            },
+            ElementType::Word => {
+                let text = element_text(self, &options.textparameters)?;
+                if !text.is_empty() {
+                    match (options.layout, self.attrib(AttribType::SPACE)) {
+                        (PlainTextLayout::TokenPerLine, _) => writer.line(&text),
+                        (_, Some(Attribute::Space(false))) => writer.inline(&text, ""),
+                        _ => writer.inline(&text, " "),
+                    }
+                }
+            },
+            ElementType::ListItem | ElementType::Row => {
+                writer.end_line();
+                let children = structure_children(self, &options.textparameters);
+                if children.is_empty() {
+                    writer.line(&element_text(self, &options.textparameters)?);
+                } else {
+                    let separator = if self.elementtype() == ElementType::Row { "\t" } else { " " };
+                    for child in children.iter() {
+                        let text = element_text(child, &options.textparameters)?;
+                        writer.inline(&text, separator);
+                    }
+                    writer.end_line();
+                }
+            },
+            elementtype => {
+                //block elements such as paragraphs and divisions
+                writer.end_block();
+                let children = structure_children(self, &options.textparameters);
+                if children.is_empty() {
+                    let text = element_text(self, &options.textparameters)?;
+                    if !text.is_empty() {
+                        writer.inline(&text, " ");
+                    }
+                } else {
+                    for child in children.iter() {
+                        child.render_plaintext(writer, options)?;
+                    }
+                }
+                if elementtype != ElementType::Text && elementtype != ElementType::Speech {
+                    writer.end_block();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///Renders this element and everything under it as plain text, preserving the layout. Blocks
+    ///such as paragraphs, divisions and headings are separated by blank lines.
+    pub fn to_plaintext(&self, options: &PlainTextOptions) -> Result<String,FoliaError> {
+        let mut writer = PlainTextWriter::default();
+        self.render_plaintext(&mut writer, options)?;
+        Ok(writer.finish())
+    }
+}
+
+impl Document {
+    ///Renders the whole body of the document as plain text, see ``Element::to_plaintext()``
+    pub fn to_plaintext(&self, options: &PlainTextOptions) -> Result<String,FoliaError> {
+        let body = self.get_element(0).ok_or(FoliaError::InternalError("Document has no body".to_string()))?;
+        body.to_plaintext(options)
+    }
+}
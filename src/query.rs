@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::time::Duration;
 
 use crate::common::*;
 use crate::types::*;
@@ -37,7 +38,8 @@ pub struct Query {
     pub subset: Cmp<String>,
     pub annotator: Cmp<String>,
     pub annotatortype: Cmp<ProcessorType>,
-    pub confidence: Cmp<f64>
+    pub confidence: Cmp<f64>,
+    pub timeinterval: Option<(Duration,Duration)>,
 }
 
 #[derive(Clone,PartialEq,Debug)]
@@ -141,6 +143,13 @@ impl Query {
         self
     }
 
+    ///Add a matching criterion on time: matches only elements whose begin and end time overlap
+    ///with the specified interval (begin inclusive, end exclusive)
+    pub fn timeinterval(mut self, begin: Duration, end: Duration) -> Self {
+        self.timeinterval = Some((begin, end));
+        self
+    }
+
     ///Creates an empty (all matching) select query
     pub fn select() -> Self {
         Self::default()
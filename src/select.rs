@@ -1,4 +1,5 @@
 use std::ops::Deref;
+use std::time::Duration;
 
 use crate::common::*;
 use crate::types::*;
@@ -31,6 +32,7 @@ pub struct Selector {
     pub confidence: Cmp<f64>,
     pub annotator: Cmp<String>,
     pub annotatortype: Cmp<ProcessorType>,
+    pub timeinterval: Option<(Duration,Duration)>,
     pub next: Option<Box<Selector>>
 }
 
@@ -45,6 +47,7 @@ impl Selector {
         selector.elementtype = query.elementtype.clone();
         selector.elementgroup = query.elementgroup.clone();
         selector.contexttype = query.contexttype.clone();
+        selector.timeinterval = query.timeinterval;
         selector.datatypes = vec![DataTypeSelector::Elements];
         //if we have subsets, we use contexttype instead of elementtype (because elementtype will
         //always be ElementType::feature)
@@ -165,6 +168,12 @@ impl Selector {
         self
     }
 
+    ///Sets the selector to also yield foreign XML
+    pub fn with_foreignxml(mut self) -> Self {
+        self.datatypes.push(DataTypeSelector::ForeignXml);
+        self
+    }
+
     ///Constrains the selector by element type
     pub fn element(mut self, value: Cmp<ElementType>) -> Self {
         self.elementtype = value;
@@ -180,7 +189,7 @@ impl Selector {
 
     ///Creates a selector on all data (alias for Selector::default())
     pub fn all_data() -> Self {
-        Selector::default().with_elements().with_text().with_comments()
+        Selector::default().with_elements().with_text().with_comments().with_foreignxml()
     }
 
     ///Constrains the selector by element group
@@ -206,6 +215,15 @@ impl Selector {
         self.processor != Cmp::Unmatchable
     }
 
+    ///Tests if the element's begin and end time overlap with the time interval of the selector
+    fn matches_time(&self, element: &Element) -> bool {
+        if let (Some((begin, end)), Some(elementbegin), Some(elementend)) = (self.timeinterval, element.begintime(), element.endtime()) {
+            elementbegin < end && elementend > begin
+        } else {
+            false
+        }
+    }
+
     ///Tests if the selector matches against the specified data item, given an element store.
     ///There is no need to invoke this directly if you use a ``SelectIterator``.
     pub fn matches(&self, document: &Document, item: &DataType) -> bool {
@@ -228,7 +246,8 @@ impl Selector {
                     (self.class == Cmp::Any || self.class.matches(element.class_key().as_ref())) &&
                     (self.processor == Cmp::Any || self.processor.matches(element.processor_key().as_ref())) &&
                     (self.annotator == Cmp::Any || self.annotator.matches(element.annotator().map(|s| s.to_string()).as_ref())) &&
-                    (self.annotatortype == Cmp::Any || self.annotatortype.matches(element.annotatortype().as_ref()))
+                    (self.annotatortype == Cmp::Any || self.annotatortype.matches(element.annotatortype().as_ref())) &&
+                    (self.timeinterval.is_none() || self.matches_time(&element))
                 } else {
                     //element does not exist, can never match
                     false
@@ -236,6 +255,7 @@ impl Selector {
             },
             DataType::Text(_) => self.datatypes.contains(&DataTypeSelector::Text),
             DataType::Comment(_) => self.datatypes.contains(&DataTypeSelector::Comments),
+            DataType::ForeignXml(_) => self.datatypes.contains(&DataTypeSelector::ForeignXml),
             DataType::AddElement(_) => false,
         };
         if let Some(next) = &self.next {
@@ -307,6 +327,7 @@ pub enum DataTypeSelector {
     Elements,
     Text,
     Comments,
+    ForeignXml,
 }
 
 
@@ -8,7 +8,7 @@ use std::string::ToString;
 use std::io::Write;
 use std::io::BufWriter;
 use quick_xml::{Reader,Writer};
-use quick_xml::events::{Event,BytesStart,BytesEnd,BytesText};
+use quick_xml::events::{Event,BytesStart,BytesEnd,BytesText,BytesDecl};
 
 use crate::common::*;
 use crate::types::*;
@@ -18,35 +18,164 @@ use crate::attrib::*;
 use crate::store::*;
 use crate::elementstore::*;
 use crate::metadata::*;
+use crate::specification::*;
 use crate::select::*;
+use crate::text::*;
 use crate::document::Document;
 
 const NL: &[u8] = b"\n";
 
+///The indentation used for canonical serialisation
+const CANONICAL_INDENT: usize = 2;
+
+///The order in which the attributes of an element are written in canonical serialisation,
+///attributes not in this list follow in alphabetical order
+const CANONICAL_ATTRIBUTE_ORDER: &[&str] = &["xml:id", "set", "subset", "class", "processor"];
+
+///The element groups that are written as ``typegroup`` in explicit form, in order of precedence
+const TYPEGROUPS: &[(ElementGroup, &str)] = &[
+    (ElementGroup::Structure, "structure"),
+    (ElementGroup::Subtoken, "subtoken"),
+    (ElementGroup::Inline, "inline"),
+    (ElementGroup::Span, "span"),
+    (ElementGroup::SpanRole, "spanrole"),
+    (ElementGroup::Layer, "layer"),
+    (ElementGroup::Content, "content"),
+    (ElementGroup::TextMarkup, "textmarkup"),
+    (ElementGroup::HigherOrder, "higherorder"),
+];
+
 fn to_serialisation_error(err: quick_xml::Error) -> FoliaError {
     FoliaError::SerialisationError(format!("{}",err))
 }
 
+#[derive(Clone,Debug,PartialEq)]
+///Options for serialisation to XML, use the ``with_*`` methods to build them from the defaults
+pub struct SerialiseOptions {
+    ///Indentation (number of spaces), 0 disables indentation
+    pub indent: usize,
+    ///Canonical serialisation (see ``Document::xml_canonical()``), this implies a fixed indentation
+    pub canonical: bool,
+    ///Explicit form (``form="explicit"``) for tools that can't resolve defaults: sets, processors
+    ///and text classes are always written, every element carries its ``typegroup``, features are
+    ///never expressed as attributes and word references carry the text of the word (``t``)
+    pub explicit: bool,
+    ///Omit sets and processors that equal the declared defaults (ignored in explicit form)
+    pub omit_defaults: bool,
+    ///Write an XML declaration (``<?xml version="1.0" encoding="utf-8"?>``)
+    pub xml_declaration: bool,
+    ///Write comments
+    pub comments: bool,
+    ///Elements of these annotation types are not written, along with everything under them and
+    ///their declarations
+    pub exclude: Vec<AnnotationType>,
+    ///Write empty elements as self-closing tags (``<w/>``) rather than paired tags (``<w></w>``)
+    pub self_closing: bool,
+}
+
+impl Default for SerialiseOptions {
+    fn default() -> Self {
+        Self {
+            indent: 0,
+            canonical: false,
+            explicit: false,
+            omit_defaults: true,
+            xml_declaration: false,
+            comments: true,
+            exclude: Vec::new(),
+            self_closing: true,
+        }
+    }
+}
+
+impl SerialiseOptions {
+    pub fn with_indent(mut self, indent: usize) -> SerialiseOptions {
+        self.indent = indent;
+        self
+    }
+    pub fn with_canonical(mut self, value: bool) -> SerialiseOptions {
+        self.canonical = value;
+        self
+    }
+    pub fn with_explicit(mut self, value: bool) -> SerialiseOptions {
+        self.explicit = value;
+        self
+    }
+    pub fn with_omit_defaults(mut self, value: bool) -> SerialiseOptions {
+        self.omit_defaults = value;
+        self
+    }
+    pub fn with_xml_declaration(mut self, value: bool) -> SerialiseOptions {
+        self.xml_declaration = value;
+        self
+    }
+    pub fn with_comments(mut self, value: bool) -> SerialiseOptions {
+        self.comments = value;
+        self
+    }
+    pub fn with_exclude(mut self, annotationtype: AnnotationType) -> SerialiseOptions {
+        self.exclude.push(annotationtype);
+        self
+    }
+    pub fn with_self_closing(mut self, value: bool) -> SerialiseOptions {
+        self.self_closing = value;
+        self
+    }
+}
+
+///Writes an empty element, either self-closing or as a pair of tags depending on the options
+fn write_empty(writer: &mut Writer<Cursor<Vec<u8>>>, start: BytesStart, options: &SerialiseOptions) -> Result<(), FoliaError> {
+    if options.self_closing {
+        writer.write_event(Event::Empty(start)).map_err(to_serialisation_error)?;
+    } else {
+        let end = BytesEnd::owned(start.name().to_vec());
+        writer.write_event(Event::Start(start)).map_err(to_serialisation_error)?;
+        writer.write_event(Event::End(end)).map_err(to_serialisation_error)?;
+    }
+    Ok(())
+}
+
 impl Document {
     ///Serialises a document to XML (vector of bytes, utf-8)
     pub fn xml(&self, root_key: ElementKey, indent: usize) -> Result<Vec<u8>, FoliaError> {
+        self.xml_with_options(root_key, &SerialiseOptions::default().with_indent(indent))
+    }
+
+    ///Serialises a document to XML in canonical form: declarations, metadata and attributes are
+    ///written in a fixed order, with fixed indentation and with whitespace in text collapsed.
+    ///Two semantically equal documents serialise to identical bytes.
+    pub fn xml_canonical(&self, root_key: ElementKey) -> Result<Vec<u8>, FoliaError> {
+        self.xml_with_options(root_key, &SerialiseOptions::default().with_canonical(true))
+    }
+
+    ///Serialises a document to XML (vector of bytes, utf-8) with the specified options
+    pub fn xml_with_options(&self, root_key: ElementKey, options: &SerialiseOptions) -> Result<Vec<u8>, FoliaError> {
+        let indent = if options.canonical { CANONICAL_INDENT } else { options.indent };
         let mut writer = match indent {
             0 => Writer::new(Cursor::new(Vec::new())),
             indent =>  Writer::new_with_indent(Cursor::new(Vec::new()), b' ', indent)
         };
 
+        if options.xml_declaration {
+            writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"utf-8"), None))).map_err(to_serialisation_error)?;
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
+        }
+
         let mut doc_start = BytesStart::borrowed_name(b"FoLiA");
         doc_start.push_attribute(("xmlns", str::from_utf8(NSFOLIA).unwrap() ));
         doc_start.push_attribute(("xmlns:xlink", str::from_utf8(NSXLINK).unwrap() ));
         doc_start.push_attribute(("xml:id",self.id.as_str()));
         doc_start.push_attribute(("version",FOLIAVERSION ));
         doc_start.push_attribute(("generator", GENERATOR ));
+        if options.explicit {
+            doc_start.push_attribute(("form", "explicit"));
+        }
         writer.write_event(Event::Start(doc_start)).map_err(to_serialisation_error)?;
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
 
-        self.xml_metadata(&mut writer)?;
+        self.xml_metadata(&mut writer, options)?;
 
-        self.xml_elements(&mut writer, root_key)?;
+        self.xml_elements(&mut writer, root_key, options, false, false)?;
 
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         writer.write_event(Event::End(BytesEnd::borrowed(b"FoLiA"))).map_err(to_serialisation_error)?;
@@ -54,7 +183,63 @@ impl Document {
         Ok(result)
     }
 
-    fn xml_metadata(&self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<(), FoliaError> {
+    ///Serialises a subtree to a standalone XML fragment in the FoLiA namespace, i.e. without the
+    ///``<FoLiA>`` wrapper and metadata. If ``with_declarations`` is set, the subtree is wrapped in
+    ///a ``<fragment>`` element along with the declarations it uses. Fragments can be read back
+    ///into a document using ``parse_fragment()``. Sets and processors are always written explicitly, as
+    ///a default here need not be the default in the document the fragment ends up in. With
+    ///declarations, the processors that the fragment refers to are included as provenance.
+    pub fn xml_fragment(&self, key: ElementKey, options: &SerialiseOptions, with_declarations: bool) -> Result<Vec<u8>, FoliaError> {
+        if self.get_elementdata(key).is_none() {
+            return Err(FoliaError::KeyError(format!("Specified element key not found: {:?}", key)));
+        }
+        let indent = if options.canonical { CANONICAL_INDENT } else { options.indent };
+        let mut writer = match indent {
+            0 => Writer::new(Cursor::new(Vec::new())),
+            indent =>  Writer::new_with_indent(Cursor::new(Vec::new()), b' ', indent)
+        };
+        if options.xml_declaration {
+            writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"utf-8"), None))).map_err(to_serialisation_error)?;
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
+        }
+        if with_declarations {
+            let mut fragment_start = BytesStart::borrowed_name(b"fragment");
+            fragment_start.push_attribute(("xmlns", str::from_utf8(NSFOLIA).unwrap() ));
+            fragment_start.push_attribute(("xmlns:xlink", str::from_utf8(NSXLINK).unwrap() ));
+            writer.write_event(Event::Start(fragment_start)).map_err(to_serialisation_error)?;
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
+            let mut used: Vec<DecKey> = Vec::new();
+            let mut used_processors: Vec<ProcKey> = Vec::new();
+            for item in self.select_data_by_key(key, Selector::all_data(), Recursion::Always, true, false) {
+                if let DataType::Element(key) = item.data {
+                    if let Some(element) = self.get_element(*key) {
+                        if let Some(declaration_key) = element.declaration_key() {
+                            if !used.contains(&declaration_key) {
+                                used.push(declaration_key);
+                                if let Some(declaration) = self.get_declaration(declaration_key) {
+                                    used_processors.extend(declaration.processors.iter());
+                                }
+                            }
+                        }
+                        if let Some(processor_key) = element.processor_key() {
+                            used_processors.push(processor_key);
+                        }
+                    }
+                }
+            }
+            self.xml_declarations(&mut writer, options, Some(&used))?;
+            self.xml_provenance(&mut writer, options, Some(&used_processors))?;
+            self.xml_elements(&mut writer, key, options, false, true)?;
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
+            writer.write_event(Event::End(BytesEnd::borrowed(b"fragment"))).map_err(to_serialisation_error)?;
+        } else {
+            self.xml_elements(&mut writer, key, options, true, true)?;
+        }
+        let result = writer.into_inner().into_inner();
+        Ok(result)
+    }
+
+    fn xml_metadata(&self, writer: &mut Writer<Cursor<Vec<u8>>>, options: &SerialiseOptions) -> Result<(), FoliaError> {
         let mut metadata_start = BytesStart::borrowed_name(b"metadata");
         if let Some(metadatatype) = &self.metadata.metadatatype {
             metadata_start.push_attribute(("type", metadatatype.as_str() ));
@@ -64,9 +249,12 @@ impl Document {
         }
         writer.write_event(Event::Start(metadata_start)).map_err(to_serialisation_error)?;
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
-        self.xml_declarations(writer)?;
-        self.xml_provenance(writer)?;
-        for (meta_id, value) in self.metadata.data.iter() {
+        self.xml_declarations(writer, options, None)?;
+        self.xml_provenance(writer, options, None)?;
+        //metadata is kept in hashmaps, sort it so the output is stable
+        let mut data: Vec<(&String,&String)> = self.metadata.data.iter().collect();
+        data.sort();
+        for (meta_id, value) in data {
             let mut meta_start = BytesStart::borrowed_name(b"meta");
             meta_start.push_attribute(("id", meta_id.as_str() ));
             writer.write_event(Event::Start(meta_start)).map_err(to_serialisation_error)?;
@@ -74,17 +262,24 @@ impl Document {
             writer.write_event(Event::End(BytesEnd::borrowed(b"meta"))).map_err(to_serialisation_error)?;
             writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         }
+        self.xml_foreigndata(writer, &self.metadata.foreigndata)?;
         //there is a bit too much duplication going on here, to be solved later
-        for (submetadata_id, submetadata) in self.submetadata.iter() {
+        let mut submetadata: Vec<(&String,&Metadata)> = self.submetadata.iter().collect();
+        submetadata.sort_by_key(|(a,_)| *a);
+        for (submetadata_id, submetadata) in submetadata {
             let mut submetadata_start = BytesStart::borrowed_name(b"submetadata");
             submetadata_start.push_attribute(("xml:id", submetadata_id.as_str() ));
             if let Some(metadatatype) = &submetadata.metadatatype {
                 submetadata_start.push_attribute(("type", metadatatype.as_str() ));
             }
-            if let Some(src) = &self.metadata.src {
+            if let Some(src) = &submetadata.src {
                 submetadata_start.push_attribute(("src", src.as_str() ));
             }
-            for (meta_id, value) in submetadata.data.iter() {
+            writer.write_event(Event::Start(submetadata_start)).map_err(to_serialisation_error)?;
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
+            let mut data: Vec<(&String,&String)> = submetadata.data.iter().collect();
+            data.sort();
+            for (meta_id, value) in data {
                 let mut meta_start = BytesStart::borrowed_name(b"meta");
                 meta_start.push_attribute(("id", meta_id.as_str() ));
                 writer.write_event(Event::Start(meta_start)).map_err(to_serialisation_error)?;
@@ -92,64 +287,100 @@ impl Document {
                 writer.write_event(Event::End(BytesEnd::borrowed(b"meta"))).map_err(to_serialisation_error)?;
                 writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
             }
+            self.xml_foreigndata(writer, &submetadata.foreigndata)?;
+            writer.write_event(Event::End(BytesEnd::borrowed(b"submetadata"))).map_err(to_serialisation_error)?;
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         }
         writer.write_event(Event::End(BytesEnd::borrowed(b"metadata"))).map_err(to_serialisation_error)?;
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         Ok(())
     }
 
-    fn xml_declarations(&self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<(), FoliaError> {
+    ///Serialise foreign metadata, the XML is written verbatim
+    fn xml_foreigndata(&self, writer: &mut Writer<Cursor<Vec<u8>>>, foreigndata: &[ForeignXml]) -> Result<(), FoliaError> {
+        for foreign in foreigndata.iter() {
+            let mut start = BytesStart::borrowed_name(b"foreign-data");
+            for (name, namespace) in foreign_namespace_declarations(std::iter::once(foreign)) {
+                start.push_attribute((name.as_str(), namespace));
+            }
+            writer.write_event(Event::Start(start)).map_err(to_serialisation_error)?;
+            writer.write_event(Event::Text(BytesText::from_escaped_str(foreign.xml.as_str()))).map_err(to_serialisation_error)?;
+            writer.write_event(Event::End(BytesEnd::borrowed(b"foreign-data"))).map_err(to_serialisation_error)?;
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
+        }
+        Ok(())
+    }
+
+    ///Serialises the declarations, optionally limited to the specified ones
+    fn xml_declarations(&self, writer: &mut Writer<Cursor<Vec<u8>>>, options: &SerialiseOptions, only: Option<&[DecKey]>) -> Result<(), FoliaError> {
         writer.write_event(Event::Start( BytesStart::borrowed_name(b"annotations"))).map_err(to_serialisation_error)?;
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
-        for declaration in self.declarations() {
-            if let Some(declaration) = declaration {
-                let tagname = format!("{}-annotation", declaration.annotationtype.as_str());
-                let mut dec_start = BytesStart::owned_name(tagname.as_bytes());
-                if let Some(set) = &declaration.set {
-                    dec_start.push_attribute(("set", set.as_str() ));
-                }
-                if let Some(alias) = &declaration.alias {
-                    dec_start.push_attribute(("alias", alias.as_str() ));
-                }
-                if let Some(format) = &declaration.format {
-                    dec_start.push_attribute(("format", format.as_str() ));
-                }
-                let dec_end = BytesEnd::owned(tagname.as_bytes().to_vec());
-                if declaration.processors.is_empty() {
-                    writer.write_event(Event::Empty(dec_start)).map_err(to_serialisation_error)?;
-                } else {
-                    writer.write_event(Event::Start(dec_start)).map_err(to_serialisation_error)?;
-                    for proc_key in declaration.processors.iter() {
-                        if let Some(processor) = self.get_processor(*proc_key) {
-                            let mut ann_start = BytesStart::borrowed_name(b"annotator");
-                            ann_start.push_attribute(("processor", processor.id.as_str() ));
-                            writer.write_event(Event::Empty(ann_start)).map_err(to_serialisation_error)?;
-                        } else {
-                            return Err(FoliaError::InternalError(format!("Unable to resolve referenced processor during serialisation")));
-                        }
+        let mut declarations: Vec<&Declaration> = self.declarations().enumerate()
+            .filter(|(key, _)| only.map(|only| only.contains(&(*key as DecKey))).unwrap_or(true))
+            .filter_map(|(_, declaration)| declaration.as_deref())
+            .filter(|declaration| !options.exclude.contains(&declaration.annotationtype))
+            .collect();
+        if options.canonical {
+            declarations.sort_by(|a,b| (a.annotationtype.as_str(), &a.set).cmp(&(b.annotationtype.as_str(), &b.set)));
+        }
+        for declaration in declarations {
+            let tagname = format!("{}-annotation", declaration.annotationtype.as_str());
+            let mut dec_start = BytesStart::owned_name(tagname.as_bytes());
+            if let Some(set) = &declaration.set {
+                dec_start.push_attribute(("set", set.as_str() ));
+            }
+            if let Some(alias) = &declaration.alias {
+                dec_start.push_attribute(("alias", alias.as_str() ));
+            }
+            if let Some(format) = &declaration.format {
+                dec_start.push_attribute(("format", format.as_str() ));
+            }
+            let dec_end = BytesEnd::owned(tagname.as_bytes().to_vec());
+            if declaration.processors.is_empty() {
+                write_empty(writer, dec_start, options)?;
+            } else {
+                writer.write_event(Event::Start(dec_start)).map_err(to_serialisation_error)?;
+                for proc_key in declaration.processors.iter() {
+                    if let Some(processor) = self.get_processor(*proc_key) {
+                        let mut ann_start = BytesStart::borrowed_name(b"annotator");
+                        ann_start.push_attribute(("processor", processor.id.as_str() ));
+                        write_empty(writer, ann_start, options)?;
+                    } else {
+                        return Err(FoliaError::InternalError(format!("Unable to resolve referenced processor during serialisation")));
                     }
-                    writer.write_event(Event::End(dec_end)).map_err(to_serialisation_error)?;
                 }
-                writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
+                writer.write_event(Event::End(dec_end)).map_err(to_serialisation_error)?;
             }
+            writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         }
         writer.write_event(Event::End(BytesEnd::borrowed(b"annotations"))).map_err(to_serialisation_error)?;
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         Ok(())
     }
 
-    fn xml_provenance(&self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<(), FoliaError> {
+    ///Serialises the provenance chain, if ``only`` is set then only the processors (with their
+    ///subprocessors) that contain any of the specified processors are included
+    fn xml_provenance(&self, writer: &mut Writer<Cursor<Vec<u8>>>, options: &SerialiseOptions, only: Option<&[ProcKey]>) -> Result<(), FoliaError> {
         writer.write_event(Event::Start( BytesStart::borrowed_name(b"provenance"))).map_err(to_serialisation_error)?;
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         for processor_key in self.provenancestore.chain.iter() {
-            self.xml_processor(writer, *processor_key)?;
+            if only.map(|only| only.iter().any(|key| self.processor_contains(*processor_key, *key))).unwrap_or(true) {
+                self.xml_processor(writer, *processor_key, options)?;
+            }
         }
         writer.write_event(Event::End(BytesEnd::borrowed(b"provenance"))).map_err(to_serialisation_error)?;
         writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
         Ok(())
     }
 
-    fn xml_processor(&self, writer: &mut Writer<Cursor<Vec<u8>>>, processor_key: ProcKey) -> Result<(),FoliaError> {
+    ///Is the processor either the specified one or one of its (nested) subprocessors?
+    fn processor_contains(&self, processor_key: ProcKey, needle: ProcKey) -> bool {
+        processor_key == needle || self.get_processor(processor_key).map(|processor| {
+            processor.processors.iter().any(|subprocessor_key| self.processor_contains(*subprocessor_key, needle))
+        }).unwrap_or(false)
+    }
+
+    fn xml_processor(&self, writer: &mut Writer<Cursor<Vec<u8>>>, processor_key: ProcKey, options: &SerialiseOptions) -> Result<(),FoliaError> {
         if let Some(processor) = self.get_processor(processor_key) {
             let mut processor_start = BytesStart::borrowed_name(b"processor");
             processor_start.push_attribute(("xml:id", processor.id.as_str() ));
@@ -189,11 +420,11 @@ impl Document {
                 processor_start.push_attribute(("resourcelink", processor.resourcelink.as_str() ));
             }
             if processor.processors.is_empty() {
-                writer.write_event(Event::Empty(processor_start)).map_err(to_serialisation_error)?;
+                write_empty(writer, processor_start, options)?;
             } else {
                 writer.write_event(Event::Start(processor_start)).map_err(to_serialisation_error)?;
                 for subprocessor_key in processor.processors.iter() {
-                    self.xml_processor(writer, *subprocessor_key)?;
+                    self.xml_processor(writer, *subprocessor_key, options)?;
                 }
                 writer.write_event(Event::End(BytesEnd::borrowed(b"processor"))).map_err(to_serialisation_error)?;
             }
@@ -202,16 +433,67 @@ impl Document {
         Ok(())
     }
 
-    ///Serialize elements to XML
-    pub(crate) fn xml_elements(&self, writer: &mut Writer<Cursor<Vec<u8>>>, root_key: ElementKey) -> Result<(), FoliaError> {
+    ///Returns the attribute name if the feature can be serialised in its shortcut form, i.e. as an
+    ///attribute on its parent (e.g. ``head="..."`` on ``<pos>``). This is only possible if the subset
+    ///has such a shortcut for the parent, the feature is the only one of its subset, and it carries
+    ///nothing besides its subset and class.
+    pub(crate) fn feature_shortcut(&self, feature: &Element) -> Option<&'static str> {
+        if feature.elementtype() != ElementType::Feature || !feature.elementdata().data.is_empty() || feature.class().is_none() {
+            return None;
+        }
+        let subset = feature.subset()?;
+        let parent = feature.get_parent()?;
+        if parent.declaration_key() != feature.declaration_key() || (feature.processor_key().is_some() && parent.processor_key() != feature.processor_key()) || feature.attribs().iter().any(|attrib| !attrib.decodable()) {
+            return None;
+        }
+        let attribname = self.props(parent.elementtype()).accepted_data.iter().find_map(|accepteddata| match accepteddata {
+            AcceptedData::AcceptElementType(elementtype) if ElementGroup::Feature.contains(*elementtype) => {
+                self.props(*elementtype).subset.filter(|s| *s == subset)
+            },
+            _ => None
+        })?;
+        let count = parent.elementdata().data.iter().filter(|item| match item {
+            DataType::Element(key) => self.get_element(*key).map(|sibling| sibling.elementtype() == ElementType::Feature && sibling.subset() == Some(subset)).unwrap_or(false),
+            _ => false
+        }).count();
+        if count == 1 {
+            Some(attribname)
+        } else {
+            None
+        }
+    }
+
+    ///Returns true if the element is not serialised as an element at all, either because its
+    ///annotation type is excluded or because it is a feature expressed as an attribute on its parent
+    fn xml_omitted(&self, element: &Element, options: &SerialiseOptions) -> bool {
+        if let Some(annotationtype) = self.props(element.elementtype()).annotationtype {
+            if options.exclude.contains(&annotationtype) {
+                return true;
+            }
+        }
+        !options.explicit && self.feature_shortcut(element).is_some()
+    }
+
+    ///Serialize elements to XML, if ``namespaced`` is set the FoLiA namespace is declared on the root element.
+    ///If ``explicit_defaults`` is set, sets and processors are always written, even if they are the declared defaults.
+    pub(crate) fn xml_elements(&self, writer: &mut Writer<Cursor<Vec<u8>>>, root_key: ElementKey, options: &SerialiseOptions, namespaced: bool, explicit_defaults: bool) -> Result<(), FoliaError> {
         //caches declarations that are defaults
         let dec_is_default: Vec<bool> = self.declarationstore.default_mask();
+        let omit_defaults = options.omit_defaults && !options.explicit;
+        //depth of an omitted element whose descendants are being skipped
+        let mut skip_depth: Option<usize> = None;
 
         //Select children
         let mut stack: Vec<(BytesEnd,ElementType,String)> = vec![];
         let mut previous_depth = 0;
         let mut last_start: String = "<ROOT>".to_string();
         for item in self.select_data_by_key(root_key,Selector::all_data(),Recursion::Always, true, false) {
+            if let Some(depth) = skip_depth {
+                if item.depth > depth {
+                    continue;
+                }
+                skip_depth = None;
+            }
             while item.depth < previous_depth {
                 if let Some((end,elementtype,_tagstring)) = stack.pop() {
                     writer.write_event(Event::End(end)).map_err(to_serialisation_error)?;
@@ -224,6 +506,18 @@ impl Document {
                 }
                 previous_depth -= 1;
             }
+            if let DataType::Element(key) = item.data {
+                if self.get_element(*key).map(|element| self.xml_omitted(&element, options)).unwrap_or(false) {
+                    //excluded or serialised as an attribute on the parent already, note that
+                    //previous_depth is deliberately left untouched
+                    skip_depth = Some(item.depth);
+                    continue;
+                }
+            } else if let DataType::Comment(_) = item.data {
+                if !options.comments {
+                    continue;
+                }
+            }
             match item.data {
                 DataType::Element(key) => {
                     if let Some(element) = self.get_element(*key) {
@@ -231,24 +525,39 @@ impl Document {
                         //eprintln!("[DEBUG] (processing tag {} ({}))", tagstring.as_str(), item.depth);
                         let tag = tagstring.as_bytes();
                         let mut start = BytesStart::owned(tag.to_vec(), tag.len());
+                        if namespaced && *key == root_key {
+                            start.push_attribute(("xmlns", str::from_utf8(NSFOLIA).unwrap() ));
+                            start.push_attribute(("xmlns:xlink", str::from_utf8(NSXLINK).unwrap() ));
+                        }
+                        let mut attribs: Vec<(&str,String)> = Vec::new();
                         for attrib in element.attribs().iter() {
                             if *attrib != Attribute::Ignore && !attrib.decodable()  {
-                                start.push_attribute((attrib.attribtype().into(), format!("{}",attrib).as_str() ));
+                                attribs.push((attrib.attribtype().into(), format!("{}",attrib)));
                             }
                         }
                         if let Some(declaration_key) = element.declaration_key() {
                             //check if the declaration is the default, no need to serialise set then
-                            if !dec_is_default.get(declaration_key as usize).expect("checking default")  && element.elementtype() != ElementType::Feature {
+                            if (!omit_defaults || explicit_defaults || !dec_is_default.get(declaration_key as usize).expect("checking default"))  && element.elementtype() != ElementType::Feature {
                                 //decode encoded attributes
                                 if let Some(set) = element.set() {
-                                    start.push_attribute(("set", set) );
+                                    attribs.push(("set", set.to_string()));
                                 }
                             }
                             if let Some(subset) = element.subset() {
-                                start.push_attribute(("subset", subset) );
+                                attribs.push(("subset", subset.to_string()));
                             }
                             if let Some(class) = element.class() {
-                                start.push_attribute(("class", class) );
+                                attribs.push(("class", class.to_string()));
+                            }
+                            //features that can be expressed as attributes
+                            for item in element.data.data.iter() {
+                                if let DataType::Element(feature_key) = item {
+                                    if let Some(feature) = self.get_element(*feature_key) {
+                                        if let (false, Some(attribname), Some(class)) = (options.explicit, self.feature_shortcut(&feature), feature.class()) {
+                                            attribs.push((attribname, class.to_string()));
+                                        }
+                                    }
+                                }
                             }
                             if let Some(processor) = element.processor() {
                                 //check if this processor is the default one, if so we don't need
@@ -263,13 +572,61 @@ impl Document {
                                     false
                                 };
 
-                                if !is_default {
-                                    start.push_attribute(("processor", processor) );
+                                if !omit_defaults || explicit_defaults || !is_default {
+                                    attribs.push(("processor", processor.to_string()));
+                                }
+                            }
+                        }
+                        if options.explicit {
+                            if (element.elementtype() == ElementType::TextContent || element.elementtype() == ElementType::PhonContent) && element.declaration_key().is_none() {
+                                //text and phonetic content are not declared here, write the defaults they imply
+                                let default_key = self.props(element.elementtype()).annotationtype.and_then(|annotationtype| self.declarationstore.get_default_key(annotationtype));
+                                if let Some(declaration) = default_key.and_then(|key| self.get_declaration(key)) {
+                                    if let Some(set) = &declaration.set {
+                                        attribs.push(("set", set.to_string()));
+                                    }
+                                    if element.attrib(AttribType::PROCESSOR).is_none() {
+                                        if let Some(processor) = declaration.default_processor().and_then(|key| self.get_processor(key)) {
+                                            attribs.push(("processor", processor.id.to_string()));
+                                        }
+                                    }
+                                }
+                                if element.attrib(AttribType::CLASS).is_none() {
+                                    attribs.push(("class", "current".to_string()));
+                                }
+                            }
+                            if element.elementtype() == ElementType::WordReference && element.attrib(AttribType::TEXT).is_none() {
+                                if let Some(word) = element.elementdata().idref().and_then(|id| self.get_element_by_id(id)) {
+                                    attribs.push(("t", word.text(&TextParameters::default())?));
                                 }
                             }
+                            if let Some(typegroup) = typegroup(element.elementtype()) {
+                                attribs.push(("typegroup", typegroup.to_string()));
+                            }
+                        }
+                        if options.canonical {
+                            attribs.sort_by_key(|(name,_)| canonical_attribute_rank(name));
+                        }
+                        for (name, value) in attribs.iter() {
+                            start.push_attribute((*name, value.as_str()));
+                        }
+                        if element.elementtype() == ElementType::ForeignData {
+                            let foreignxml = element.data.data.iter().filter_map(|item| match item {
+                                DataType::ForeignXml(foreign) => Some(foreign),
+                                _ => None
+                            });
+                            for (name, namespace) in foreign_namespace_declarations(foreignxml) {
+                                start.push_attribute((name.as_str(), namespace));
+                            }
                         }
-                        if element.data.is_empty() {
-                            writer.write_event(Event::Empty(start)).map_err(to_serialisation_error)?;
+                        let is_empty = element.data.data.iter().all(|item| match item {
+                            DataType::Element(key) => self.get_element(*key).map(|child| self.xml_omitted(&child, options)).unwrap_or(false),
+                            DataType::Comment(_) => !options.comments,
+                            DataType::SpanReference(_) => true,
+                            _ => false
+                        });
+                        if is_empty {
+                            write_empty(writer, start, options)?;
                         } else {
                             writer.write_event(Event::Start(start)).map_err(to_serialisation_error)?;
                             let end = BytesEnd::owned(tag.to_vec());
@@ -277,21 +634,28 @@ impl Document {
                             last_start = tagstring.clone();
                             stack.push((end,element.elementtype(),tagstring));
                         }
-                        if !ElementGroup::TextMarkup.contains(element.elementtype()) && element.elementtype() != ElementType::TextContent && element.elementtype() != ElementType::PhonContent {
+                        if !ElementGroup::TextMarkup.contains(element.elementtype()) && element.elementtype() != ElementType::TextContent && element.elementtype() != ElementType::PhonContent && element.elementtype() != ElementType::ForeignData {
                             writer.write_event(Event::Text(BytesText::from_plain(NL))).map_err(to_serialisation_error)?;
                         }
                     }
                 },
                 DataType::Text(text) => {
                     //eprintln!("[DEBUG] (processing text)");
-                    let text = BytesText::from_plain_str(text.as_str());
-                    writer.write_event(Event::Text(text)).map_err(to_serialisation_error)?;
+                    if options.canonical {
+                        let text = collapse_whitespace(text);
+                        writer.write_event(Event::Text(BytesText::from_plain_str(text.as_str()))).map_err(to_serialisation_error)?;
+                    } else {
+                        writer.write_event(Event::Text(BytesText::from_plain_str(text.as_str()))).map_err(to_serialisation_error)?;
+                    }
                 },
                 DataType::Comment(comment) => {
                     //eprintln!("[DEBUG] (processing comment)");
                     let text = BytesText::from_plain_str(comment.as_str());
                     writer.write_event(Event::Comment(text)).map_err(to_serialisation_error)?;
                 }
+                DataType::ForeignXml(foreign) => {
+                    writer.write_event(Event::Text(BytesText::from_escaped_str(foreign.xml.as_str()))).map_err(to_serialisation_error)?;
+                },
                 DataType::SpanReference(_) | DataType::AddElement(_) => {
                     //does not serialise
                 },
@@ -308,3 +672,49 @@ impl Document {
         Ok(())
     }
 }
+
+///Returns the namespace declarations (attribute name and namespace) for the bindings that foreign
+///XML relies on but does not make itself, these are added to the wrapping ``<foreign-data>`` element
+fn foreign_namespace_declarations<'a>(foreignxml: impl Iterator<Item=&'a ForeignXml>) -> Vec<(String,&'a str)> {
+    let mut declarations: Vec<(String,&str)> = Vec::new();
+    for foreign in foreignxml {
+        for (prefix, namespace) in foreign.namespaces.iter() {
+            let name = if prefix.is_empty() { "xmlns".to_string() } else { format!("xmlns:{}", prefix) };
+            if !declarations.iter().any(|(n,_)| *n == name) {
+                declarations.push((name, namespace.as_str()));
+            }
+        }
+    }
+    declarations
+}
+
+///Returns the type group of an element type as written in explicit form
+fn typegroup(elementtype: ElementType) -> Option<&'static str> {
+    TYPEGROUPS.iter().find(|(group, _)| group.contains(elementtype)).map(|(_, name)| *name)
+}
+
+///Returns the sort key of an attribute in canonical serialisation
+fn canonical_attribute_rank(name: &str) -> (usize, &str) {
+    match CANONICAL_ATTRIBUTE_ORDER.iter().position(|x| *x == name) {
+        Some(rank) => (rank, ""),
+        None => (CANONICAL_ATTRIBUTE_ORDER.len(), name),
+    }
+}
+
+///Collapses all runs of whitespace into a single space
+fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                result.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            result.push(c);
+            in_whitespace = false;
+        }
+    }
+    result
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::fs;
+use std::convert::TryInto;
+
+use crate::common::*;
+use crate::types::*;
+use crate::error::*;
+use crate::element::*;
+use crate::elementstore::*;
+use crate::metadata::*;
+use crate::document::*;
+
+///Magic bytes with which every snapshot starts
+pub const SNAPSHOT_MAGIC: &[u8;8] = b"FOLIASNP";
+
+///Version of the snapshot format, increment whenever the layout of the snapshot changes
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+///Version of the library that wrote the snapshot, snapshots hold internal (encoded) structures so are
+///only compatible with the exact same library version
+pub const SNAPSHOT_LIBRARY_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+//The borrowed (writing) and owned (reading) variants must have identical field order and types
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    id: &'a str,
+    version: &'a str,
+    metadata: &'a Metadata,
+    submetadata: &'a HashMap<String,Metadata>,
+    elements: &'a Vec<Option<Box<ElementData>>>,
+    elementindex: &'a HashMap<String,ElementKey>,
+    declarations: &'a Vec<Option<Box<Declaration>>>,
+    declarationindex: &'a HashMap<String,DecKey>,
+    processors: &'a Vec<Option<Box<Processor>>>,
+    processorindex: &'a HashMap<String,ProcKey>,
+    chain: &'a Vec<ProcKey>,
+    active_processor: Option<ProcKey>,
+    autodeclare: bool,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    id: String,
+    version: String,
+    metadata: Metadata,
+    submetadata: HashMap<String,Metadata>,
+    elements: Vec<Option<Box<ElementData>>>,
+    elementindex: HashMap<String,ElementKey>,
+    declarations: Vec<Option<Box<Declaration>>>,
+    declarationindex: HashMap<String,DecKey>,
+    processors: Vec<Option<Box<Processor>>>,
+    processorindex: HashMap<String,ProcKey>,
+    chain: Vec<ProcKey>,
+    active_processor: Option<ProcKey>,
+    autodeclare: bool,
+}
+
+///Checks the snapshot header and returns the payload that follows it
+fn snapshot_payload(data: &[u8]) -> Result<&[u8], FoliaError> {
+    if data.len() < SNAPSHOT_MAGIC.len() + 6 || &data[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+        return Err(FoliaError::ParseError("Data is not a FoLiA snapshot".to_string()));
+    }
+    let mut offset = SNAPSHOT_MAGIC.len();
+    let formatversion = u32::from_le_bytes(data[offset..offset+4].try_into().expect("four bytes"));
+    offset += 4;
+    if formatversion != SNAPSHOT_FORMAT_VERSION {
+        return Err(FoliaError::ParseError(format!("Snapshot has format version {}, but this library only supports version {}", formatversion, SNAPSHOT_FORMAT_VERSION)));
+    }
+    let length = u16::from_le_bytes(data[offset..offset+2].try_into().expect("two bytes")) as usize;
+    offset += 2;
+    if data.len() < offset + length {
+        return Err(FoliaError::ParseError("Snapshot header is truncated".to_string()));
+    }
+    let libraryversion = &data[offset..offset+length];
+    if libraryversion != SNAPSHOT_LIBRARY_VERSION.as_bytes() {
+        return Err(FoliaError::ParseError(format!("Snapshot was written by library version {}, but this is version {}", String::from_utf8_lossy(libraryversion), SNAPSHOT_LIBRARY_VERSION)));
+    }
+    Ok(&data[offset+length..])
+}
+
+impl Document {
+    ///Serialises the document to a compact binary snapshot. The snapshot holds the internal
+    ///stores with all keys already encoded, so it reloads quickly but can only be read by the same library version.
+    pub fn to_snapshot(&self) -> Result<Vec<u8>, FoliaError> {
+        let snapshot = SnapshotRef {
+            id: &self.id,
+            version: &self.version,
+            metadata: &self.metadata,
+            submetadata: &self.submetadata,
+            elements: &self.elementstore.items,
+            elementindex: &self.elementstore.index,
+            declarations: &self.declarationstore.items,
+            declarationindex: &self.declarationstore.index,
+            processors: &self.provenancestore.items,
+            processorindex: &self.provenancestore.index,
+            chain: &self.provenancestore.chain,
+            active_processor: self.active_processor,
+            autodeclare: self.autodeclare,
+        };
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(SNAPSHOT_MAGIC);
+        data.extend_from_slice(&SNAPSHOT_FORMAT_VERSION.to_le_bytes());
+        data.extend_from_slice(&(SNAPSHOT_LIBRARY_VERSION.len() as u16).to_le_bytes());
+        data.extend_from_slice(SNAPSHOT_LIBRARY_VERSION.as_bytes());
+        bincode::serialize_into(&mut data, &snapshot).map_err(|e| FoliaError::SerialisationError(format!("Unable to serialise snapshot: {}", e)))?;
+        Ok(data)
+    }
+
+    ///Writes a binary snapshot of the document to file
+    pub fn write_snapshot(&self, filename: &str) -> Result<(), FoliaError> {
+        let data = self.to_snapshot()?;
+        fs::write(filename, data)?;
+        Ok(())
+    }
+
+    ///Loads a document from a binary snapshot, as produced by ``to_snapshot()``. Snapshots of
+    ///other format or library versions are rejected.
+    pub fn from_snapshot(data: &[u8]) -> Result<Self, FoliaError> {
+        let payload = snapshot_payload(data)?;
+        let snapshot: Snapshot = bincode::deserialize(payload).map_err(|e| FoliaError::ParseError(format!("Unable to load snapshot: {}", e)))?;
+        let elementstore = ElementStore {
+            items: snapshot.elements,
+            index: snapshot.elementindex,
+            ..ElementStore::default()
+        };
+        let declarationstore = DeclarationStore {
+            items: snapshot.declarations,
+            index: snapshot.declarationindex,
+        };
+        let provenancestore = ProvenanceStore {
+            items: snapshot.processors,
+            index: snapshot.processorindex,
+            chain: snapshot.chain,
+        };
+        Ok(Self {
+            id: snapshot.id,
+            version: snapshot.version,
+            filename: None,
+            elementstore,
+            provenancestore,
+            declarationstore,
+            metadata: snapshot.metadata,
+            submetadata: snapshot.submetadata,
+            active_processor: snapshot.active_processor,
+            autodeclare: snapshot.autodeclare,
+        })
+    }
+
+    ///Loads a document from a binary snapshot file
+    pub fn from_snapshot_file(filename: &str) -> Result<Self, FoliaError> {
+        let data = fs::read(filename)?;
+        let mut doc = Self::from_snapshot(&data)?;
+        doc.filename = Some(filename.to_string());
+        Ok(doc)
+    }
+}
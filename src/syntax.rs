@@ -0,0 +1,279 @@
+use std::fmt;
+
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::query::*;
+use crate::select::*;
+use crate::text::*;
+use crate::document::*;
+
+#[derive(Clone,Debug,PartialEq)]
+///A child of a constituent in a syntax tree
+pub enum SyntaxNode {
+    ///A nested constituent
+    Constituent(Constituent),
+    ///A word (the leaf of the tree), with its text
+    Word(ElementKey, String),
+}
+
+#[derive(Clone,Debug,PartialEq)]
+///A constituent (a syntactic unit) in a syntax tree
+pub struct Constituent {
+    ///The key of the syntactic unit
+    pub key: ElementKey,
+    ///The label (the class of the syntactic unit)
+    pub label: Option<String>,
+    ///Nested constituents and words, in document order
+    pub children: Vec<SyntaxNode>,
+}
+
+impl Constituent {
+    ///Returns the keys of all the words covered by this constituent
+    pub fn words(&self) -> Vec<ElementKey> {
+        let mut words: Vec<ElementKey> = Vec::new();
+        for child in self.children.iter() {
+            match child {
+                SyntaxNode::Constituent(constituent) => words.extend(constituent.words()),
+                SyntaxNode::Word(key, _) => words.push(*key),
+            }
+        }
+        words
+    }
+
+    ///Returns the nested constituents (not recursive)
+    pub fn constituents(&self) -> impl Iterator<Item=&Constituent> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxNode::Constituent(constituent) => Some(constituent),
+            SyntaxNode::Word(_,_) => None,
+        })
+    }
+
+    ///Returns a Penn Treebank-style bracketed representation of this constituent
+    pub fn to_bracketed(&self) -> String {
+        let mut s = String::new();
+        s.push('(');
+        if let Some(label) = &self.label {
+            s.push_str(&escape_bracketed(label));
+        }
+        for child in self.children.iter() {
+            s.push(' ');
+            match child {
+                SyntaxNode::Constituent(constituent) => s.push_str(&constituent.to_bracketed()),
+                SyntaxNode::Word(_, text) => s.push_str(&escape_bracketed(text)),
+            }
+        }
+        s.push(')');
+        s
+    }
+}
+
+#[derive(Clone,Debug,PartialEq)]
+///A view on a syntax layer, obtained through ``Element::syntax_tree()``
+pub struct SyntaxTree {
+    ///The key of the syntax layer
+    pub key: ElementKey,
+    ///The top-level constituents
+    pub constituents: Vec<Constituent>,
+}
+
+impl SyntaxTree {
+    ///Returns a Penn Treebank-style bracketed representation of the tree, one line per top-level constituent
+    pub fn to_bracketed(&self) -> String {
+        self.constituents.iter().map(|constituent| constituent.to_bracketed()).collect::<Vec<String>>().join("\n")
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_bracketed())
+    }
+}
+
+///Escapes brackets in labels and words the way the Penn Treebank does. Whitespace is replaced
+///by an underscore, which can not be reversed, so ``annotate_syntax()`` rejects words containing whitespace.
+fn escape_bracketed(s: &str) -> String {
+    let s: String = s.chars().map(|c| if c.is_whitespace() { '_' } else { c }).collect();
+    s.replace('(', "-LRB-").replace(')', "-RRB-")
+}
+
+fn unescape_bracketed(s: &str) -> String {
+    s.replace("-LRB-", "(").replace("-RRB-", ")")
+}
+
+///A constituent as parsed from a bracketed string, prior to being added to a document
+#[derive(Debug)]
+enum BracketedNode {
+    Constituent(Option<String>, Vec<BracketedNode>),
+    Word(String),
+}
+
+impl BracketedNode {
+    fn parse(bracketed: &str) -> Result<Vec<BracketedNode>,FoliaError> {
+        let bracketed = bracketed.replace('(', " ( ").replace(')', " ) ");
+        let mut tokens = bracketed.split_whitespace().peekable();
+        let mut nodes: Vec<BracketedNode> = Vec::new();
+        while let Some(token) = tokens.next() {
+            if token != "(" {
+                return Err(FoliaError::ParseError(format!("Expected '(' in bracketed string, got '{}'", token)));
+            }
+            nodes.push(Self::parse_constituent(&mut tokens)?);
+        }
+        Ok(nodes)
+    }
+
+    ///Parses a constituent, the opening bracket has already been consumed
+    fn parse_constituent<'t>(tokens: &mut std::iter::Peekable<impl Iterator<Item=&'t str>>) -> Result<BracketedNode,FoliaError> {
+        let label = match tokens.peek() {
+            Some(&"(") | Some(&")") => None,
+            Some(token) => Some(unescape_bracketed(token)),
+            None => return Err(FoliaError::ParseError("Unexpected end of bracketed string".to_string())),
+        };
+        if label.is_some() {
+            tokens.next();
+        }
+        let mut children: Vec<BracketedNode> = Vec::new();
+        loop {
+            match tokens.next() {
+                Some("(") => children.push(Self::parse_constituent(tokens)?),
+                Some(")") => break,
+                Some(word) => children.push(BracketedNode::Word(unescape_bracketed(word))),
+                None => return Err(FoliaError::ParseError("Unexpected end of bracketed string, missing ')'".to_string())),
+            }
+        }
+        Ok(BracketedNode::Constituent(label, children))
+    }
+
+    ///Converts the node into element data for a syntactic unit, consuming the words it covers
+    fn into_elementdata(self, words: &mut std::slice::Iter<(String,String)>, set: &str) -> Result<ElementData,FoliaError> {
+        match self {
+            BracketedNode::Constituent(label, children) => {
+                let mut elementdata = ElementData::new(ElementType::SyntacticUnit).with_attrib(Attribute::Set(set.to_string()));
+                if let Some(label) = label {
+                    elementdata.set_attrib(Attribute::Class(label));
+                }
+                for child in children {
+                    match child {
+                        BracketedNode::Word(text) => {
+                            let (id, wordtext) = words.next().ok_or(
+                                FoliaError::ValidationError(format!("Bracketed string has more words than the element (at '{}')", text))
+                            )?;
+                            if text != *wordtext {
+                                return Err(FoliaError::ValidationError(format!("Word in bracketed string ('{}') does not match the text of word {} ('{}')", text, id, wordtext)));
+                            }
+                            elementdata = elementdata.with_span(&[id.as_str()]);
+                        },
+                        constituent => {
+                            elementdata = elementdata.add_element(constituent.into_elementdata(words, set)?);
+                        }
+                    }
+                }
+                Ok(elementdata)
+            },
+            BracketedNode::Word(text) => Err(FoliaError::ParseError(format!("Word outside of a constituent in bracketed string: '{}'", text)))
+        }
+    }
+}
+
+impl<'a> Element<'a> {
+    ///Returns a tree view on the syntax layer of the specified set in this element (usually a sentence).
+    ///Returns an error if there is no such layer or if a word reference in the tree can not be resolved.
+    pub fn syntax_tree(&self, set: Cmp<String>) -> Result<SyntaxTree,FoliaError> {
+        let document = self.document.ok_or(FoliaError::InternalError("Element has no associated document".to_string()))?;
+        let layer = self.get_element(ElementType::SyntaxLayer, set, Recursion::No).ok_or(
+            FoliaError::KeyError(format!("No syntax layer found in element {}", self.id().unwrap_or("(no id)")))
+        )?;
+        let mut constituents: Vec<Constituent> = Vec::new();
+        for child in layer.elementdata().data.iter() {
+            if let DataType::Element(key) = child {
+                let child = document.get_element(*key).ok_or(FoliaError::KeyError(format!("No such element key: {}", key)))?;
+                if child.elementtype() == ElementType::SyntacticUnit {
+                    constituents.push(child.build_constituent()?);
+                }
+            }
+        }
+        Ok(SyntaxTree {
+            key: layer.key().ok_or(FoliaError::InternalError("Syntax layer has no key".to_string()))?,
+            constituents
+        })
+    }
+
+    fn build_constituent(&self) -> Result<Constituent,FoliaError> {
+        let document = self.document.ok_or(FoliaError::InternalError("Element has no associated document".to_string()))?;
+        let mut children: Vec<SyntaxNode> = Vec::new();
+        for item in self.data.data.iter() {
+            if let DataType::Element(key) = item {
+                let child = document.get_element(*key).ok_or(FoliaError::KeyError(format!("No such element key: {}", key)))?;
+                match child.elementtype() {
+                    ElementType::SyntacticUnit => children.push(SyntaxNode::Constituent(child.build_constituent()?)),
+                    ElementType::WordReference => {
+                        let word = child.resolve().ok_or_else(|| FoliaError::KeyError(format!(
+                            "Unable to resolve word reference to {} in syntactic unit {}", child.elementdata().idref().unwrap_or("(no id)"), self.id().unwrap_or("(no id)")
+                        )))?;
+                        let text = word.text(&TextParameters::default()).unwrap_or_default();
+                        children.push(SyntaxNode::Word(word.key().ok_or(FoliaError::InternalError("Word has no key".to_string()))?, text));
+                    },
+                    _ => {}
+                }
+            }
+        }
+        Ok(Constituent {
+            key: self.key().ok_or(FoliaError::InternalError("Syntactic unit has no key".to_string()))?,
+            label: self.class().map(|s| s.to_string()),
+            children,
+        })
+    }
+}
+
+impl Document {
+    ///High-level method to add a syntax tree to an element (usually a sentence) from a Penn
+    ///Treebank-style bracketed string, e.g. ``(S (NP (DT the) (NN man)) (VP (VBD slept)))``. The words
+    ///in the string must match the text of the words in the element, in order. A top-level
+    ///bracket without a label (as in ``( (S ...) )``) is discarded. If no set is provided, the
+    ///default set is used. Returns the keys of the added top-level syntactic units.
+    pub fn annotate_syntax(&mut self, parent_key: ElementKey, bracketed: &str, set: Option<&str>) -> Result<Vec<ElementKey>,FoliaError> {
+        let set: String = match set {
+            Some(set) => set.to_string(),
+            None => self.get_default_set(AnnotationType::SYNTAX).ok_or(
+                FoliaError::IncompleteError("No set defined when adding syntax annotation and none could be inferred".to_string())
+            )?.to_string()
+        };
+        let parent = self.get_element(parent_key).ok_or(
+            FoliaError::InternalError(format!("Specified element key not found: {:?}", parent_key))
+        )?;
+        let mut words: Vec<(String,String)> = Vec::new();
+        for word in parent.select(Selector::elements().element(Cmp::Is(ElementType::Word)), Recursion::Always) {
+            let id = word.id().ok_or(FoliaError::IncompleteError("Words must have an ID to be part of a syntax tree".to_string()))?;
+            let text = word.text(&TextParameters::default())?;
+            if text.contains(char::is_whitespace) {
+                return Err(FoliaError::ValidationError(format!("Word {} contains whitespace ('{}') and can not be expressed in a bracketed string", id, text)));
+            }
+            words.push((id.to_string(), text));
+        }
+        let mut nodes = BracketedNode::parse(bracketed)?;
+        if nodes.len() == 1 {
+            if let BracketedNode::Constituent(None, children) = &nodes[0] {
+                if children.iter().all(|child| matches!(child, BracketedNode::Constituent(_,_))) {
+                    if let BracketedNode::Constituent(None, children) = nodes.remove(0) {
+                        nodes = children;
+                    }
+                }
+            }
+        }
+        let mut words = words.iter();
+        let mut elements: Vec<ElementData> = Vec::new();
+        for node in nodes {
+            elements.push(node.into_elementdata(&mut words, &set)?);
+        }
+        if let Some((id, _)) = words.next() {
+            return Err(FoliaError::ValidationError(format!("Bracketed string does not cover all words, first uncovered word: {}", id)));
+        }
+        let mut keys: Vec<ElementKey> = Vec::new();
+        for element in elements {
+            keys.push(self.annotate(parent_key, element)?);
+        }
+        Ok(keys)
+    }
+}
@@ -12,6 +12,17 @@ use crate::metadata::*;
 use crate::select::*;
 use crate::document::*;
 
+#[derive(Clone,Copy,Debug,PartialEq,Default)]
+///Determines which branch of a correction is used when obtaining text
+pub enum CorrectionHandling {
+    ///Use the corrected text (``<new>``, or ``<current>`` if there is no new version)
+    #[default]
+    Current,
+    ///Use the text prior to correction (``<original>``, or ``<current>`` if there is no original version). This also
+    ///uses the ``original`` attribute on text markup corrections (``<t-correction>``)
+    Original,
+}
+
 #[derive(Clone)]
 ///Parameters for the text() method
 pub struct TextParameters {
@@ -19,6 +30,9 @@ pub struct TextParameters {
     pub textclass: Option<String>,
     pub strict: bool,
     pub retaintokenisation: bool,
+    pub correctionhandling: CorrectionHandling,
+    ///Show hyphenation breaks (``<t-hbr>``) in the text
+    pub hyphbreaks: bool,
 }
 
 impl<'a> Default for TextParameters {
@@ -28,6 +42,8 @@ impl<'a> Default for TextParameters {
             textclass: Some("current".to_string()),
             strict: false,
             retaintokenisation: false,
+            correctionhandling: CorrectionHandling::default(),
+            hyphbreaks: false,
         }
     }
 }
@@ -51,6 +67,16 @@ impl TextParameters {
         self.textclass = Some(textclass.to_string());
         self
     }
+
+    pub fn correctionhandling(mut self, correctionhandling: CorrectionHandling) -> Self {
+        self.correctionhandling = correctionhandling;
+        self
+    }
+
+    pub fn hyphbreaks(mut self, hyphbreaks: bool) -> Self {
+        self.hyphbreaks = hyphbreaks;
+        self
+    }
 }
 
 
@@ -60,6 +86,12 @@ impl<'a> Element<'a> {
 
     ///Returns the text content of a given element
     pub fn text_by_key(&self, set: DecKey, textclass: ClassKey, strict: bool, retaintokenisation: bool, previousdelimiter: Option<String>) -> Result<String,FoliaError> {
+        let textparameters = TextParameters { strict, retaintokenisation, ..TextParameters::default() };
+        self.text_by_key_with(set, textclass, &textparameters, previousdelimiter)
+    }
+
+    ///Returns the text content of a given element, the set and textclass in the text parameters are ignored in favour of the keys passed
+    pub(crate) fn text_by_key_with(&self, set: DecKey, textclass: ClassKey, textparameters: &TextParameters, previousdelimiter: Option<String>) -> Result<String,FoliaError> {
         let doc = self.document().ok_or(FoliaError::KeyError("Element has no associated document".to_string()))?;
 
         let properties = doc.props(self.elementtype());
@@ -74,15 +106,30 @@ impl<'a> Element<'a> {
                     },
                     DataType::Element(element_key) => {
                         if let Some(element) = doc.get_element(*element_key) {
-                            let properties = doc.props(element.elementtype());
-                            if properties.printable {
-                                if !text.is_empty() {
-                                    if let Some(textdelimiter) = properties.textdelimiter {
-                                        text += textdelimiter;
+                            match element.elementtype() {
+                                ElementType::Linebreak => {
+                                    text.push('\n');
+                                },
+                                ElementType::Hyphbreak if !textparameters.hyphbreaks => {},
+                                ElementType::Hyphbreak => {
+                                    let textpart = element.text_by_key_with(set,textclass,textparameters,None)?;
+                                    if textpart.is_empty() {
+                                        text.push('-');
+                                    } else {
+                                        text += &textpart;
+                                    }
+                                },
+                                ElementType::TextMarkupCorrection if textparameters.correctionhandling == CorrectionHandling::Original && element.has_attrib(AttribType::ORIGINAL) => {
+                                    if let Some(Attribute::Original(original)) = element.attrib(AttribType::ORIGINAL) {
+                                        text += original;
+                                    }
+                                },
+                                elementtype => {
+                                    if doc.props(elementtype).printable {
+                                        let textpart = element.text_by_key_with(set,textclass,textparameters,None)?;
+                                        text += &textpart;
                                     }
                                 }
-                                let textpart = element.text_by_key(set,textclass,strict, retaintokenisation,None)?;
-                                text += &textpart;
                             }
                         }
                     },
@@ -96,46 +143,57 @@ impl<'a> Element<'a> {
             //Get text from children first
             let mut delimiter: String = String::new();
             let mut text: String = String::new();
-            let mut textcontent_element: Option<Element> = None;
             for element in self.elementdata().data.iter() {
                 if let DataType::Element(element_key) = element {
                     if let Some(element) = doc.get_element(*element_key) {
                         if ElementGroup::Structure.contains(element.elementtype()) ||
-                           element.elementtype() == ElementType::Correction ||
                            ElementGroup::Span.contains(element.elementtype()) {
 
-                           if let Ok(textpart) = element.text_by_key(set,textclass,false, retaintokenisation, Some(delimiter.clone())) {
+                           if let Ok(textpart) = element.text_by_key_with(set,textclass,textparameters, Some(delimiter.clone())) {
                                //delimiter will be buffered and only printed upon next iteration
                                text += &textpart;
-                               if let Ok(s) = element.get_textdelimiter(retaintokenisation) {
+                               if let Ok(s) = element.get_textdelimiter(textparameters.retaintokenisation) {
                                    delimiter = s.to_string();
                                }
                            }
+                        } else if element.elementtype() == ElementType::Correction {
+                            //follow only the branch of the correction we are interested in
+                            if let Some(branch) = element.get_correction_branch(textparameters.correctionhandling) {
+                               if let Ok(textpart) = branch.text_by_key_with(set,textclass,textparameters, Some(delimiter.clone())) {
+                                   text += &textpart;
+                                   if let Ok(s) = branch.get_textdelimiter(textparameters.retaintokenisation) {
+                                       delimiter = s.to_string();
+                                   }
+                               }
+                            }
                         } else if element.elementtype() == ElementType::WordReference {
                             //follow the reference
                             if let Some(element) = element.resolve() {
-                               if let Ok(textpart) = element.text_by_key(set,textclass,false, retaintokenisation, Some(delimiter.clone())) {
+                               if let Ok(textpart) = element.text_by_key_with(set,textclass,textparameters, Some(delimiter.clone())) {
                                    //delimiter will be buffered and only printed upon next iteration
                                    text += &textpart;
-                                   if let Ok(s) = element.get_textdelimiter(retaintokenisation) {
+                                   if let Ok(s) = element.get_textdelimiter(textparameters.retaintokenisation) {
                                        delimiter = s.to_string();
                                    }
                                }
                             }
-                        } else if element.elementtype() == ElementType::TextContent {
-                            textcontent_element = Some(element);
                         }
                     }
                 }
             }
-            if text.is_empty() && textcontent_element.is_some() {
-                if let Ok(parttext) = textcontent_element.unwrap().text_by_key(set,textclass,false,retaintokenisation, None) {
-                    text = parttext
+            if text.is_empty() {
+                //only the text content of the requested set and class qualifies
+                if let Some(textcontent_element) = self.get_textcontent(set, textclass) {
+                    if let Ok(parttext) = textcontent_element.text_by_key_with(set,textclass,textparameters, None) {
+                        text = parttext
+                    }
                 }
             }
 
-            if !text.is_empty() && previousdelimiter.is_some() {
-                text = previousdelimiter.unwrap() + text.as_str();
+            if !text.is_empty() {
+                if let Some(previousdelimiter) = previousdelimiter {
+                    text = previousdelimiter + text.as_str();
+                }
             }
 
             if !text.is_empty() {
@@ -146,6 +204,29 @@ impl<'a> Element<'a> {
         }
     }
 
+    ///Returns the branch of a correction element (``<new>``, ``<original>`` or ``<current>``) that
+    ///is to be followed given the correction handling.
+    pub fn get_correction_branch(&self, correctionhandling: CorrectionHandling) -> Option<Element<'a>> {
+        let doc = self.document?;
+        let preferred = match correctionhandling {
+            CorrectionHandling::Current => ElementType::New,
+            CorrectionHandling::Original => ElementType::Original,
+        };
+        let mut current: Option<Element<'a>> = None;
+        for item in self.elementdata().data.iter() {
+            if let DataType::Element(element_key) = item {
+                if let Some(element) = doc.get_element(*element_key) {
+                    if element.elementtype() == preferred {
+                        return Some(element);
+                    } else if element.elementtype() == ElementType::Current {
+                        current = Some(element);
+                    }
+                }
+            }
+        }
+        current
+    }
+
     ///Returns the text delimiter for this element
     pub fn get_textdelimiter(&self, retaintokenisation: bool) -> Result<Cow<str>,FoliaError> {
         let doc = self.document().ok_or(FoliaError::KeyError("Element has no associated document".to_string()))?;
@@ -195,11 +276,86 @@ impl<'a> Element<'a> {
     ///fallback to the default text set and "current class".
     pub fn text(&self, textparameters: &TextParameters) -> Result<String,FoliaError> {
         let doc = self.document().ok_or(FoliaError::KeyError("Element has no associated document".to_string()))?;
-        if let Some(dec_key) = doc.get_declaration_key_by_id(Declaration::index_id(AnnotationType::TEXT, &textparameters.set.as_ref().map(|s| s.as_str())).as_str()) {
-            let class_key = doc.class_key(dec_key, &textparameters.textclass.as_ref().map(|s| s.as_str()).expect("unwrapping textclass")  )?;
-            self.text_by_key(dec_key, class_key,textparameters.strict,textparameters.retaintokenisation, None)
-        } else {
-            Err(FoliaError::EncodeError(format!("No declaration for the specified text set ({})", textparameters.set.as_ref().map(|s| s.as_str()).expect("unwrapping set"))))
+        let (dec_key, class_key) = doc.text_keys(textparameters)?;
+        self.text_by_key_with(dec_key, class_key, textparameters, None)
+    }
+
+    ///Returns the text content element (``<t>``) directly under this element that matches the
+    ///given set and textclass. A text content element without an explicit set or class is
+    ///considered to be in the default text set and of class "current".
+    pub fn get_textcontent(&self, set: DecKey, textclass: ClassKey) -> Option<Element<'a>> {
+        let doc = self.document?;
+        let declaration = doc.get_declaration(set)?;
+        let set = declaration.set.as_deref().unwrap_or(DEFAULT_TEXT_SET);
+        let textclass = declaration.get_class(textclass)?;
+        for item in self.elementdata().data.iter() {
+            if let DataType::Element(element_key) = item {
+                if let Some(element) = doc.get_element(*element_key) {
+                    if element.elementtype() == ElementType::TextContent &&
+                       element.set().unwrap_or(DEFAULT_TEXT_SET) == set &&
+                       element.class().unwrap_or("current") == textclass {
+                        return Some(element);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    ///Returns the character offsets of all words in the text of this element (as returned by ``text()``).
+    ///Each item is a tuple of the word's key, begin offset and end offset (non-inclusive). Offsets are expressed in unicode characters.
+    pub fn get_word_offsets(&self, textparameters: &TextParameters) -> Result<Vec<(ElementKey,usize,usize)>,FoliaError> {
+        let text = self.text(textparameters)?;
+        let mut words: Vec<Element> = Vec::new();
+        self.collect_words(textparameters.correctionhandling, &mut words);
+        let mut offsets: Vec<(ElementKey,usize,usize)> = Vec::with_capacity(words.len());
+        let mut cursor: usize = 0; //byte offset
+        let mut charcursor: usize = 0; //character offset
+        for word in words {
+            let wordtext = word.text(textparameters)?;
+            if let Some(position) = text[cursor..].find(wordtext.as_str()) {
+                let begin = charcursor + text[cursor..cursor+position].chars().count();
+                let end = begin + wordtext.chars().count();
+                offsets.push((word.key().expect("unwrapping key"), begin, end));
+                cursor += position + wordtext.len();
+                charcursor = end;
+            } else {
+                return Err(FoliaError::ValidationError(format!("Text of word {} ({}) can not be found in the text of its ancestor", word.id().unwrap_or("(no id)"), wordtext)));
+            }
+        }
+        Ok(offsets)
+    }
+
+    ///Returns the keys of all words that (partially) cover the specified character range in the text of this element (as returned by ``text()``).
+    ///The range is expressed in unicode characters, the end offset is non-inclusive.
+    pub fn get_words_in_range(&self, begin: usize, end: usize, textparameters: &TextParameters) -> Result<Vec<ElementKey>,FoliaError> {
+        Ok(self.get_word_offsets(textparameters)?.into_iter().filter_map(|(key, wordbegin, wordend)| {
+            if wordbegin < end && wordend > begin {
+                Some(key)
+            } else {
+                None
+            }
+        }).collect())
+    }
+
+    ///Collects all words under this element, in document order, following the same elements as text composition does
+    fn collect_words(&self, correctionhandling: CorrectionHandling, words: &mut Vec<Element<'a>>) {
+        if let Some(doc) = self.document {
+            for item in self.elementdata().data.iter() {
+                if let DataType::Element(element_key) = item {
+                    if let Some(element) = doc.get_element(*element_key) {
+                        if element.elementtype() == ElementType::Word {
+                            words.push(element);
+                        } else if ElementGroup::Structure.contains(element.elementtype()) {
+                            element.collect_words(correctionhandling, words);
+                        } else if element.elementtype() == ElementType::Correction {
+                            if let Some(branch) = element.get_correction_branch(correctionhandling) {
+                                branch.collect_words(correctionhandling, words);
+                            }
+                        }
+                    }
+                }
+            }
         }
     }
 }
@@ -208,6 +364,74 @@ impl<'a> Element<'a> {
 
 
 impl Document {
+    ///Resolves the text set and textclass in the text parameters to their encoded keys
+    pub(crate) fn text_keys(&self, textparameters: &TextParameters) -> Result<(DecKey,ClassKey),FoliaError> {
+        if let Some(dec_key) = self.get_declaration_key_by_id(Declaration::index_id(AnnotationType::TEXT, &textparameters.set.as_deref()).as_str()) {
+            let class_key = self.class_key(dec_key, textparameters.textclass.as_deref().expect("unwrapping textclass"))?;
+            Ok((dec_key, class_key))
+        } else {
+            Err(FoliaError::EncodeError(format!("No declaration for the specified text set ({})", textparameters.set.as_deref().expect("unwrapping set"))))
+        }
+    }
+
+    ///Computes and sets the ``offset`` attribute on all text content elements (``<t>``) under the
+    ///specified element. Offsets are relative to the text content of the nearest ancestor that
+    ///carries text of the same set and class, and are expressed in unicode characters.
+    pub fn compute_offsets(&mut self, element_key: ElementKey, textparameters: &TextParameters) -> Result<(),FoliaError> {
+        let (set, textclass) = self.text_keys(textparameters)?;
+        let mut offsets: Vec<(ElementKey,usize)> = Vec::new();
+        let mut stack: Vec<(String,usize)> = Vec::new();
+        self.collect_offsets(element_key, set, textclass, textparameters, &mut stack, &mut offsets)?;
+        for (textcontent_key, offset) in offsets {
+            if offset > u16::MAX as usize {
+                return Err(FoliaError::ValidationError(format!("Offset {} is too large to be encoded", offset)));
+            }
+            if let Some(textcontent) = self.get_mut_elementdata(textcontent_key) {
+                textcontent.set_attrib(Attribute::Offset(offset as u16));
+            }
+        }
+        Ok(())
+    }
+
+    ///Recursion step for ``compute_offsets()``, the stack holds the text of all text-bearing ancestors along with a cursor (byte offset)
+    fn collect_offsets(&self, element_key: ElementKey, set: DecKey, textclass: ClassKey, textparameters: &TextParameters, stack: &mut Vec<(String,usize)>, offsets: &mut Vec<(ElementKey,usize)>) -> Result<(),FoliaError> {
+        let element = self.get_element(element_key).ok_or_else(|| FoliaError::KeyError(format!("No such element key: {}", element_key)))?;
+        let mut pushed = false;
+        if let Some(textcontent) = element.get_textcontent(set, textclass) {
+            let text = textcontent.text_by_key_with(set, textclass, textparameters, None)?;
+            if let Some((parenttext, cursor)) = stack.last_mut() {
+                if let Some(position) = parenttext[*cursor..].find(text.as_str()) {
+                    let offset = parenttext[..*cursor+position].chars().count();
+                    offsets.push((textcontent.key().expect("unwrapping key"), offset));
+                    *cursor += position + text.len();
+                } else {
+                    return Err(FoliaError::ValidationError(format!("Text of element {} ({}) can not be found in the text of its ancestor", element.id().unwrap_or("(no id)"), text)));
+                }
+            }
+            stack.push((text, 0));
+            pushed = true;
+        }
+        for item in element.elementdata().data.iter() {
+            if let DataType::Element(child_key) = item {
+                if let Some(child) = self.get_element(*child_key) {
+                    if ElementGroup::Structure.contains(child.elementtype()) {
+                        self.collect_offsets(*child_key, set, textclass, textparameters, stack, offsets)?;
+                    } else if child.elementtype() == ElementType::Correction {
+                        if let Some(branch) = child.get_correction_branch(textparameters.correctionhandling) {
+                            if let Some(branch_key) = branch.key() {
+                                self.collect_offsets(branch_key, set, textclass, textparameters, stack, offsets)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if pushed {
+            stack.pop();
+        }
+        Ok(())
+    }
+
     ///Returns the text of the given element
     pub fn text_by_key(&self, element_key: ElementKey, set: DecKey, textclass: ClassKey, strict: bool, retaintokenisation: bool) -> Result<String,FoliaError> {
         if let Some(element) = self.get_element(element_key) {
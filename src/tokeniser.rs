@@ -0,0 +1,198 @@
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::document::*;
+
+///Abbreviations that are recognised by default, the period is part of these tokens
+pub const DEFAULT_ABBREVIATIONS: &[&str] = &["mr.", "mrs.", "ms.", "dr.", "prof.", "st.", "vs.", "etc.", "e.g.", "i.e.", "cf.", "no.", "fig.", "jr.", "sr.", "inc.", "ltd.", "co."];
+
+///Characters that are considered punctuation in addition to ASCII punctuation
+const EXTRA_PUNCTUATION: &str = "«»“”‘’„‚…–—¡¿";
+
+///Punctuation that may follow the end of a sentence and still belongs to it
+const CLOSING_PUNCTUATION: &str = ")]}\"'»”’";
+
+#[derive(Clone)]
+///Options for the rule-based tokeniser used by ``Document::from_text()``
+pub struct TokeniserOptions {
+    ///Abbreviations (lowercase, including the final period) of which the period is not split off
+    pub abbreviations: Vec<String>,
+    ///Tokens consisting solely of these characters end a sentence
+    pub sentence_end: Vec<char>,
+    ///Treat every line as a sentence instead of detecting sentence boundaries
+    pub sentenceperline: bool,
+}
+
+impl Default for TokeniserOptions {
+    fn default() -> Self {
+        Self {
+            abbreviations: DEFAULT_ABBREVIATIONS.iter().map(|s| s.to_string()).collect(),
+            sentence_end: vec!['.', '!', '?', '…'],
+            sentenceperline: false,
+        }
+    }
+}
+
+impl TokeniserOptions {
+    pub fn with_abbreviation(mut self, abbreviation: &str) -> Self {
+        self.abbreviations.push(abbreviation.to_lowercase());
+        self
+    }
+
+    pub fn with_sentence_end(mut self, sentence_end: Vec<char>) -> Self {
+        self.sentence_end = sentence_end;
+        self
+    }
+
+    pub fn with_sentenceperline(mut self, sentenceperline: bool) -> Self {
+        self.sentenceperline = sentenceperline;
+        self
+    }
+
+    fn is_abbreviation(&self, token: &str) -> bool {
+        let lowercase = token.to_lowercase();
+        if self.abbreviations.contains(&lowercase) {
+            return true;
+        }
+        //initials
+        let mut chars = token.chars();
+        matches!((chars.next(), chars.next(), chars.next()), (Some(c), Some('.'), None) if c.is_uppercase())
+    }
+
+    fn is_sentence_end(&self, token: &str) -> bool {
+        token.chars().all(|c| self.sentence_end.contains(&c))
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || EXTRA_PUNCTUATION.contains(c)
+}
+
+///A token along with whether it is followed by whitespace
+#[derive(Debug,PartialEq)]
+struct Token<'t> {
+    text: &'t str,
+    space: bool,
+}
+
+///Splits a chunk of text without whitespace into tokens, leading and trailing punctuation is split
+///off (consecutive identical punctuation characters form a single token), punctuation inside a
+///word is retained
+fn tokenise_chunk<'t>(chunk: &'t str, options: &TokeniserOptions) -> Vec<&'t str> {
+    let mut leading: Vec<&'t str> = Vec::new();
+    let mut trailing: Vec<&'t str> = Vec::new();
+    let mut remainder = chunk;
+    while let Some(c) = remainder.chars().next().filter(|c| is_punctuation(*c)) {
+        let end = remainder.find(|x| x != c).unwrap_or(remainder.len());
+        leading.push(&remainder[..end]);
+        remainder = &remainder[end..];
+    }
+    while let Some(c) = remainder.chars().last().filter(|c| is_punctuation(*c)) {
+        if c == '.' && options.is_abbreviation(remainder) {
+            break;
+        }
+        let begin = remainder.rfind(|x| x != c).map(|i| i + remainder[i..].chars().next().expect("char").len_utf8()).unwrap_or(0);
+        trailing.push(&remainder[begin..]);
+        remainder = &remainder[..begin];
+    }
+    if !remainder.is_empty() {
+        leading.push(remainder);
+    }
+    leading.extend(trailing.into_iter().rev());
+    leading
+}
+
+///Tokenises text, returning tokens and whether they are followed by whitespace
+fn tokenise<'t>(text: &'t str, options: &TokeniserOptions) -> Vec<Token<'t>> {
+    let mut tokens: Vec<Token<'t>> = Vec::new();
+    for chunk in text.split_whitespace() {
+        let chunktokens = tokenise_chunk(chunk, options);
+        let last = chunktokens.len() - 1;
+        for (i, token) in chunktokens.into_iter().enumerate() {
+            tokens.push(Token { text: token, space: i == last });
+        }
+    }
+    tokens
+}
+
+///Groups tokens into sentences
+fn split_sentences<'t>(tokens: Vec<Token<'t>>, options: &TokeniserOptions) -> Vec<Vec<Token<'t>>> {
+    let mut sentences: Vec<Vec<Token<'t>>> = Vec::new();
+    let mut sentence: Vec<Token<'t>> = Vec::new();
+    let mut ending = false;
+    for token in tokens {
+        //closing punctuation directly attached to the end of the sentence still belongs to it
+        let attached = !sentence.last().map(|t: &Token| t.space).unwrap_or(true);
+        let closing = token.text.chars().all(|c| CLOSING_PUNCTUATION.contains(c));
+        if ending && !(attached && closing) {
+            sentences.push(std::mem::take(&mut sentence));
+            ending = false;
+        }
+        if options.is_sentence_end(token.text) {
+            ending = true;
+        }
+        sentence.push(token);
+    }
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+    sentences
+}
+
+impl Document {
+    ///Builds a new document from plain text with a simple rule-based tokeniser. Paragraphs are
+    ///separated by blank lines, sentences end with end punctuation (or at the end of a line if
+    ///``sentenceperline`` is set) and tokens are split on whitespace and punctuation. The result
+    ///consists of paragraphs, sentences and words with generated IDs and the corresponding
+    ///declarations.
+    pub fn from_text(id: &str, text: &str, options: &TokeniserOptions, properties: DocumentProperties) -> Result<Self,FoliaError> {
+        let mut doc = Document::new(id, properties)?;
+        doc.declare(AnnotationType::PARAGRAPH, &None, &None, &None)?;
+        doc.declare(AnnotationType::SENTENCE, &None, &None, &None)?;
+        doc.declare(AnnotationType::TOKEN, &None, &None, &None)?;
+        let body_key: ElementKey = 0;
+
+        //collect paragraphs, separated by blank lines
+        let mut paragraphs: Vec<Vec<&str>> = Vec::new();
+        let mut paragraph: Vec<&str> = Vec::new();
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                if !paragraph.is_empty() {
+                    paragraphs.push(std::mem::take(&mut paragraph));
+                }
+            } else {
+                paragraph.push(line);
+            }
+        }
+        if !paragraph.is_empty() {
+            paragraphs.push(paragraph);
+        }
+
+        for (p, lines) in paragraphs.into_iter().enumerate() {
+            let paragraph_id = format!("{}.p.{}", id, p + 1);
+            let paragraph_key = doc.add_element_to(body_key, ElementData::new(ElementType::Paragraph).with_attrib(Attribute::Id(paragraph_id.clone())))?;
+            let paragraphtext = lines.join("\n");
+            let sentences: Vec<Vec<Token>> = if options.sentenceperline {
+                lines.iter().map(|line| tokenise(line, options)).filter(|tokens| !tokens.is_empty()).collect()
+            } else {
+                split_sentences(tokenise(&paragraphtext, options), options)
+            };
+            for (s, sentence) in sentences.into_iter().enumerate() {
+                let sentence_id = format!("{}.s.{}", paragraph_id, s + 1);
+                let sentence_key = doc.add_element_to(paragraph_key, ElementData::new(ElementType::Sentence).with_attrib(Attribute::Id(sentence_id.clone())))?;
+                for (w, token) in sentence.into_iter().enumerate() {
+                    let mut word = ElementData::new(ElementType::Word)
+                                        .with_attrib(Attribute::Id(format!("{}.w.{}", sentence_id, w + 1)))
+                                        .with_text(token.text.to_string());
+                    if !token.space {
+                        word.set_attrib(Attribute::Space(false));
+                    }
+                    doc.add_element_to(sentence_key, word)?;
+                }
+            }
+        }
+        Ok(doc)
+    }
+}
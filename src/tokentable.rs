@@ -0,0 +1,151 @@
+use std::io::Write;
+
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::query::*;
+use crate::select::*;
+use crate::text::*;
+use crate::document::*;
+
+#[derive(Debug,Clone,PartialEq)]
+///A column in a token table: an annotation type and optionally a set (any set if omitted).
+///Columns of span annotation types are rendered in BIO form.
+pub struct TokenTableColumn {
+    pub annotationtype: AnnotationType,
+    pub set: Option<String>,
+}
+
+impl TokenTableColumn {
+    pub fn new(annotationtype: AnnotationType, set: Option<&str>) -> Self {
+        Self {
+            annotationtype,
+            set: set.map(|s| s.to_string()),
+        }
+    }
+
+    ///Returns true if the column is rendered in BIO form
+    pub fn is_span(&self) -> bool {
+        ElementGroup::Span.contains(self.annotationtype.elementtype())
+    }
+
+    ///Returns the name of the column, as used in the header of the TSV output
+    pub fn name(&self) -> String {
+        match &self.set {
+            Some(set) => format!("{}:{}", self.annotationtype, set),
+            None => self.annotationtype.to_string(),
+        }
+    }
+
+    fn matches_set(&self, element: &Element) -> bool {
+        match &self.set {
+            Some(set) => element.set() == Some(set.as_str()),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+///A row in a token table, corresponding to a single word
+pub struct TokenTableRow {
+    pub key: ElementKey,
+    pub id: Option<String>,
+    pub text: String,
+    ///One value per column, ``None`` if the word has no such annotation (BIO columns always
+    ///have a value)
+    pub values: Vec<Option<String>>,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+///A table with a row per word and a column per annotation, obtained through ``Document::token_table()``
+pub struct TokenTable {
+    pub columns: Vec<TokenTableColumn>,
+    pub rows: Vec<TokenTableRow>,
+}
+
+///Makes a value suitable for a TSV cell
+fn tsv_value(value: Option<&str>) -> String {
+    match value {
+        Some(value) if !value.is_empty() => value.replace(['\t', '\n'], " "),
+        _ => "_".to_string(),
+    }
+}
+
+impl TokenTable {
+    ///Writes the table as tab separated values, with a header line. Missing values are written as ``_``.
+    pub fn write_tsv<W: Write>(&self, writer: &mut W) -> Result<(),FoliaError> {
+        let mut header: Vec<String> = vec!("id".to_string(), "text".to_string());
+        header.extend(self.columns.iter().map(|column| column.name()));
+        writeln!(writer, "{}", header.join("\t"))?;
+        for row in self.rows.iter() {
+            let mut cells: Vec<String> = vec!(tsv_value(row.id.as_deref()), tsv_value(Some(row.text.as_str())));
+            cells.extend(row.values.iter().map(|value| tsv_value(value.as_deref())));
+            writeln!(writer, "{}", cells.join("\t"))?;
+        }
+        Ok(())
+    }
+
+    ///Returns the table as tab separated values, see ``write_tsv()``
+    pub fn to_tsv(&self) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        self.write_tsv(&mut out).expect("writing to memory");
+        String::from_utf8(out).expect("valid utf-8")
+    }
+}
+
+impl<'a> Element<'a> {
+    ///Returns the value of a token table column for this word
+    fn token_table_value(&self, column: &TokenTableColumn) -> Option<String> {
+        if column.is_span() {
+            //follow the back-references to the spans this word is a part of
+            let document = self.document?;
+            for item in self.data.data.iter() {
+                if let DataType::SpanReference(span_key) = item {
+                    if let Some(span) = document.get_element(*span_key) {
+                        if span.elementtype() == column.annotationtype.elementtype() && column.matches_set(&span) {
+                            let prefix = if span.span_words().first() == Some(self) { "B" } else { "I" };
+                            return Some(match span.class() {
+                                Some(class) => format!("{}-{}", prefix, class),
+                                None => prefix.to_string(),
+                            });
+                        }
+                    }
+                }
+            }
+            Some("O".to_string())
+        } else {
+            let set = match &column.set {
+                Some(set) => Cmp::Is(set.clone()),
+                None => Cmp::Any,
+            };
+            self.get_annotation(column.annotationtype, set, Recursion::No)
+                .and_then(|annotation| annotation.class().map(|class| class.to_string()))
+        }
+    }
+}
+
+impl Document {
+    ///Builds a table with a row for every word in the document, holding its ID, its text and the
+    ///class of the annotation for each of the specified columns
+    pub fn token_table(&self, columns: &[TokenTableColumn]) -> Result<TokenTable,FoliaError> {
+        let mut rows: Vec<TokenTableRow> = Vec::new();
+        for word in self.select(Selector::elements().element(Cmp::Is(ElementType::Word)), Recursion::Always) {
+            let text = match word.text(&TextParameters::default()) {
+                Ok(text) => text,
+                Err(FoliaError::NoTextError(_)) => String::new(),
+                Err(err) => return Err(err),
+            };
+            rows.push(TokenTableRow {
+                key: word.key().ok_or(FoliaError::InternalError("Word has no key".to_string()))?,
+                id: word.id().map(|id| id.to_string()),
+                text,
+                values: columns.iter().map(|column| word.token_table_value(column)).collect(),
+            });
+        }
+        Ok(TokenTable {
+            columns: columns.to_vec(),
+            rows,
+        })
+    }
+}
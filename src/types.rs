@@ -754,7 +754,7 @@ impl std::str::FromStr for ElementType {
 }
 
 
-#[derive(Debug,PartialEq,Clone)]
+#[derive(Debug,PartialEq,Clone,Serialize,Deserialize)]
 ///This data structure represents a data item in the document tree.
 pub enum DataType {
     ///Text item
@@ -768,6 +768,18 @@ pub enum DataType {
     ///A to-be-constructed element, this is a only a temporary value that allows nested
     ///construction, it will not actually appear in the document's data model
     AddElement(ElementData),
+    ///Raw XML in a foreign (non-FoLiA) namespace, as held by ``<foreign-data>``
+    ForeignXml(ForeignXml),
+}
+
+#[derive(Debug,PartialEq,Clone,Default,Serialize,Deserialize)]
+///Raw XML in a foreign (non-FoLiA) namespace, kept verbatim
+pub struct ForeignXml {
+    pub xml: String,
+    ///The namespace bindings (prefix and namespace, the prefix is empty for the default namespace)
+    ///that the XML relies on but that were made outside of it, in the order they were encountered.
+    ///These are declared on the wrapping ``<foreign-data>`` element on serialisation.
+    pub namespaces: Vec<(String,String)>,
 }
 
 impl DataType {
@@ -780,7 +792,7 @@ impl DataType {
 }
 
 
-#[derive(Clone,Copy)]
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub enum BodyType {
     Text,
     Speech
@@ -0,0 +1,113 @@
+use crate::common::*;
+use crate::types::*;
+use crate::element::*;
+use crate::error::*;
+use crate::attrib::*;
+use crate::store::*;
+use crate::metadata::*;
+use crate::document::*;
+
+impl ElementType {
+    ///Returns the FoLiA version in which this element type was introduced, element types that
+    ///have been around since FoLiA v1.0 (or before) return ``1.0.0``
+    pub fn since_version(&self) -> &'static str {
+        match self {
+            ElementType::Observation | ElementType::ObservationLayer |
+            ElementType::Sentiment | ElementType::SentimentLayer |
+            ElementType::Statement | ElementType::StatementLayer | ElementType::StatementRelation |
+            ElementType::Predicate => "1.3.0",
+            ElementType::Hyphbreak => "1.5.0",
+            ElementType::Hiddenword |
+            ElementType::Modality | ElementType::ModalitiesLayer | ElementType::ModalityFeature |
+            ElementType::Cue | ElementType::Scope |
+            ElementType::Relation | ElementType::SpanRelation | ElementType::SpanRelationLayer |
+            ElementType::LinkReference => "2.0.0",
+            _ => "1.0.0",
+        }
+    }
+}
+
+impl Document {
+    ///Returns true if the document is in FoLiA v1 (or older), according to its version
+    pub fn is_legacy(&self) -> bool {
+        parse_version(&self.version).0 < 2
+    }
+
+    ///Returns the minimum FoLiA version needed to represent this document, computed from the
+    ///element types that are actually in use (and the use of provenance, which requires FoLiA v2)
+    pub fn required_version(&self) -> String {
+        let mut required: &str = if self.provenancestore.chain.is_empty() { "1.0.0" } else { "2.0.0" };
+        for element in <Self as Store<ElementData,ElementKey>>::iter(self).flatten() {
+            let version = element.elementtype.since_version();
+            if parse_version(version) > parse_version(required) {
+                required = version;
+            }
+        }
+        required.to_string()
+    }
+
+    ///Upgrades a FoLiA v1 document to FoLiA v2: every distinct pair of old-style ``annotator`` and
+    ///``annotatortype`` attributes becomes a processor in the provenance chain, and the elements
+    ///refer to these processors instead. The document version is bumped to the current version.
+    ///This is invoked automatically when loading FoLiA v1 documents.
+    pub fn upgrade(&mut self) -> Result<(),FoliaError> {
+        let mut upgrades: Vec<(ElementKey, Option<String>, Option<ProcessorType>)> = Vec::new();
+        for (key, element) in <Self as Store<ElementData,ElementKey>>::iter(self).enumerate() {
+            if let Some(element) = element {
+                let mut annotator: Option<String> = None;
+                let mut annotatortype: Option<ProcessorType> = None;
+                for attrib in element.attribs.iter() {
+                    match attrib {
+                        Attribute::Annotator(name) => annotator = Some(name.clone()),
+                        Attribute::AnnotatorType(processortype) => annotatortype = Some(*processortype),
+                        _ => {}
+                    }
+                }
+                if annotator.is_some() || annotatortype.is_some() {
+                    upgrades.push((key as ElementKey, annotator, annotatortype));
+                }
+            }
+        }
+        for (key, annotator, annotatortype) in upgrades {
+            //anything not specified on the element itself is inherited from the default processor of the declaration
+            let declaration_key = self.get_elementdata(key).and_then(|element| element.declaration_key().ok().flatten());
+            let default = declaration_key.and_then(|declaration_key| self.get_declaration(declaration_key))
+                                         .and_then(|declaration| declaration.default_processor())
+                                         .and_then(|processor_key| self.get_processor(processor_key));
+            let name = annotator.or_else(|| default.map(|processor| processor.name.clone())).unwrap_or_else(|| "unknown".to_string());
+            let processortype = annotatortype.or_else(|| default.map(|processor| processor.processortype)).unwrap_or_default();
+            let processor_key = self.legacy_processor(&name, processortype)?;
+            if let Some(declaration) = declaration_key.and_then(|declaration_key| self.get_mut_declaration(declaration_key)) {
+                if !declaration.processors.contains(&processor_key) {
+                    declaration.processors.push(processor_key);
+                }
+            }
+            if let Some(element) = self.get_mut_elementdata(key) {
+                element.attribs.retain(|attrib| !matches!(attrib, Attribute::Annotator(_) | Attribute::AnnotatorType(_) | Attribute::ProcessorRef(_)));
+                element.attribs.push(Attribute::ProcessorRef(processor_key));
+            }
+        }
+        self.version = FOLIAVERSION.to_string();
+        Ok(())
+    }
+
+    ///Returns the processor for an old-style annotator, it is added to the provenance chain if it
+    ///does not exist yet
+    pub(crate) fn legacy_processor(&mut self, name: &str, processortype: ProcessorType) -> Result<ProcKey,FoliaError> {
+        for processor_key in self.provenancestore.chain.iter() {
+            if let Some(processor) = self.get_processor(*processor_key) {
+                if processor.name == name && processor.processortype == processortype {
+                    return Ok(*processor_key);
+                }
+            }
+        }
+        let base_id: String = format!("proc.{}.{}", name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect::<String>(), processortype);
+        let mut id = base_id.clone();
+        let mut n = 1;
+        while self.get_processor_by_id(&id).is_some() {
+            n += 1;
+            id = format!("{}.{}", base_id, n);
+        }
+        self.add_processor(Processor::new(name.to_string()).with_id(id).with_type(processortype))
+    }
+}
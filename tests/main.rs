@@ -207,6 +207,100 @@ const EXAMPLE_MARKUP: &[u8] = br#"<?xml version="1.0" encoding="utf-8"?>
   </text>
 </FoLiA>"#;
 
+const EXAMPLE_CORRECTION: &[u8] = br#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation>
+             <annotator processor="p1" />
+          </text-annotation>
+          <sentence-annotation>
+             <annotator processor="p1" />
+          </sentence-annotation>
+          <token-annotation>
+             <annotator processor="p1" />
+          </token-annotation>
+          <correction-annotation set="ocr-corrections">
+             <annotator processor="p1" />
+          </correction-annotation>
+          <hyphenation-annotation>
+             <annotator processor="p1" />
+          </hyphenation-annotation>
+      </annotations>
+      <provenance>
+         <processor xml:id="p1" name="proycon" type="manual" />
+      </provenance>
+  </metadata>
+  <text xml:id="example.text">
+      <s xml:id="example.s.1">
+         <w xml:id="example.s.1.w.1"><t>The</t></w>
+         <w xml:id="example.s.1.w.2">
+            <correction xml:id="example.s.1.w.2.correction.1" class="ocr">
+                <new>
+                    <t>quick</t>
+                </new>
+                <original>
+                    <t>qnick</t>
+                </original>
+            </correction>
+         </w>
+         <w xml:id="example.s.1.w.3"><t>fox</t></w>
+      </s>
+      <s xml:id="example.s.2">
+        <t>A <t-correction class="ocr" original="hnge">huge</t-correction> ele<t-hbr/>phant.</t>
+      </s>
+  </text>
+</FoLiA>"#;
+
+const EXAMPLE_SPEECH: &[u8] = br#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata>
+      <annotations>
+          <text-annotation/>
+          <utterance-annotation/>
+          <token-annotation/>
+          <timesegment-annotation set="cgn"/>
+      </annotations>
+  </metadata>
+  <speech xml:id="example.speech" src="example.wav">
+      <utt xml:id="example.utt.1" begintime="00:00:01.000" endtime="00:00:02.500" speaker="A">
+          <t>hello world</t>
+          <w xml:id="example.utt.1.w.1" begintime="00:00:01.000" endtime="00:00:01.700"><t>hello</t></w>
+          <w xml:id="example.utt.1.w.2" begintime="00:00:01.800" endtime="00:00:02.500"><t>world</t></w>
+          <timing>
+             <timesegment xml:id="example.utt.1.ts.1" set="cgn" class="speech" begintime="00:00:01.000" endtime="00:00:02.500">
+                <wref id="example.utt.1.w.1"/>
+                <wref id="example.utt.1.w.2"/>
+             </timesegment>
+          </timing>
+      </utt>
+      <utt xml:id="example.utt.2" begintime="00:00:03.000" endtime="00:00:04.250" speaker="B">
+          <t>bye</t>
+      </utt>
+  </speech>
+</FoLiA>"#;
+
+const EXAMPLE_FOREIGN: &[u8] = br#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" xmlns:ex="http://example.org/ex" version="2.0" xml:id="example">
+  <metadata type="cmdi">
+      <annotations>
+          <text-annotation/>
+          <token-annotation/>
+      </annotations>
+      <foreign-data><cmd:CMD xmlns:cmd="http://www.clarin.eu/cmd/" CMDVersion="1.1"><cmd:Header><cmd:MdCreator>proycon</cmd:MdCreator></cmd:Header><cmd:Resources/></cmd:CMD></foreign-data>
+  </metadata>
+  <text xml:id="example.text">
+      <w xml:id="example.w.1">
+          <t>hello</t>
+          <foreign-data><x:stuff xmlns:x="http://x.org/" x:id="1"><x:a attr="1">text &amp; more</x:a><!-- note --><x:b/></x:stuff></foreign-data>
+      </w>
+      <w xml:id="example.w.2">
+          <t>world</t>
+          <foreign-data><ex:thing n="2"><ex:nested>in default</ex:nested></ex:thing></foreign-data>
+      </w>
+  </text>
+</FoLiA>"#;
+
 #[test]
 fn test001_instantiate() {
     match Document::new("example", DocumentProperties::default()) {
@@ -941,3 +1035,1732 @@ fn test016_add_provenance() {
         }
     }
 }
+
+#[test]
+fn test017a_compute_offsets() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            doc.compute_offsets(0, &TextParameters::default()).expect("computing offsets");
+            for (id, expected_offset) in &[("example.p.1.s.1.w.1",0),("example.p.1.s.1.w.3",7),("example.p.1.s.1.w.4",17),("example.p.1.s.1.w.5",20)] {
+                let word = doc.get_element_by_id(id).expect("word");
+                let textcontent = word.get_element(ElementType::TextContent, Cmp::Any, Recursion::No).expect("textcontent");
+                assert_matches!(textcontent.attrib(AttribType::OFFSET), Some(Attribute::Offset(offset)) if *offset == *expected_offset);
+            }
+            //the sentence text has no text-bearing ancestor so it gets no offset
+            let sentence = doc.get_element_by_id("example.p.1.s.1").expect("sentence");
+            let textcontent = sentence.get_element(ElementType::TextContent, Cmp::Any, Recursion::No).expect("textcontent");
+            assert!(textcontent.attrib(AttribType::OFFSET).is_none());
+            //offsets are serialised
+            let xml = doc.get_element_by_id("example.p.1.s.1.w.3").expect("word").xml(0).expect("serialising word");
+            assert!(xml.contains("offset=\"7\""), "{}", xml);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test017b_words_in_range() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let sentence = doc.get_element_by_id("example.p.1.s.1").expect("sentence");
+            let offsets = sentence.get_word_offsets(&TextParameters::default()).expect("word offsets");
+            assert_eq!(offsets.len(), 5);
+            assert_eq!(offsets[2], (doc.get_element_key_by_id("example.p.1.s.1.w.3").unwrap(), 7, 16));
+            let keys = sentence.get_words_in_range(3, 16, &TextParameters::default()).expect("words in range");
+            assert_eq!(keys, vec![doc.get_element_key_by_id("example.p.1.s.1.w.2").unwrap(), doc.get_element_key_by_id("example.p.1.s.1.w.3").unwrap()]);
+            let keys = sentence.get_words_in_range(8, 9, &TextParameters::default()).expect("words in range");
+            assert_eq!(keys, vec![doc.get_element_key_by_id("example.p.1.s.1.w.3").unwrap()]);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test018a_text_correction() {
+    match Document::from_str(str::from_utf8(EXAMPLE_CORRECTION).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let sentence = doc.get_element_by_id("example.s.1").expect("sentence");
+            assert_eq!(sentence.text(&TextParameters::default()).expect("text"), "The quick fox");
+            assert_eq!(sentence.text(&TextParameters::default().correctionhandling(CorrectionHandling::Original)).expect("text"), "The qnick fox");
+            let word = doc.get_element_by_id("example.s.1.w.2").expect("word");
+            assert_eq!(word.text(&TextParameters::default()).expect("text"), "quick");
+            let offsets = sentence.get_words_in_range(4, 5, &TextParameters::default()).expect("words in range");
+            assert_eq!(offsets, vec![word.key().unwrap()]);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test018b_text_markup_correction_hyphbreak() {
+    match Document::from_str(str::from_utf8(EXAMPLE_CORRECTION).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let sentence = doc.get_element_by_id("example.s.2").expect("sentence");
+            assert_eq!(sentence.text(&TextParameters::default()).expect("text"), "A huge elephant.");
+            assert_eq!(sentence.text(&TextParameters::default().correctionhandling(CorrectionHandling::Original)).expect("text"), "A hnge elephant.");
+            assert_eq!(sentence.text(&TextParameters::default().hyphbreaks(true)).expect("text"), "A huge ele-phant.");
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test019a_speech_parse() {
+    match Document::from_str(str::from_utf8(EXAMPLE_SPEECH).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            assert_eq!(doc.bodytype(), BodyType::Speech);
+            let utterance = doc.get_element_by_id("example.utt.2").expect("utterance");
+            assert_eq!(utterance.begintime(), Some(std::time::Duration::from_secs(3)));
+            assert_eq!(utterance.endtime(), Some(std::time::Duration::from_millis(4250)));
+            assert_eq!(utterance.text(&TextParameters::default()).expect("text"), "bye");
+            let timesegment = doc.get_element_by_id("example.utt.1.ts.1").expect("timesegment");
+            assert_eq!(timesegment.class(), Some("speech"));
+            assert_eq!(timesegment.set(), Some("cgn"));
+            //round trip
+            let xml = doc.xml(0, 4).expect("serialisation");
+            let doc2 = Document::from_str(str::from_utf8(&xml).expect("utf-8"), DocumentProperties::default()).expect("reparsing");
+            assert_eq!(doc2.bodytype(), BodyType::Speech);
+            assert_eq!(doc2.get_element_by_id("example.utt.2").expect("utterance").endtime(), Some(std::time::Duration::from_millis(4250)));
+            assert!(str::from_utf8(&xml).expect("utf-8").contains("endtime=\"00:00:04.250\""));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test019b_speech_timeinterval() {
+    match Document::from_str(str::from_utf8(EXAMPLE_SPEECH).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let query = Query::select().element(Cmp::Is(ElementType::Word)).timeinterval(parse_time("00:00:01.750").unwrap(), parse_time("00:00:03.500").unwrap());
+            let selector = Selector::from_query(&doc, &query).expect("selector");
+            let ids: Vec<String> = doc.select(selector, Recursion::Always).map(|e| e.id().unwrap().to_string()).collect();
+            assert_eq!(ids, vec!["example.utt.1.w.2"]);
+            let query = Query::select().timeinterval(parse_time("00:00:02.000").unwrap(), parse_time("00:00:03.500").unwrap());
+            let selector = Selector::from_query(&doc, &query).expect("selector");
+            let ids: Vec<String> = doc.select(selector, Recursion::Always).map(|e| e.id().unwrap().to_string()).collect();
+            assert_eq!(ids, vec!["example.utt.1", "example.utt.1.ts.1", "example.utt.1.w.2", "example.utt.2"]);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test019c_speech_new() {
+    match Document::new("example", DocumentProperties::default().with_bodytype(BodyType::Speech)) {
+        Ok(mut doc) => {
+            let root: ElementKey = 0;
+            doc.annotate(root,
+                         ElementData::new(ElementType::Utterance)
+                         .with_attrib(Attribute::Id("utt.1".to_string()))
+                         .with_attrib(Attribute::BeginTime(parse_time("00:01:02.5").unwrap()))
+                         .with_attrib(Attribute::EndTime(parse_time("01:02:03").unwrap()))
+                         .with_text("hello".to_string())
+                        ).expect("Adding utterance");
+            let xml = doc.xml(0, 4).expect("serialisation");
+            let xml = str::from_utf8(&xml).expect("utf-8");
+            assert!(xml.contains("<speech>"), "{}", xml);
+            assert!(xml.contains("begintime=\"00:01:02.500\" endtime=\"01:02:03.000\""), "{}", xml);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test019d_add_span_with_nested_wrefs() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            let layer = doc.get_element_by_id("example.p.1.s.1").expect("sentence").get_element(ElementType::DependenciesLayer, Cmp::Any, Recursion::No).expect("layer").key().expect("key");
+            let dependency = ElementData::new(ElementType::Dependency).with_attrib(Attribute::Class("punct".to_string()))
+                .add_element(ElementData::new(ElementType::Headspan).add_element(ElementData::new(ElementType::WordReference).with_attrib(Attribute::Idref("example.p.1.s.1.w.3".to_string()))))
+                .add_element(ElementData::new(ElementType::DependencyDependent).add_element(ElementData::new(ElementType::WordReference).with_attrib(Attribute::Idref("example.p.1.s.1.w.5".to_string()))));
+            doc.add_element_to(layer, dependency).expect("adding dependency");
+            //the wrefs nested inside the span roles are resolved to the words
+            let word = doc.get_element_by_id("example.p.1.s.1.w.5").expect("word");
+            let annotation = word.get_annotation(AnnotationType::DEPENDENCY, Cmp::Any, Recursion::No).expect("dependency");
+            assert_eq!(annotation.class(), Some("punct"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test019e_annotate_span_with_spanroles() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            let dependency = ElementData::new(ElementType::Dependency).with_attrib(Attribute::Class("punct".to_string()))
+                .add_element(ElementData::new(ElementType::Headspan).with_span(&["example.p.1.s.1.w.3"]))
+                .add_element(ElementData::new(ElementType::DependencyDependent).with_span(&["example.p.1.s.1.w.5"]));
+            //the span is made up of the words in the span roles
+            let key = doc.annotate_span(dependency).expect("adding dependency");
+            let dependency = doc.get_element(key).expect("dependency");
+            assert_eq!(dependency.get_parent().expect("layer").elementtype(), ElementType::DependenciesLayer);
+            let sentence = doc.get_element_by_id("example.p.1.s.1").expect("sentence");
+            assert_eq!(sentence.get_elements(ElementType::DependenciesLayer, Cmp::Any, Recursion::No).count(), 1);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test019f_annotate_span_existing_layer() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            //annotating from a word reuses the layer in the sentence rather than creating a new one
+            let word_key = doc.get_element_key_by_id("example.p.1.s.2.w.1").expect("word");
+            doc.annotate(word_key, ElementData::new(ElementType::Chunk).with_attrib(Attribute::Class("np".to_string())).with_span(&["example.p.1.s.2.w.1", "example.p.1.s.2.w.2"])).expect("adding chunk");
+            let sentence = doc.get_element_by_id("example.p.1.s.2").expect("sentence");
+            let layers: Vec<_> = sentence.get_elements(ElementType::ChunkingLayer, Cmp::Any, Recursion::No).collect();
+            assert_eq!(layers.len(), 1);
+            assert_eq!(layers[0].get_elements(ElementType::Chunk, Cmp::Any, Recursion::No).count(), 2);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test020a_dependency_navigation() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let word = doc.get_element_by_id("example.p.1.s.1.w.2").expect("word");
+            assert_eq!(word.deprel(), Some("su"));
+            assert_eq!(word.dependency_head().expect("head").id(), Some("example.p.1.s.1.w.3"));
+            assert_eq!(word.dependency().expect("dependency").id(), Some("example.p.1.s.1.dependency.1"));
+            let dependents: Vec<String> = word.dependents().iter().map(|e| e.id().unwrap().to_string()).collect();
+            assert_eq!(dependents, vec!["example.p.1.s.1.w.1"]);
+            let head = doc.get_element_by_id("example.p.1.s.1.w.3").expect("word");
+            assert!(head.dependency_head().is_none());
+            assert!(head.deprel().is_none());
+            let dependents: Vec<String> = head.dependents().iter().map(|e| e.id().unwrap().to_string()).collect();
+            assert_eq!(dependents, vec!["example.p.1.s.1.w.2", "example.p.1.s.1.w.4"]);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test020b_dependency_tree() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let sentence = doc.get_element_by_id("example.p.1.s.1").expect("sentence");
+            let tree = sentence.dependency_tree().expect("dependency tree");
+            assert_eq!(tree.nodes().len(), 5);
+            let key = |id: &str| doc.get_element_key_by_id(id).expect("key");
+            assert_eq!(tree.root(), Some(key("example.p.1.s.1.w.3")));
+            assert_eq!(tree.roots(), vec![key("example.p.1.s.1.w.3"), key("example.p.1.s.1.w.5")]);
+            assert_eq!(tree.deprel(key("example.p.1.s.1.w.1")), Some("det"));
+            assert_eq!(tree.dependents(key("example.p.1.s.1.w.3")), vec![key("example.p.1.s.1.w.2"), key("example.p.1.s.1.w.4")]);
+            assert_eq!(tree.path_to_root(key("example.p.1.s.1.w.1")).expect("path"), vec![key("example.p.1.s.1.w.1"), key("example.p.1.s.1.w.2"), key("example.p.1.s.1.w.3")]);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test020c_annotate_dependency() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            let head_key = doc.get_element_key_by_id("example.p.1.s.1.w.3").expect("head");
+            let dependent_key = doc.get_element_key_by_id("example.p.1.s.1.w.5").expect("dependent");
+            let dependency_key = doc.annotate_dependency(head_key, dependent_key, "punct", Some("alpino-dependencies")).expect("adding dependency");
+            let dependency = doc.get_element(dependency_key).expect("dependency");
+            //added to the existing layer
+            let layer = dependency.get_parent().expect("layer");
+            assert_eq!(layer.elementtype(), ElementType::DependenciesLayer);
+            assert_eq!(layer.get_elements(ElementType::Dependency, Cmp::Any, Recursion::No).count(), 4);
+            let word = doc.get_element(dependent_key).expect("word");
+            assert_eq!(word.deprel(), Some("punct"));
+            assert_eq!(word.dependency_head().and_then(|e| e.key()), Some(head_key));
+            let head = doc.get_element(head_key).expect("word");
+            assert_eq!(head.dependents().len(), 3);
+            //round trip
+            let xml = doc.xml(0, 4).expect("serialisation");
+            let doc2 = Document::from_str(str::from_utf8(&xml).expect("utf-8"), DocumentProperties::default()).expect("reparsing");
+            let word = doc2.get_element_by_id("example.p.1.s.1.w.5").expect("word");
+            assert_eq!(word.deprel(), Some("punct"));
+            assert_eq!(word.dependency_head().expect("head").id(), Some("example.p.1.s.1.w.3"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test021a_syntax_tree() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let sentence = doc.get_element_by_id("example.p.1.s.1").expect("sentence");
+            let tree = sentence.syntax_tree(Cmp::Any).expect("syntax tree");
+            assert_eq!(tree.constituents.len(), 1);
+            let top = &tree.constituents[0];
+            assert_eq!(top.label.as_deref(), Some("top"));
+            assert_eq!(top.words().len(), 5);
+            let np = top.constituents().next().expect("smain").constituents().next().expect("np");
+            assert_eq!(np.label.as_deref(), Some("np"));
+            assert_eq!(np.words(), vec![doc.get_element_key_by_id("example.p.1.s.1.w.1").unwrap(), doc.get_element_key_by_id("example.p.1.s.1.w.2").unwrap()]);
+            assert_eq!(tree.to_bracketed(), "(top (smain (np (top De) (top man)) (verb begroette) (pron hem)) (punct .))");
+            assert!(sentence.syntax_tree(Cmp::Is("nonexistant".to_string())).is_err());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test021b_annotate_syntax() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            doc.declare(AnnotationType::SYNTAX, &Some("penn".to_string()), &None, &None).expect("declaring");
+            let sentence_key = doc.get_element_key_by_id("example.p.1.s.1").expect("sentence");
+            assert!(doc.annotate_syntax(sentence_key, "(S (NP (DT De) (NN vrouw)))", Some("penn")).is_err());
+            let keys = doc.annotate_syntax(sentence_key, "( (S (NP (DT De) (NN man)) (VP (VBD begroette) (PRP hem)) (. .)) )", Some("penn")).expect("adding syntax tree");
+            assert_eq!(keys.len(), 1);
+            let sentence = doc.get_element(sentence_key).expect("sentence");
+            let tree = sentence.syntax_tree(Cmp::Is("penn".to_string())).expect("syntax tree");
+            assert_eq!(tree.constituents[0].key, keys[0]);
+            assert_eq!(tree.to_bracketed(), "(S (NP (DT De) (NN man)) (VP (VBD begroette) (PRP hem)) (. .))");
+            //the original tree is untouched
+            assert_eq!(sentence.get_elements(ElementType::SyntaxLayer, Cmp::Any, Recursion::No).count(), 2);
+            //round trip
+            let xml = doc.xml(0, 4).expect("serialisation");
+            let doc2 = Document::from_str(str::from_utf8(&xml).expect("utf-8"), DocumentProperties::default()).expect("reparsing");
+            let sentence = doc2.get_element_by_id("example.p.1.s.1").expect("sentence");
+            assert_eq!(sentence.syntax_tree(Cmp::Is("penn".to_string())).expect("syntax tree").to_bracketed(), "(S (NP (DT De) (NN man)) (VP (VBD begroette) (PRP hem)) (. .))");
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test022_coreference() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            doc.declare(AnnotationType::COREFERENCE, &Some("coref".to_string()), &None, &None).expect("declaring");
+            assert!(doc.add_coreference_chain(&[&["example.p.1.s.1.w.1","example.p.1.s.1.w.2"], &["example.p.1.s.1.w.99"]], Some("coref")).is_err());
+            let chain_key = doc.add_coreference_chain(&[&["example.p.1.s.1.w.1","example.p.1.s.1.w.2"], &["example.p.1.s.1.w.4"]], Some("coref")).expect("adding chain");
+            let chain = doc.get_element(chain_key).expect("chain");
+            let layer = chain.get_parent().expect("layer");
+            assert_eq!(layer.elementtype(), ElementType::CoreferenceLayer);
+            assert_eq!(layer.get_parent().expect("sentence").id(), Some("example.p.1.s.1"));
+            assert_eq!(chain.mentions().len(), 2);
+            assert_eq!(doc.coreference_chains(Cmp::Is("coref".to_string())).count(), 1);
+            assert_eq!(doc.coreference_chains(Cmp::Is("other".to_string())).count(), 0);
+            let word = doc.get_element_by_id("example.p.1.s.1.w.4").expect("word");
+            assert_eq!(word.coreference_chains().len(), 1);
+            let mentions = word.coreferent_mentions();
+            assert_eq!(mentions.len(), 1);
+            let mention_words: Vec<String> = mentions[0].span_words().iter().map(|w| w.id().unwrap().to_string()).collect();
+            assert_eq!(mention_words, vec!["example.p.1.s.1.w.1", "example.p.1.s.1.w.2"]);
+            assert!(doc.get_element_by_id("example.p.1.s.1.w.3").expect("word").coreferent_mentions().is_empty());
+            //round trip
+            let xml = doc.xml(0, 4).expect("serialisation");
+            let doc2 = Document::from_str(str::from_utf8(&xml).expect("utf-8"), DocumentProperties::default()).expect("reparsing");
+            let word = doc2.get_element_by_id("example.p.1.s.1.w.1").expect("word");
+            let mention_words: Vec<String> = word.coreferent_mentions()[0].span_words().iter().map(|w| w.id().unwrap().to_string()).collect();
+            assert_eq!(mention_words, vec!["example.p.1.s.1.w.4"]);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test023a_features() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            let word_key = doc.get_element_key_by_id("example.p.1.s.2.w.1").expect("word");
+            doc.annotate(word_key, ElementData::new(ElementType::PosAnnotation)
+                                    .with_attrib(Attribute::Class("pron".to_string()))
+                                    .with_feature("head", "pron")
+                                    .with_feature("number", "singular")
+                                    .with_feature("person", "3")
+                                    .with_feature("person", "third")
+            ).expect("annotating");
+            let word = doc.get_element(word_key).expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.set(), Some("adhoc"));
+            let features = pos.features();
+            assert_eq!(features.len(), 3);
+            assert_eq!(features.get("head"), Some(&vec!["pron".to_string()]));
+            assert_eq!(features.get("person"), Some(&vec!["3".to_string(), "third".to_string()]));
+            assert_eq!(pos.get_feature(Cmp::Is("number".to_string())).expect("feature").class(), Some("singular"));
+            let xml = doc.xml(word_key, 0).expect("serialisation");
+            let xml = str::from_utf8(&xml).expect("utf-8");
+            assert!(xml.contains("<pos class=\"pron\" head=\"pron\">"), "{}", xml);
+            assert!(!xml.contains("subset=\"head\""), "{}", xml);
+            assert!(xml.contains("<feat subset=\"number\" class=\"singular\"/>"), "{}", xml);
+            assert!(xml.contains("<feat subset=\"person\" class=\"3\"/>"), "{}", xml);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test023b_features_roundtrip() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            let word_key = doc.get_element_key_by_id("example.p.1.s.2.w.1").expect("word");
+            doc.annotate(word_key, ElementData::new(ElementType::PosAnnotation)
+                                    .with_attrib(Attribute::Class("pron".to_string()))
+                                    .with_feature("head", "pron")
+            ).expect("annotating");
+            let xml = doc.xml(0, 4).expect("serialisation");
+            let xml = str::from_utf8(&xml).expect("utf-8");
+            assert!(xml.contains("<pos class=\"pron\" head=\"pron\"/>"), "{}", xml);
+            let doc2 = Document::from_str(xml, DocumentProperties::default()).expect("reparsing");
+            let word = doc2.get_element_by_id("example.p.1.s.2.w.1").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.features().get("head"), Some(&vec!["pron".to_string()]));
+            let word = doc2.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.features().get("number"), Some(&vec!["singular".to_string()]));
+            assert_eq!(doc2.xml(0, 4).expect("serialisation"), xml.as_bytes());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test024_foreign_xml() {
+    match Document::from_str(str::from_utf8(EXAMPLE_FOREIGN).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            assert_eq!(doc.metadata.foreigndata, vec![ForeignXml { xml: r#"<cmd:CMD xmlns:cmd="http://www.clarin.eu/cmd/" CMDVersion="1.1"><cmd:Header><cmd:MdCreator>proycon</cmd:MdCreator></cmd:Header><cmd:Resources/></cmd:CMD>"#.to_string(), namespaces: Vec::new() }]);
+            let word = doc.get_element_by_id("example.w.1").expect("word");
+            let foreigndata = word.get_element(ElementType::ForeignData, Cmp::Any, Recursion::No).expect("foreign data");
+            assert_eq!(foreigndata.foreign_xml().expect("foreign xml"), r#"<x:stuff xmlns:x="http://x.org/" x:id="1"><x:a attr="1">text &amp; more</x:a><!-- note --><x:b/></x:stuff>"#);
+            assert_eq!(word.text(&TextParameters::default()).expect("text"), "hello");
+            //the XML is kept as is, the namespace bound on the root is recorded along with it
+            let word = doc.get_element_by_id("example.w.2").expect("word");
+            let foreigndata = word.get_element(ElementType::ForeignData, Cmp::Any, Recursion::No).expect("foreign data");
+            assert_eq!(foreigndata.foreign_xml().expect("foreign xml"), r#"<ex:thing n="2"><ex:nested>in default</ex:nested></ex:thing>"#);
+            assert_eq!(foreigndata.elementdata().get_data_at(0), Some(&DataType::ForeignXml(ForeignXml {
+                xml: r#"<ex:thing n="2"><ex:nested>in default</ex:nested></ex:thing>"#.to_string(),
+                namespaces: vec![("ex".to_string(), "http://example.org/ex".to_string())],
+            })));
+            //round trip
+            let xml = doc.xml(0, 0).expect("serialisation");
+            let xml = str::from_utf8(&xml).expect("utf-8");
+            assert!(xml.contains(r#"<foreign-data><x:stuff xmlns:x="http://x.org/" x:id="1"><x:a attr="1">text &amp; more</x:a><!-- note --><x:b/></x:stuff></foreign-data>"#), "{}", xml);
+            assert!(xml.contains(r#"<foreign-data><cmd:CMD xmlns:cmd="http://www.clarin.eu/cmd/" CMDVersion="1.1">"#), "{}", xml);
+            //namespaces declared outside of the foreign XML are declared on the foreign-data element
+            assert!(xml.contains(r#"<foreign-data xmlns:ex="http://example.org/ex"><ex:thing n="2"><ex:nested>in default</ex:nested></ex:thing></foreign-data>"#), "{}", xml);
+            let doc2 = Document::from_str(xml, DocumentProperties::default()).expect("reparsing");
+            assert_eq!(doc2.metadata.foreigndata, doc.metadata.foreigndata);
+            assert_eq!(doc2.xml(0, 0).expect("serialisation"), xml.as_bytes());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test024b_foreign_submetadata() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation/>
+          <token-annotation/>
+      </annotations>
+      <submetadata xml:id="example.metadata.1" type="cmdi">
+          <foreign-data><cmd:CMD xmlns:cmd="http://www.clarin.eu/cmd/"  CMDVersion='1.1'><cmd:Header><cmd:MdCreator >proycon</cmd:MdCreator><cmd:MdProfile /></cmd:Header></cmd:CMD></foreign-data>
+      </submetadata>
+  </metadata>
+  <text xml:id="example.text">
+      <w xml:id="example.w.1"><t>hello</t></w>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            let submetadata = doc.submetadata.get("example.metadata.1").expect("submetadata");
+            //the foreign XML is preserved byte for byte
+            assert_eq!(submetadata.foreigndata[0].xml, r#"<cmd:CMD xmlns:cmd="http://www.clarin.eu/cmd/"  CMDVersion='1.1'><cmd:Header><cmd:MdCreator >proycon</cmd:MdCreator><cmd:MdProfile /></cmd:Header></cmd:CMD>"#);
+            //round trip
+            let xml = doc.xml(0, 0).expect("serialisation");
+            let xml = str::from_utf8(&xml).expect("utf-8");
+            assert!(xml.contains(r#"<submetadata xml:id="example.metadata.1" type="cmdi">"#), "{}", xml);
+            assert!(xml.contains("</submetadata>"), "{}", xml);
+            let doc2 = Document::from_str(xml, DocumentProperties::default()).expect("reparsing");
+            let submetadata2 = doc2.submetadata.get("example.metadata.1").expect("submetadata");
+            assert_eq!(submetadata2.foreigndata, submetadata.foreigndata);
+            assert_eq!(submetadata2.metadatatype.as_deref(), Some("cmdi"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test024c_foreign_namespace_clash() {
+    //the same prefix is bound to different namespaces in different foreign-data blocks
+    let xml = r#"<FoLiA xmlns="http://ilk.uvt.nl/folia" xmlns:xlink="http://www.w3.org/1999/xlink" xml:id="example" version="2.1.0" generator="folia-rust">
+<metadata type="cmdi">
+<annotations>
+<text-annotation/>
+<token-annotation/>
+<text-annotation set="https://raw.githubusercontent.com/proycon/folia/master/setdefinitions/text.foliaset.ttl"/>
+</annotations>
+<provenance>
+</provenance>
+<foreign-data xmlns:ns0="http://example.org/a"><ns0:record><ns0:title>A</ns0:title></ns0:record></foreign-data>
+<foreign-data xmlns:ns0="http://example.org/b"><ns0:record><ns0:title>B</ns0:title></ns0:record></foreign-data>
+</metadata>
+<text xml:id="example.text">
+<w xml:id="example.w.1">
+<t>hello</t>
+<foreign-data xmlns:ns0="http://example.org/c"><ns0:thing/></foreign-data>
+</w>
+<w xml:id="example.w.2">
+<t>world</t>
+<foreign-data xmlns:ns0="http://example.org/d"><ns0:thing/></foreign-data></w></text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            let namespaces: Vec<&str> = doc.metadata.foreigndata.iter().map(|foreign| foreign.namespaces[0].1.as_str()).collect();
+            assert_eq!(namespaces, vec!["http://example.org/a", "http://example.org/b"]);
+            let serialised = doc.xml(0, 0).expect("serialisation");
+            assert_eq!(str::from_utf8(&serialised).expect("utf-8"), xml);
+            let doc2 = Document::from_str(xml, DocumentProperties::default()).expect("reparsing");
+            assert_eq!(doc2.xml(0, 0).expect("serialisation"), serialised);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test025a_metadata_cmdi() {
+    match Document::from_str(str::from_utf8(EXAMPLE_FOREIGN).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            assert_eq!(doc.metadata.get_type(), MetadataType::Cmdi);
+            assert_eq!(doc.metadata.author(), Some("proycon".to_string()));
+            assert_eq!(doc.metadata.title(), None);
+            assert_eq!(doc.metadata.query("Header/MdCreator").expect("query"), vec!["proycon".to_string()]);
+            assert!(doc.metadata.query("Resources/MdCreator").expect("query").is_empty());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test025b_metadata_dublincore() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="dc">
+      <annotations>
+          <text-annotation/>
+      </annotations>
+      <foreign-data>
+        <dc:metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>Het boek</dc:title>
+          <dc:creator>J. Jansen &amp; P. Peters</dc:creator>
+          <dc:date>1984-04-04</dc:date>
+        </dc:metadata>
+      </foreign-data>
+  </metadata>
+  <text xml:id="example.text">
+      <t>Hallo</t>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            assert_eq!(doc.metadata.get_type(), MetadataType::DublinCore);
+            assert_eq!(doc.metadata.title(), Some("Het boek".to_string()));
+            assert_eq!(doc.metadata.author(), Some("J. Jansen & P. Peters".to_string()));
+            assert_eq!(doc.metadata.date(), Some("1984-04-04".to_string()));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test025c_metadata_src_imdi() {
+    let dir = std::env::temp_dir().join(format!("folia-test025c-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("creating temporary directory");
+    std::fs::write(dir.join("example.imdi"), r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- IMDI session -->
+<METATRANSCRIPT xmlns="http://www.mpi.nl/IMDI/Schema/IMDI" Type="SESSION">
+  <Session>
+    <Name>interview-01</Name>
+    <Title>An interview</Title>
+    <Date>2001-02-03</Date>
+    <MDGroup><Actors><Actor><Name>speaker1</Name><FullName>Jan Jansen</FullName></Actor></Actors></MDGroup>
+  </Session>
+</METATRANSCRIPT>"#).expect("writing metadata");
+    std::fs::write(dir.join("example.folia.xml"), r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="imdi" src="example.imdi">
+      <annotations>
+          <text-annotation/>
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+      <t>Hallo</t>
+  </text>
+</FoLiA>"#).expect("writing document");
+    match Document::from_file(dir.join("example.folia.xml").to_str().unwrap(), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            assert_eq!(doc.metadata.get_type(), MetadataType::Imdi);
+            assert_eq!(doc.metadata.title(), None);
+            doc.load_metadata_src().expect("loading metadata");
+            assert_eq!(doc.metadata.title(), Some("An interview".to_string()));
+            assert_eq!(doc.metadata.author(), Some("Jan Jansen".to_string()));
+            assert_eq!(doc.metadata.date(), Some("2001-02-03".to_string()));
+            //external metadata is not inlined
+            let xml = doc.xml(0, 0).expect("serialisation");
+            assert!(!str::from_utf8(&xml).expect("utf-8").contains("METATRANSCRIPT"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test026_submetadata() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <division-annotation/>
+          <text-annotation/>
+          <token-annotation/>
+      </annotations>
+      <meta id="author">Editor</meta>
+      <submetadata xml:id="chapter1.metadata">
+          <meta id="author">Jan Jansen</meta>
+      </submetadata>
+      <submetadata xml:id="chapter2.metadata">
+          <meta id="author">Piet Peters</meta>
+      </submetadata>
+  </metadata>
+  <text xml:id="example.text">
+    <div xml:id="example.preface">
+      <w xml:id="example.preface.w.1"><t>Preface</t></w>
+    </div>
+    <div xml:id="example.chapter1" metadata="chapter1.metadata">
+      <w xml:id="example.chapter1.w.1"><t>One</t></w>
+    </div>
+    <div xml:id="example.chapter2">
+      <w xml:id="example.chapter2.w.1"><t>Two</t></w>
+    </div>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(mut doc) => {
+            assert_eq!(doc.submetadata.len(), 2);
+            let word = doc.get_element_by_id("example.chapter1.w.1").expect("word");
+            assert_eq!(word.metadata_id(), Some("chapter1.metadata"));
+            assert_eq!(word.metadata().expect("metadata").get("author"), Some("Jan Jansen".to_string()));
+            let word = doc.get_element_by_id("example.preface.w.1").expect("word");
+            assert_eq!(word.metadata_id(), None);
+            assert_eq!(word.metadata().expect("metadata").get("author"), Some("Editor".to_string()));
+
+            let key = doc.get_element_key_by_id("example.chapter2").expect("div");
+            assert!(doc.assign_submetadata(key, Some("nonexistent")).is_err());
+            doc.assign_submetadata(key, Some("chapter2.metadata")).expect("assigning submetadata");
+            let word = doc.get_element_by_id("example.chapter2.w.1").expect("word");
+            assert_eq!(word.metadata().expect("metadata").get("author"), Some("Piet Peters".to_string()));
+
+            let mut metadata = Metadata::default();
+            metadata.data.insert("author".to_string(), "Klaas Klaassen".to_string());
+            doc.add_submetadata("preface.metadata", metadata).expect("adding submetadata");
+            assert!(doc.add_submetadata("preface.metadata", Metadata::default()).is_err());
+            let key = doc.get_element_key_by_id("example.preface.w.1").expect("word");
+            doc.assign_submetadata(key, Some("preface.metadata")).expect("assigning submetadata");
+
+            //submetadata and references survive serialisation
+            let xml = doc.xml(0, 0).expect("serialisation");
+            let doc = Document::from_str(str::from_utf8(&xml).expect("utf-8"), DocumentProperties::default()).expect("reparsing");
+            assert_eq!(doc.submetadata.len(), 3);
+            let word = doc.get_element_by_id("example.preface.w.1").expect("word");
+            assert_eq!(word.metadata().expect("metadata").get("author"), Some("Klaas Klaassen".to_string()));
+            let word = doc.get_element_by_id("example.chapter2.w.1").expect("word");
+            assert_eq!(word.metadata().expect("metadata").get("author"), Some("Piet Peters".to_string()));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test027_canonical_serialisation() {
+    let xml_a = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation/>
+          <token-annotation/>
+          <sentence-annotation/>
+          <pos-annotation set="https://somewhere/tagset"/>
+      </annotations>
+      <meta id="title">Example</meta>
+      <meta id="author">Jan Jansen</meta>
+      <meta id="date">2020-01-01</meta>
+      <meta id="language">nld</meta>
+  </metadata>
+  <text xml:id="example.text">
+    <s xml:id="example.s.1" speaker="jan" n="1">
+      <t>Hello   world</t>
+      <w xml:id="example.s.1.w.1" class="WORD"><t>Hello</t><pos class="INTJ" confidence="0.5"/></w>
+      <w class="WORD" xml:id="example.s.1.w.2"><t>world</t></w>
+    </s>
+  </text>
+</FoLiA>"#;
+    let xml_b = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <pos-annotation set="https://somewhere/tagset"/>
+          <sentence-annotation/>
+          <token-annotation/>
+          <text-annotation/>
+      </annotations>
+      <meta id="language">nld</meta>
+      <meta id="date">2020-01-01</meta>
+      <meta id="title">Example</meta>
+      <meta id="author">Jan Jansen</meta>
+  </metadata>
+  <text xml:id="example.text">
+    <s n="1" speaker="jan" xml:id="example.s.1">
+      <t>Hello
+        world</t>
+      <w class="WORD" xml:id="example.s.1.w.1"><t>Hello</t><pos confidence="0.5" class="INTJ"/></w>
+      <w xml:id="example.s.1.w.2" class="WORD"><t>world</t></w>
+    </s>
+  </text>
+</FoLiA>"#;
+    let doc_a = Document::from_str(xml_a, DocumentProperties::default()).expect("parsing document a");
+    let doc_b = Document::from_str(xml_b, DocumentProperties::default()).expect("parsing document b");
+    let canonical_a = doc_a.xml_canonical(0).expect("serialisation");
+    let canonical_b = doc_b.xml_canonical(0).expect("serialisation");
+    assert_eq!(str::from_utf8(&canonical_a).unwrap(), str::from_utf8(&canonical_b).unwrap());
+    let canonical = str::from_utf8(&canonical_a).unwrap();
+    assert!(canonical.find("<meta id=\"author\">").unwrap() < canonical.find("<meta id=\"title\">").unwrap());
+    assert!(canonical.find("<pos-annotation").unwrap() < canonical.find("<text-annotation").unwrap());
+    assert!(canonical.contains("<s xml:id=\"example.s.1\" n=\"1\" speaker=\"jan\">"));
+    assert!(canonical.contains("<t>Hello world</t>"));
+    //canonical output is stable when reparsed
+    let doc_c = Document::from_str(canonical, DocumentProperties::default()).expect("reparsing");
+    assert_eq!(doc_c.xml_canonical(0).expect("serialisation"), canonical_a);
+    //metadata order is stable in regular serialisation too
+    let xml = doc_b.xml(0, 2).expect("serialisation");
+    let xml = str::from_utf8(&xml).unwrap();
+    assert!(xml.find("<meta id=\"author\">").unwrap() < xml.find("<meta id=\"date\">").unwrap());
+}
+
+#[test]
+fn test027b_empty_declarations() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata>
+      <annotations>
+          <text-annotation />
+          <pos-annotation set="adhoc-pos"/>
+          <lemma-annotation set="adhoc-lemma" />
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+    <s xml:id="example.s.1">
+      <w xml:id="example.s.1.w.1"><t>Hello</t><pos class="intj"/></w>
+    </s>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            //declarations without annotators are self-closing
+            assert_eq!(doc.get_default_set(AnnotationType::POS), Some("adhoc-pos"));
+            assert_eq!(doc.get_default_set(AnnotationType::LEMMA), Some("adhoc-lemma"));
+            let word = doc.get_element_by_id("example.s.1.w.1").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.set(), Some("adhoc-pos"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test028_serialise_options() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation/>
+          <token-annotation/>
+          <sentence-annotation/>
+          <pos-annotation set="adhoc">
+             <annotator processor="p1" />
+          </pos-annotation>
+          <lemma-annotation set="adhoc-lemmas"/>
+      </annotations>
+      <provenance>
+         <processor xml:id="p1" name="tagger" type="auto" />
+      </provenance>
+  </metadata>
+  <text xml:id="example.text">
+    <s xml:id="example.s.1">
+      <!-- a comment -->
+      <w xml:id="example.s.1.w.1"><t>houses</t><pos class="N(plural)" head="N"/><lemma class="house"/></w>
+    </s>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            let compact = doc.xml_with_options(0, &SerialiseOptions::default()).expect("serialisation");
+            assert_eq!(compact, doc.xml(0, 0).expect("serialisation"));
+            let compact = str::from_utf8(&compact).unwrap();
+            assert!(compact.contains("<pos class=\"N(plural)\" head=\"N\"/>"), "{}", compact);
+            assert!(compact.contains("<!-- a comment -->"), "{}", compact);
+            assert!(!compact.starts_with("<?xml"));
+
+            let options = SerialiseOptions::default().with_explicit(true).with_xml_declaration(true);
+            let explicit = doc.xml_with_options(0, &options).expect("serialisation");
+            let explicit = str::from_utf8(&explicit).unwrap();
+            assert!(explicit.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>"), "{}", explicit);
+            assert!(explicit.contains("form=\"explicit\""), "{}", explicit);
+            assert!(explicit.contains("<s xml:id=\"example.s.1\" typegroup=\"structure\">"), "{}", explicit);
+            assert!(explicit.contains("<pos set=\"adhoc\" class=\"N(plural)\" processor=\"p1\" typegroup=\"inline\">"), "{}", explicit);
+            assert!(explicit.contains("<feat subset=\"head\" class=\"N\" typegroup=\"higherorder\"/>"), "{}", explicit);
+            assert!(explicit.contains("<lemma set=\"adhoc-lemmas\" class=\"house\" typegroup=\"inline\"/>"), "{}", explicit);
+            assert!(explicit.contains("<t class=\"current\" typegroup=\"content\">houses</t>"), "{}", explicit);
+            let doc2 = Document::from_str(explicit, DocumentProperties::default()).expect("reparsing explicit form");
+            assert_eq!(doc2.xml_with_options(0, &options).expect("serialisation"), explicit.as_bytes());
+
+            let options = SerialiseOptions::default().with_comments(false).with_exclude(AnnotationType::POS).with_self_closing(false);
+            let reduced = doc.xml_with_options(0, &options).expect("serialisation");
+            let reduced = str::from_utf8(&reduced).unwrap();
+            assert!(!reduced.contains("comment"), "{}", reduced);
+            assert!(!reduced.contains("<pos "), "{}", reduced);
+            assert!(!reduced.contains("pos-annotation"), "{}", reduced);
+            assert!(reduced.contains("<lemma class=\"house\"></lemma>"), "{}", reduced);
+            assert!(reduced.contains("<text-annotation></text-annotation>"), "{}", reduced);
+            let doc3 = Document::from_str(reduced, DocumentProperties::default()).expect("reparsing reduced form");
+            let word = doc3.get_element_by_id("example.s.1.w.1").expect("word");
+            assert!(word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).is_none());
+            assert!(word.get_annotation(AnnotationType::LEMMA, Cmp::Any, Recursion::No).is_some());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+    //explicit form also writes the defaults of declarations with processors and the text spanned by word references
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let explicit = doc.xml_with_options(0, &SerialiseOptions::default().with_explicit(true)).expect("serialisation");
+            let explicit = str::from_utf8(&explicit).unwrap();
+            assert!(explicit.contains("<w xml:id=\"example.p.1.s.2.w.4\" set=\"https://raw.githubusercontent.com/LanguageMachines/uctodata/master/setdefinitions/tokconfig-eng.foliaset.ttl\" class=\"WORD\" processor=\"p1\" typegroup=\"structure\">"), "{}", explicit);
+            assert!(explicit.contains("<pos set=\"adhoc\" class=\"noun\" processor=\"p2\" typegroup=\"inline\">"), "{}", explicit);
+            assert!(explicit.contains("<wref id=\"example.p.1.s.2.w.4\" t=\"example\"/>"), "{}", explicit);
+            assert!(explicit.contains("<chunking set=\"shallowsyntaxset\" typegroup=\"layer\">"), "{}", explicit);
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test029_fragments() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let sentence_key = doc.get_element_key_by_id("example.p.1.s.2").expect("sentence");
+            let fragment = doc.xml_fragment(sentence_key, &SerialiseOptions::default(), false).expect("serialisation");
+            let fragment = str::from_utf8(&fragment).expect("utf-8");
+            assert!(fragment.starts_with("<s xmlns=\"http://ilk.uvt.nl/folia\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" xml:id=\"example.p.1.s.2\">"), "{}", fragment);
+            assert!(!fragment.contains("<metadata"), "{}", fragment);
+
+            let fragment = doc.xml_fragment(sentence_key, &SerialiseOptions::default().with_indent(2), true).expect("serialisation");
+            let fragment = str::from_utf8(&fragment).expect("utf-8");
+            assert!(fragment.starts_with("<fragment xmlns=\"http://ilk.uvt.nl/folia\""), "{}", fragment);
+            assert!(fragment.contains("<pos-annotation set=\"adhoc\">"), "{}", fragment);
+            assert!(fragment.contains("<chunking-annotation set=\"shallowsyntaxset\">"), "{}", fragment);
+            assert!(!fragment.contains("paragraph-annotation"), "{}", fragment);
+
+            //read the fragment back into a new document
+            let mut doc2 = Document::new("example2", DocumentProperties::default()).expect("new document");
+            let keys = doc2.parse_fragment(0, fragment).expect("parsing fragment");
+            assert_eq!(keys.len(), 1);
+            let sentence = doc2.get_element(keys[0]).expect("sentence");
+            assert_eq!(sentence.id(), Some("example.p.1.s.2"));
+            assert_eq!(sentence.text(&TextParameters::default()).expect("text"), "This is an example & a test.");
+            let word = doc2.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.set(), Some("adhoc"));
+            assert_eq!(pos.class(), Some("noun"));
+            assert_eq!(pos.features().get("number"), Some(&vec!["singular".to_string()]));
+            let chunk = doc2.get_element_by_id("example.p.1.s.2.chunk.1").expect("chunk");
+            assert_eq!(chunk.span_words().len(), 2);
+            assert_eq!(chunk.set(), Some("shallowsyntaxset"));
+
+            //the same elements can't be added twice
+            assert!(doc2.parse_fragment(0, fragment).is_err());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test029b_fragments_explicit_sets() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let word_key = doc.get_element_key_by_id("example.p.1.s.2.w.4").expect("word");
+            let fragment = doc.xml_fragment(word_key, &SerialiseOptions::default(), false).expect("serialisation");
+            let fragment = str::from_utf8(&fragment).expect("utf-8");
+            //adhoc and p2 are the defaults in the source document, but they must still be written
+            assert!(fragment.contains("<pos set=\"adhoc\" class=\"noun\" processor=\"p2\">"), "{}", fragment);
+
+            //read the fragment into a document with another default set for pos
+            let fragment = doc.xml_fragment(word_key, &SerialiseOptions::default(), true).expect("serialisation");
+            let fragment = str::from_utf8(&fragment).expect("utf-8");
+            assert!(fragment.contains("<pos set=\"adhoc\" class=\"noun\" processor=\"p2\">"), "{}", fragment);
+            let mut doc2 = Document::new("example2", DocumentProperties::default()).expect("new document");
+            doc2.declare(AnnotationType::POS, &Some("otherset".to_string()), &None, &None).expect("declaration");
+            let keys = doc2.parse_fragment(0, fragment).expect("parsing fragment");
+            assert_eq!(keys.len(), 1);
+            let word = doc2.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.set(), Some("adhoc"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test029c_fragments_provenance() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let sentence_key = doc.get_element_key_by_id("example.p.1.s.2").expect("sentence");
+            let fragment = doc.xml_fragment(sentence_key, &SerialiseOptions::default(), true).expect("serialisation");
+            let fragment = str::from_utf8(&fragment).expect("utf-8");
+            assert!(fragment.contains("<processor xml:id=\"p1\" name=\"proycon\" type=\"manual\"/>"), "{}", fragment);
+            assert!(fragment.contains("<processor xml:id=\"p2\" name=\"proycon\" type=\"manual\"/>"), "{}", fragment);
+
+            //read the fragment into a document that has its own provenance
+            let mut doc2 = Document::new("example2", DocumentProperties::default().with_processor(Processor::this_library())).expect("new document");
+            doc2.parse_fragment(0, fragment).expect("parsing fragment");
+            let word = doc2.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.processor(), Some("p2"));
+            let chunk = doc2.get_element_by_id("example.p.1.s.2.chunk.1").expect("chunk");
+            assert_eq!(chunk.processor(), Some("p2"));
+            assert_eq!(doc2.get_processor_key_by_id("p1").and_then(|key| doc2.get_processor(key)).map(|processor| processor.name.as_str()), Some("proycon"));
+
+            //the result can be serialised and read again
+            let xml = doc2.xml(0, 4).expect("serialisation");
+            let doc3 = Document::from_str(str::from_utf8(&xml).expect("utf-8"), DocumentProperties::default()).expect("reparsing");
+            let word = doc3.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            assert_eq!(word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos").processor(), Some("p2"));
+            assert_eq!(word.processor(), Some("p1"));
+            assert_eq!(doc3.xml(0, 4).expect("serialisation"), xml);
+
+            //without declarations, references to unknown processors are dropped
+            let fragment = doc.xml_fragment(sentence_key, &SerialiseOptions::default(), false).expect("serialisation");
+            let mut doc4 = Document::new("example4", DocumentProperties::default()).expect("new document");
+            doc4.parse_fragment(0, str::from_utf8(&fragment).expect("utf-8")).expect("parsing fragment");
+            let word = doc4.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            assert!(word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos").processor().is_none());
+            assert!(doc4.get_processor_key_by_id("p2").is_none());
+            doc4.xml(0, 4).expect("serialisation");
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test030_upgrade_v1() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="1.5.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <token-annotation annotator="ucto" annotatortype="auto" set="tokconfig-nld"/>
+          <pos-annotation set="adhoc" annotator="frog" annotatortype="auto"/>
+          <lemma-annotation set="adhoc-lemmas"/>
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+    <s xml:id="example.s.1">
+      <w xml:id="example.s.1.w.1" class="WORD"><t>Hallo</t><pos class="TSW"/><lemma class="hallo" annotator="jan" annotatortype="manual"/></w>
+      <w xml:id="example.s.1.w.2" class="WORD"><t>wereld</t><pos class="N" annotator="jan" annotatortype="manual"/><lemma class="wereld" annotator="jan" annotatortype="manual"/></w>
+    </s>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            assert_eq!(doc.version, FOLIAVERSION);
+            assert!(!doc.is_legacy());
+            let word = doc.get_element_by_id("example.s.1.w.1").expect("word");
+            assert_eq!(word.processor(), Some("proc.ucto.auto"));
+            assert_eq!(word.annotatortype(), Some(ProcessorType::Auto));
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.annotator(), Some("frog"));
+            let lemma = word.get_annotation(AnnotationType::LEMMA, Cmp::Any, Recursion::No).expect("lemma");
+            assert_eq!(lemma.processor(), Some("proc.jan.manual"));
+            assert_eq!(lemma.annotatortype(), Some(ProcessorType::Manual));
+            assert!(lemma.attrib(AttribType::ANNOTATOR).is_none());
+            let word = doc.get_element_by_id("example.s.1.w.2").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            let lemma = word.get_annotation(AnnotationType::LEMMA, Cmp::Any, Recursion::No).expect("lemma");
+            //the same annotator maps to the same processor
+            assert_eq!(pos.processor_key(), lemma.processor_key());
+            assert_eq!(doc.provenancestore.chain.len(), 3);
+
+            let xml = doc.xml(0, 2).expect("serialisation");
+            let xml = str::from_utf8(&xml).expect("utf-8");
+            assert!(!xml.contains("annotator="), "{}", xml);
+            assert!(xml.contains("<processor xml:id=\"proc.jan.manual\" name=\"jan\" type=\"manual\"/>"), "{}", xml);
+            assert!(xml.contains("<pos class=\"N\" processor=\"proc.jan.manual\"/>"), "{}", xml);
+            assert!(xml.contains("<pos class=\"TSW\" processor=\"proc.frog.auto\"/>"), "{}", xml);
+            let doc2 = Document::from_str(xml, DocumentProperties::default()).expect("reparsing");
+            assert_eq!(doc2.xml(0, 2).expect("serialisation"), xml.as_bytes());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test031_versions() {
+    let template = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="VERSION" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation/>
+          <sentence-annotation/>
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+    <s xml:id="example.s.1">
+      <w xml:id="example.s.1.w.1" class="WORD"><t>Hallo</t></w>
+    </s>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(&template.replace("VERSION", "3.0"), DocumentProperties::default()) {
+        Ok(_) => assert!(false, "Document in a newer FoLiA version should be rejected"),
+        Err(err) => assert!(format!("{}", err).contains("requires FoLiA v3.0"), "{}", err),
+    }
+    assert!(Document::from_str(&template.replace("VERSION", "2.1.9"), DocumentProperties::default()).is_ok());
+
+    //tokens are not declared, that is only acceptable in FoLiA v1 if automatic declaration is disabled
+    assert!(Document::from_str(&template.replace("VERSION", "2.0"), DocumentProperties::default().with_autodeclare(false)).is_err());
+    let doc = Document::from_str(&template.replace("VERSION", "1.5"), DocumentProperties::default().with_autodeclare(false)).expect("parsing v1 document");
+    assert!(!doc.autodeclare);
+    //without a set, class or processor there is nothing to declare, so undeclared elements are accepted
+    assert!(Document::from_str(&template.replace("VERSION", "2.0").replace(" class=\"WORD\"", ""), DocumentProperties::default().with_autodeclare(false)).is_ok());
+
+    let mut doc = Document::from_str(&template.replace("VERSION", "2.0"), DocumentProperties::default()).expect("parsing document");
+    assert_eq!(doc.required_version(), "1.0.0");
+    let sentence_key = doc.get_element_key_by_id("example.s.1").expect("sentence");
+    doc.add_element_to(sentence_key, ElementData::new(ElementType::Hiddenword).with_attrib(Attribute::Id("example.s.1.hw.1".to_string()))).expect("adding hidden word");
+    assert_eq!(doc.required_version(), "2.0.0");
+
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            //provenance requires FoLiA v2
+            assert_eq!(doc.required_version(), "2.0.0");
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test031b_autodeclare_disabled() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default().with_autodeclare(false)) {
+        Ok(mut doc) => {
+            let word_key = doc.get_element_key_by_id("example.p.1.s.1.w.1").expect("word");
+            //adding already encoded data (for a declared type) works
+            let pos = doc.get_element_by_id("example.p.1.s.2.w.4").expect("word").get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos").elementdata().clone();
+            doc.add_element_to(word_key, pos).expect("adding encoded pos");
+            let word = doc.get_element(word_key).expect("word");
+            assert_eq!(word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos").class(), Some("noun"));
+
+            //annotation types that are not declared are rejected
+            assert!(doc.add_element_to(word_key, ElementData::new(ElementType::LemmaAnnotation).with_attrib(Attribute::Set("lemmas".to_string())).with_attrib(Attribute::Class("hello".to_string()))).is_err());
+            //as are undeclared sets of declared types
+            assert!(doc.add_element_to(word_key, ElementData::new(ElementType::PosAnnotation).with_attrib(Attribute::Set("otherset".to_string())).with_attrib(Attribute::Class("N".to_string()))).is_err());
+            //but declared ones are accepted
+            doc.declare(AnnotationType::LEMMA, &Some("lemmas".to_string()), &None, &None).expect("declaration");
+            doc.add_element_to(word_key, ElementData::new(ElementType::LemmaAnnotation).with_attrib(Attribute::Set("lemmas".to_string())).with_attrib(Attribute::Class("hello".to_string()))).expect("adding lemma");
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test032_conllu_export() {
+    match Document::from_str(str::from_utf8(EXAMPLE_DEP).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(mut doc) => {
+            let word_key = doc.get_element_key_by_id("example.p.1.s.1.w.2").expect("word");
+            doc.annotate(word_key, ElementData::new(ElementType::PosAnnotation)
+                                    .with_attrib(Attribute::Set("https://universaldependencies.org/u/pos/".to_string()))
+                                    .with_attrib(Attribute::Class("NOUN".to_string()))
+                                    .with_feature("Number", "Sing")
+                                    .with_feature("Gender", "Com")
+            ).expect("annotating pos");
+            doc.annotate(word_key, ElementData::new(ElementType::PosAnnotation)
+                                    .with_attrib(Attribute::Set("cgn".to_string()))
+                                    .with_attrib(Attribute::Class("N(soort,ev)".to_string()))
+            ).expect("annotating xpos");
+            doc.annotate(word_key, ElementData::new(ElementType::LemmaAnnotation)
+                                    .with_attrib(Attribute::Class("man".to_string()))
+            ).expect("annotating lemma");
+            //part-of-speech tags in other sets never end up in UPOS
+            let word_key = doc.get_element_key_by_id("example.p.1.s.1.w.1").expect("word");
+            doc.annotate(word_key, ElementData::new(ElementType::PosAnnotation)
+                                    .with_attrib(Attribute::Set("adhoc".to_string()))
+                                    .with_attrib(Attribute::Class("art".to_string()))
+            ).expect("annotating pos");
+            let options = ConlluOptions::default().with_upos_set("https://universaldependencies.org/u/pos/").with_xpos_set("cgn");
+            let conllu = doc.to_conllu(&options).expect("conllu");
+            let lines: Vec<&str> = conllu.lines().collect();
+            assert_eq!(lines[0], "# sent_id = example.p.1.s.1");
+            assert_eq!(lines[1], "# text = De man begroette hem.");
+            assert_eq!(lines[2], "1\tDe\t_\t_\t_\t_\t2\tdet\t_\t_");
+            assert_eq!(lines[3], "2\tman\tman\tNOUN\tN(soort,ev)\tGender=Com|Number=Sing\t3\tsu\t_\t_");
+            //the root has no dependency annotation in this document
+            assert_eq!(lines[4], "3\tbegroette\t_\t_\t_\t_\t_\t_\t_\t_");
+            assert_eq!(lines[5], "4\them\t_\t_\t_\t_\t3\tobj1\t_\tSpaceAfter=No");
+            assert_eq!(lines[7], "");
+            assert_eq!(lines.len(), 8);
+
+            //UPOS comes from the UD set by default
+            let conllu = doc.to_conllu(&ConlluOptions::default()).expect("conllu");
+            assert_eq!(conllu.lines().nth(2), Some("1\tDe\t_\t_\t_\t_\t2\tdet\t_\t_"));
+            assert_eq!(conllu.lines().nth(3), Some("2\tman\tman\tNOUN\t_\tGender=Com|Number=Sing\t3\tsu\t_\t_"));
+
+            //a set that is not used leaves the columns empty
+            let options = ConlluOptions::default().with_upos_set("nonexistent").with_dependency_set("nonexistent");
+            let conllu = doc.to_conllu(&options).expect("conllu");
+            assert_eq!(conllu.lines().nth(3), Some("2\tman\tman\t_\t_\t_\t_\t_\t_\t_"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test033_conllu_import() {
+    let conllu = "# newpar\n\
+# sent_id = s1\n\
+# text = Il va au marché.\n\
+1\tIl\til\tPRON\t_\tNumber=Sing|Person=3\t2\tnsubj\t_\t_\n\
+2\tva\taller\tVERB\t_\t_\t0\troot\t_\t_\n\
+3-4\tau\t_\t_\t_\t_\t_\t_\t_\t_\n\
+3\tà\tà\tADP\t_\t_\t5\tcase\t_\t_\n\
+4\tle\tle\tDET\t_\tDefinite=Def|PronType=Art\t5\tdet\t_\t_\n\
+5\tmarché\tmarché\tNOUN\t_\tGender=Masc|Number=Sing\t2\tobl\t_\tSpaceAfter=No\n\
+6\t.\t.\tPUNCT\t_\t_\t2\tpunct\t_\t_\n\
+\n\
+# sent_id = 2\n\
+1\tOui\toui\tINTJ\t_\t_\t0\troot\t_\t_\n\
+2\t!\t!\t_\t_\tPunctType=Excl\t1\t_\t_\t_\n\
+\n";
+    let doc = Document::from_conllu("example", conllu, &ConlluOptions::default(), DocumentProperties::default()).expect("importing conllu");
+    assert!(doc.get_element_by_id("example.p.1").is_some());
+    let sentence = doc.get_element_by_id("s1").expect("sentence");
+    assert_eq!(sentence.text(&TextParameters::default()).expect("text"), "Il va au marché.");
+    //a multiword token is a word followed by hidden words for the syntactic words
+    let token = doc.get_element_by_id("s1.w.3-4").expect("multiword token");
+    assert_eq!(token.elementtype(), ElementType::Word);
+    assert_eq!(token.text(&TextParameters::default()).expect("text"), "au");
+    let syntacticword = doc.get_element_by_id("s1.w.4").expect("syntactic word");
+    assert_eq!(syntacticword.elementtype(), ElementType::Hiddenword);
+    assert_eq!(syntacticword.get_annotation(AnnotationType::LEMMA, Cmp::Any, Recursion::No).expect("lemma").class(), Some("le"));
+    assert_eq!(syntacticword.dependency_head().and_then(|head| head.id().map(|s| s.to_string())), Some("s1.w.5".to_string()));
+    let word = doc.get_element_by_id("s1.w.5").expect("word");
+    assert_eq!(word.get_annotation(AnnotationType::POS, Cmp::Is(UD_POS_SET.to_string()), Recursion::No).expect("pos").class(), Some("NOUN"));
+    assert_eq!(word.get_annotation(AnnotationType::LEMMA, Cmp::Any, Recursion::No).expect("lemma").class(), Some("marché"));
+    assert_eq!(word.dependency_head().and_then(|head| head.id().map(|s| s.to_string())), Some("s1.w.2".to_string()));
+    assert_eq!(word.deprel(), Some("obl"));
+    //the root is kept as a dependency without a head
+    let root = doc.get_element_by_id("s1.w.2").expect("word");
+    assert!(root.dependency().is_some());
+    assert!(root.dependency_head().is_none());
+    assert_eq!(root.deprel(), Some("root"));
+    //the second sentence ID is not a valid XML ID and is generated instead
+    assert!(doc.get_element_by_id("example.s.2").is_some());
+    //an unknown relation gives a dependency without a class
+    let word = doc.get_element_by_id("example.s.2.w.2").expect("word");
+    assert_eq!(word.dependency_head().and_then(|head| head.id().map(|s| s.to_string())), Some("example.s.2.w.1".to_string()));
+    assert!(word.dependency().expect("dependency").class().is_none());
+    //features without UPOS are kept on a part-of-speech annotation without a class
+    let pos = word.get_annotation(AnnotationType::POS, Cmp::Is(UD_POS_SET.to_string()), Recursion::No).expect("pos");
+    assert!(pos.class().is_none());
+    assert_eq!(pos.features().get("PunctType"), Some(&vec!["Excl".to_string()]));
+    assert!(doc.active_processor.is_some());
+
+    //export again
+    let exported = doc.to_conllu(&ConlluOptions::default()).expect("exporting conllu");
+    let expected_lines: Vec<&str> = conllu.lines().filter(|line| !line.starts_with('#')).collect();
+    let exported_lines: Vec<&str> = exported.lines().filter(|line| !line.starts_with('#')).collect();
+    assert_eq!(exported_lines, expected_lines);
+
+    //the document can be serialised and reloaded
+    let xml = doc.xml(0, 4).expect("serialisation");
+    assert!(str::from_utf8(&xml).expect("utf-8").contains("<hiddenw xml:id=\"s1.w.3\">"));
+    assert!(!str::from_utf8(&xml).expect("utf-8").contains("class=\"_\""));
+    let doc2 = Document::from_str(str::from_utf8(&xml).expect("utf-8"), DocumentProperties::default()).expect("reparsing");
+    assert_eq!(doc2.to_conllu(&ConlluOptions::default()).expect("exporting conllu"), exported);
+
+    assert!(Document::from_conllu("example", "1\tfoo\n", &ConlluOptions::default(), DocumentProperties::default()).is_err());
+    //sentence IDs must be unique
+    let duplicate = "# sent_id = s1\n1\tJa\tja\tINTJ\t_\t_\t0\troot\t_\t_\n\n# sent_id = s1\n1\tNee\tnee\tINTJ\t_\t_\t0\troot\t_\t_\n";
+    match Document::from_conllu("example", duplicate, &ConlluOptions::default(), DocumentProperties::default()) {
+        Ok(_) => assert!(false, "Duplicate sentence IDs should be rejected"),
+        Err(err) => assert!(format!("{}", err).contains("Duplicate sentence ID"), "{}", err),
+    }
+}
+
+#[test]
+fn test034_from_text() {
+    let text = "Hello world! This is Dr. Jansen's test (for e.g. tokenisation), isn't it?\n\"Yes.\" It costs 3.50 euro...\n\n  \nA second paragraph\nspanning two lines";
+    let doc = Document::from_text("example", text, &TokeniserOptions::default(), DocumentProperties::default()).expect("from_text");
+    let words: Vec<String> = doc.select(Selector::elements().element(Cmp::Is(ElementType::Word)), Recursion::Always)
+                                .map(|word| word.text(&TextParameters::default()).expect("text")).collect();
+    assert_eq!(words, vec!["Hello", "world", "!", "This", "is", "Dr.", "Jansen's", "test", "(", "for", "e.g.", "tokenisation", ")", ",", "isn't", "it", "?",
+                           "\"", "Yes", ".", "\"", "It", "costs", "3.50", "euro", "...",
+                           "A", "second", "paragraph", "spanning", "two", "lines"]);
+    let sentences: Vec<String> = doc.select(Selector::elements().element(Cmp::Is(ElementType::Sentence)), Recursion::Always)
+                                .map(|sentence| sentence.text(&TextParameters::default()).expect("text")).collect();
+    assert_eq!(sentences, vec!["Hello world!", "This is Dr. Jansen's test (for e.g. tokenisation), isn't it?", "\"Yes.\"", "It costs 3.50 euro...", "A second paragraph spanning two lines"]);
+    assert_eq!(doc.get_element_by_id("example.p.1.s.2.w.13").expect("word").attrib(AttribType::SPACE), Some(&Attribute::Space(false)));
+    assert_eq!(doc.get_element_by_id("example.p.2.s.1.w.3").expect("word").text(&TextParameters::default()).expect("text"), "paragraph");
+    assert!(doc.get_element_by_id("example.p.3").is_none());
+    let xml = doc.xml(0, 4).expect("serialisation");
+    let xml = str::from_utf8(&xml).expect("utf-8");
+    assert!(xml.contains("<token-annotation/>"), "{}", xml);
+    assert!(xml.contains("<sentence-annotation/>"), "{}", xml);
+    assert!(xml.contains("<paragraph-annotation/>"), "{}", xml);
+
+    let doc = Document::from_text("example", "one line\nanother line", &TokeniserOptions::default().with_sentenceperline(true), DocumentProperties::default()).expect("from_text");
+    assert!(doc.get_element_by_id("example.p.1.s.2.w.2").is_some());
+}
+
+#[test]
+fn test034b_text_class() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata>
+      <annotations>
+          <text-annotation set="https://raw.githubusercontent.com/proycon/folia/master/setdefinitions/text.foliaset.ttl" />
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+    <s xml:id="example.s.1">
+      <w xml:id="example.s.1.w.1"><t>colour</t><t class="original">color</t></w>
+      <w xml:id="example.s.1.w.2"><t class="original">old</t></w>
+    </s>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            //only the text content of the requested class is used
+            let word = doc.get_element_by_id("example.s.1.w.1").expect("word");
+            assert_eq!(word.text(&TextParameters::default()).expect("text"), "colour");
+            assert_eq!(word.text(&TextParameters::default().textclass("original")).expect("text"), "color");
+            let word = doc.get_element_by_id("example.s.1.w.2").expect("word");
+            assert_eq!(word.text(&TextParameters::default().textclass("original")).expect("text"), "old");
+            assert!(word.text(&TextParameters::default()).is_err());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test035_plaintext() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation set="https://raw.githubusercontent.com/proycon/folia/master/setdefinitions/text.foliaset.ttl"/>
+          <division-annotation/>
+          <head-annotation/>
+          <paragraph-annotation/>
+          <sentence-annotation/>
+          <token-annotation/>
+          <linebreak-annotation/>
+          <whitespace-annotation/>
+          <list-annotation/>
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+    <div xml:id="example.div.1">
+      <head xml:id="example.head.1"><t>Chapter one</t><t class="original">Chaptre one</t></head>
+      <p xml:id="example.p.1">
+        <s xml:id="example.p.1.s.1">
+          <w xml:id="example.p.1.s.1.w.1"><t>Hello</t><t class="original">Helo</t></w>
+          <w xml:id="example.p.1.s.1.w.2" space="no"><t>world</t><t class="original">world</t></w>
+          <w xml:id="example.p.1.s.1.w.3"><t>!</t><t class="original">!</t></w>
+        </s>
+        <s xml:id="example.p.1.s.2"><t>How are you?</t><t class="original">How r u?</t></s>
+      </p>
+      <div xml:id="example.div.1.div.1">
+        <head xml:id="example.head.2"><t>Section</t></head>
+        <p xml:id="example.p.2">
+          <s xml:id="example.p.2.s.1"><t>Roses are red,</t></s>
+          <br/>
+          <s xml:id="example.p.2.s.2"><t>violets are blue.</t></s>
+        </p>
+        <whitespace/>
+        <list xml:id="example.list.1">
+          <item xml:id="example.list.1.item.1"><t>one</t></item>
+          <item xml:id="example.list.1.item.2"><t>two</t></item>
+        </list>
+      </div>
+    </div>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            let text = doc.to_plaintext(&PlainTextOptions::default()).expect("plaintext");
+            assert_eq!(text, "Chapter one\n\nHello world! How are you?\n\nSection\n\nRoses are red,\nviolets are blue.\n\none\ntwo\n");
+            let text = doc.to_plaintext(&PlainTextOptions::default().with_layout(PlainTextLayout::SentencePerLine).with_headingmarkers(true)).expect("plaintext");
+            assert_eq!(text, "# Chapter one\n\nHello world!\nHow are you?\n\n## Section\n\nRoses are red,\nviolets are blue.\n\none\ntwo\n");
+            let text = doc.to_plaintext(&PlainTextOptions::default().with_layout(PlainTextLayout::TokenPerLine)).expect("plaintext");
+            assert!(text.starts_with("Chapter one\n\nHello\nworld\n!\n\nHow are you?\n\n"), "{}", text);
+            let options = PlainTextOptions::default().with_textparameters(TextParameters::default().textclass("original"));
+            let paragraph = doc.get_element_by_id("example.p.1").expect("paragraph");
+            assert_eq!(paragraph.to_plaintext(&options).expect("plaintext"), "Helo world! How r u?\n");
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test035b_plaintext_linebreaks() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation set="https://raw.githubusercontent.com/proycon/folia/master/setdefinitions/text.foliaset.ttl"/>
+          <paragraph-annotation/>
+          <sentence-annotation/>
+          <token-annotation/>
+          <linebreak-annotation/>
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+    <p xml:id="example.p.1">
+      <s xml:id="example.p.1.s.1">
+        <w xml:id="example.p.1.s.1.w.1"><t>Roses</t></w>
+        <w xml:id="example.p.1.s.1.w.2"><t>are</t></w>
+        <w xml:id="example.p.1.s.1.w.3" space="no"><t>red</t></w>
+        <w xml:id="example.p.1.s.1.w.4"><t>,</t></w>
+        <br/>
+        <w xml:id="example.p.1.s.1.w.5"><t>violets</t></w>
+        <w xml:id="example.p.1.s.1.w.6"><t>are</t></w>
+        <w xml:id="example.p.1.s.1.w.7" space="no"><t>blue</t></w>
+        <w xml:id="example.p.1.s.1.w.8"><t>.</t></w>
+      </s>
+      <s xml:id="example.p.1.s.2">
+        <w xml:id="example.p.1.s.2.w.1" space="no"><t>Yes</t></w>
+        <w xml:id="example.p.1.s.2.w.2"><t>.</t></w>
+      </s>
+    </p>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            let text = doc.to_plaintext(&PlainTextOptions::default()).expect("plaintext");
+            assert_eq!(text, "Roses are red,\nviolets are blue. Yes.\n");
+            let text = doc.to_plaintext(&PlainTextOptions::default().with_layout(PlainTextLayout::SentencePerLine)).expect("plaintext");
+            assert_eq!(text, "Roses are red,\nviolets are blue.\nYes.\n");
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}
+
+#[test]
+fn test036_token_table() {
+    let mut doc = Document::from_text("example", "John Smith lives in New York.", &TokeniserOptions::default(), DocumentProperties::default()).expect("from_text");
+    let word_key = doc.get_element_key_by_id("example.p.1.s.1.w.3").expect("word");
+    doc.annotate(word_key, ElementData::new(ElementType::PosAnnotation)
+                            .with_attrib(Attribute::Set("adhoc".to_string()))
+                            .with_attrib(Attribute::Class("verb".to_string()))
+    ).expect("annotating pos");
+    let sentence_key = doc.get_element_key_by_id("example.p.1.s.1").expect("sentence");
+    doc.annotate(sentence_key, ElementData::new(ElementType::Entity)
+                            .with_attrib(Attribute::Set("ner".to_string()))
+                            .with_attrib(Attribute::Class("per".to_string()))
+                            .with_span(&["example.p.1.s.1.w.1", "example.p.1.s.1.w.2"])
+    ).expect("annotating entity");
+    doc.annotate(sentence_key, ElementData::new(ElementType::Entity)
+                            .with_attrib(Attribute::Set("ner".to_string()))
+                            .with_attrib(Attribute::Class("loc".to_string()))
+                            .with_span(&["example.p.1.s.1.w.5", "example.p.1.s.1.w.6"])
+    ).expect("annotating entity");
+    let columns = vec![TokenTableColumn::new(AnnotationType::POS, Some("adhoc")), TokenTableColumn::new(AnnotationType::ENTITY, None)];
+    let table = doc.token_table(&columns).expect("token table");
+    assert_eq!(table.rows.len(), 7);
+    assert_eq!(table.rows[2].id.as_deref(), Some("example.p.1.s.1.w.3"));
+    assert_eq!(table.rows[2].text, "lives");
+    assert_eq!(table.rows[2].values, vec![Some("verb".to_string()), Some("O".to_string())]);
+    let tags: Vec<&str> = table.rows.iter().map(|row| row.values[1].as_deref().unwrap()).collect();
+    assert_eq!(tags, vec!["B-per", "I-per", "O", "O", "B-loc", "I-loc", "O"]);
+    let tsv = table.to_tsv();
+    let lines: Vec<&str> = tsv.lines().collect();
+    assert_eq!(lines[0], "id\ttext\tpos:adhoc\tentity");
+    assert_eq!(lines[1], "example.p.1.s.1.w.1\tJohn\t_\tB-per");
+    assert_eq!(lines[3], "example.p.1.s.1.w.3\tlives\tverb\tO");
+    assert_eq!(lines.len(), 8);
+}
+
+#[test]
+fn test037_bio_import() {
+    let mut doc = Document::from_text("example", "John Smith lives in New York.\n\nHe likes Paris.", &TokeniserOptions::default(), DocumentProperties::default()).expect("from_text");
+    doc.add_processor(Processor::new("tagger".to_string()).with_id("tagger".to_string())).expect("adding processor");
+    let bio = "John\tB-per\nSmith\tI-per\nlives\tO\nin\tO\nNew\tB-loc\nYork\tI-loc\n.\tO\n\nHe\tO\nlikes\tO\nParis\tI-loc\n.\tO\n";
+    let keys = doc.annotate_bio(bio, &BioOptions::default().with_set("ner").with_processor("tagger")).expect("bio import");
+    assert_eq!(keys.len(), 3);
+    let entity = doc.get_element(keys[0]).expect("entity");
+    assert_eq!(entity.class(), Some("per"));
+    assert_eq!(entity.set(), Some("ner"));
+    assert_eq!(entity.processor(), Some("tagger"));
+    let table = doc.token_table(&[TokenTableColumn::new(AnnotationType::ENTITY, Some("ner"))]).expect("token table");
+    let tags: Vec<&str> = table.rows.iter().map(|row| row.values[0].as_deref().unwrap()).collect();
+    assert_eq!(tags, vec!["B-per", "I-per", "O", "O", "B-loc", "I-loc", "O", "O", "O", "B-loc", "O"]);
+
+    //matching by ID, with another span type
+    let bio = "example.p.2.s.1.w.2 likes B-verbal\nexample.p.2.s.1.w.3 Paris I-verbal\n";
+    let keys = doc.annotate_bio(bio, &BioOptions::default().with_elementtype(ElementType::Chunk).with_set("chunks").with_matching(TokenMatching::Id)).expect("bio import");
+    assert_eq!(keys.len(), 1);
+    assert_eq!(doc.get_element(keys[0]).expect("chunk").span_words().len(), 2);
+
+    //tokens must match
+    assert!(doc.annotate_bio("Jon\tB-per\n", &BioOptions::default().with_set("ner")).is_err());
+    assert!(doc.annotate_bio("John\tX-per\n", &BioOptions::default().with_set("ner")).is_err());
+
+    //a span that can't be added leaves the document untouched, even if earlier spans were fine
+    let bio = "example.p.1.s.1.w.1 John B-x\nexample.p.2.s.1 He B-x\n";
+    assert!(doc.annotate_bio(bio, &BioOptions::default().with_set("other").with_matching(TokenMatching::Id)).is_err());
+    let word = doc.get_element_by_id("example.p.1.s.1.w.1").expect("word");
+    assert!(word.get_annotation(AnnotationType::ENTITY, Cmp::Is("other".to_string()), Recursion::No).is_none());
+    assert!(doc.find_declaration(AnnotationType::ENTITY, &Some("other".to_string())).is_none());
+}
+
+#[test]
+fn test038_json() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let json = doc.to_json().expect("to json");
+            assert_eq!(json.id, "example");
+            assert_eq!(json.metadata.data.get("language").map(|s| s.as_str()), Some("eng"));
+            assert_eq!(json.provenance.len(), 2);
+            assert_eq!(json.provenance[0].id, "p1");
+            let pos = json.declarations.iter().find(|declaration| declaration.annotationtype == "pos").expect("pos declaration");
+            assert_eq!(pos.set.as_deref(), Some("adhoc"));
+            assert_eq!(pos.processors, vec!["p2".to_string()]);
+            assert_eq!(json.body.elementtype, "text");
+            assert_eq!(json.body.attribute("xml:id"), Some("example.text"));
+
+            let data = doc.to_json_string(true).expect("to json string");
+            assert!(data.contains("\"class\": \"PUNCTUATION\""));
+            assert!(data.contains("\"space\": \"no\""));
+            let doc2 = Document::from_json_str(&data, DocumentProperties::default()).expect("from json");
+            assert_eq!(doc2.to_json().expect("to json").provenance, json.provenance);
+            assert_eq!(doc2.xml_canonical(0).expect("serialising"), doc.xml_canonical(0).expect("serialising"));
+            //attributes keep their order
+            let word_key = doc.get_element_key_by_id("example.p.1.s.1.w.2").expect("word");
+            let word_key2 = doc2.get_element_key_by_id("example.p.1.s.1.w.2").expect("word");
+            let fragment = doc2.xml_fragment(word_key2, &SerialiseOptions::default(), false).expect("serialising");
+            assert!(str::from_utf8(&fragment).expect("utf-8").contains("<w xmlns=\"http://ilk.uvt.nl/folia\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" xml:id=\"example.p.1.s.1.w.2\" space=\"no\""));
+            assert_eq!(fragment, doc.xml_fragment(word_key, &SerialiseOptions::default(), false).expect("serialising"));
+            assert!(data.find("\"xml:id\": \"example.p.1.s.1.w.2\"").expect("id") < data.find("\"space\": \"no\"").expect("space"));
+            let word = doc2.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.class(), Some("noun"));
+            assert_eq!(pos.processor(), Some("p2"));
+            assert_eq!(doc2.get_element_by_id("example.p.1.s.2.chunk.1").expect("chunk").span_words().len(), 2);
+
+            assert!(Document::from_json_str("{\"id\": \"x\"}", DocumentProperties::default()).is_err());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}",err);
+        }
+    }
+}
+
+#[test]
+fn test039_html() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata type="native">
+      <annotations>
+          <text-annotation/>
+          <division-annotation/>
+          <head-annotation/>
+          <paragraph-annotation/>
+          <sentence-annotation/>
+          <token-annotation/>
+          <list-annotation/>
+          <table-annotation/>
+          <figure-annotation/>
+          <pos-annotation set="adhoc">
+             <annotator processor="p1" />
+          </pos-annotation>
+          <entity-annotation set="ner">
+             <annotator processor="p1" />
+          </entity-annotation>
+      </annotations>
+      <provenance>
+         <processor xml:id="p1" name="tagger" version="1.0" type="auto" />
+      </provenance>
+      <meta id="title">Sample &amp; co</meta>
+  </metadata>
+  <text xml:id="example.text">
+    <div xml:id="example.div.1">
+      <head xml:id="example.head.1"><t>Chapter one</t></head>
+      <p xml:id="example.p.1">
+        <s xml:id="example.p.1.s.1">
+          <w xml:id="example.p.1.s.1.w.1"><t>John</t><pos class="PROPN"/></w>
+          <w xml:id="example.p.1.s.1.w.2"><t>Smith</t><pos class="PROPN"/></w>
+          <w xml:id="example.p.1.s.1.w.3" space="no"><t>sleeps</t><pos class="VERB"/></w>
+          <w xml:id="example.p.1.s.1.w.4"><t>.</t><pos class="PUNCT"/></w>
+          <entities>
+            <entity xml:id="example.e.1" class="per">
+              <wref id="example.p.1.s.1.w.1"/>
+              <wref id="example.p.1.s.1.w.2"/>
+            </entity>
+          </entities>
+        </s>
+      </p>
+      <list xml:id="example.list.1">
+        <item xml:id="example.item.1"><t>first</t></item>
+        <item xml:id="example.item.2"><t>second</t></item>
+      </list>
+      <table xml:id="example.table.1">
+        <row><cell><t>a</t></cell><cell><t>b &lt; c</t></cell></row>
+      </table>
+      <figure xml:id="example.figure.1" src="image.png">
+        <caption><t>A picture</t></caption>
+      </figure>
+    </div>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(doc) => {
+            let html = doc.to_html(&HtmlOptions::default()).expect("html");
+            assert!(html.starts_with("<!DOCTYPE html>"));
+            assert!(html.contains("<h1 class=\"title\">Sample &amp; co</h1>"));
+            assert!(html.contains("<tr><th>title</th><td>Sample &amp; co</td></tr>"));
+            assert!(html.contains("<li>tagger 1.0 (auto)</li>"));
+            assert!(html.contains("<h1 class=\"head\" id=\"example.head.1\">Chapter one</h1>"));
+            assert!(html.contains("<li class=\"item\" id=\"example.item.2\">second</li>"));
+            assert!(html.contains("<td class=\"cell\">b &lt; c</td>"));
+            assert!(html.contains("<img src=\"image.png\"/>"));
+            assert!(html.contains("<figcaption class=\"caption\">A picture</figcaption>"));
+            //consecutive words of a span share one mark
+            assert!(html.contains("<mark class=\"span entity\" title=\"entity: per\"><span class=\"w\" id=\"example.p.1.s.1.w.1\" title=\"pos: PROPN\">John</span> <span class=\"w\" id=\"example.p.1.s.1.w.2\" title=\"pos: PROPN\">Smith</span></mark> <span class=\"w\" id=\"example.p.1.s.1.w.3\""), "{}", html);
+            assert_eq!(html.matches("<mark").count(), 1);
+            let word = doc.get_element_by_id("example.p.1.s.1.w.2").expect("word");
+            assert_eq!(word.to_html(&HtmlOptions::default()).expect("html"), "<mark class=\"span entity\" title=\"entity: per\"><span class=\"w\" id=\"example.p.1.s.1.w.2\" title=\"pos: PROPN\">Smith</span></mark>");
+            assert!(html.contains("<span class=\"w\" id=\"example.p.1.s.1.w.3\" title=\"pos: VERB\">sleeps</span><span class=\"w\""));
+
+            //glosses, limited to part-of-speech, without spans and header
+            let options = HtmlOptions::default().with_glosses(true).with_header(false).with_standalone(false)
+                            .with_annotation(AnnotationType::POS, Some("adhoc"))
+                            .with_span(AnnotationType::CHUNKING, None);
+            let html = doc.to_html(&options).expect("html");
+            assert!(html.starts_with("<div class=\"text\""));
+            assert!(!html.contains("<mark"));
+            assert!(html.contains("<span class=\"w gloss\" id=\"example.p.1.s.1.w.3\"><span class=\"t\">sleeps</span><span class=\"a pos\">VERB</span></span>"));
+
+            let sentence = doc.get_element_by_id("example.p.1.s.1").expect("sentence");
+            assert!(sentence.to_html(&options).expect("html").starts_with("<span class=\"s\" id=\"example.p.1.s.1\">"));
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}",err);
+        }
+    }
+}
+
+#[test]
+fn test040_brat() {
+    let text = "John Smith lives in New York.\n\nHe likes Paris.";
+    let mut doc = Document::from_text("example", text, &TokeniserOptions::default(), DocumentProperties::default()).expect("from_text");
+    let ann = "T1\tPerson 0 10\tJohn Smith\nT2\tLocation 20 28\tNew York\nT3\tLocation 40 45\tParis\nR1\tLivesIn Arg1:T1 Arg2:T2\nA1\tNegated T3\n#1\tAnnotatorNotes T1\tsome note\n";
+    let options = BratOptions::default().with_entity_set("brat").with_relation_set("bratrelations");
+    let keys = doc.annotate_brat(ann, &options).expect("brat import");
+    assert_eq!(keys.len(), 4);
+    let entity = doc.get_element(keys[0]).expect("entity");
+    assert_eq!(entity.class(), Some("Person"));
+    assert_eq!(entity.text(&TextParameters::default()).expect("text"), "John Smith");
+    assert_eq!(doc.get_element(keys[2]).expect("entity").features().get("Negated"), Some(&vec!["true".to_string()]));
+    let relation = doc.get_element(keys[3]).expect("relation");
+    assert_eq!(relation.class(), Some("LivesIn"));
+    assert_eq!(relation.get_parent().and_then(|parent| parent.id().map(|id| id.to_string())).as_deref(), Some("example.p.1.s.1"));
+
+    let brat = doc.to_brat(&options).expect("brat export");
+    assert_eq!(brat.text, "John Smith lives in New York.\n\nHe likes Paris.\n");
+    assert_eq!(brat.annotations, "T1\tPerson 0 10\tJohn Smith\nT2\tLocation 20 28\tNew York\nT3\tLocation 40 45\tParis\nA1\tNegated T3\nR1\tLivesIn Arg1:T1 Arg2:T2\n");
+
+    //round trip onto a fresh document
+    let mut doc2 = Document::from_text("example", text, &TokeniserOptions::default(), DocumentProperties::default()).expect("from_text");
+    doc2.annotate_brat(&brat.annotations, &options).expect("brat import");
+    assert_eq!(doc2.to_brat(&options).expect("brat export"), brat);
+
+    //discontinuous entity
+    doc2.annotate_brat("T1\tThing 0 4;11 16\tJohn lives\n", &options).expect("brat import");
+    assert!(doc2.to_brat(&options).expect("brat export").annotations.contains("\tThing 0 4;11 16\tJohn lives\n"));
+
+    //offsets have to match the text
+    assert!(doc2.annotate_brat("T1\tPerson 0 4\tJohan\n", &options).is_err());
+    assert!(doc2.annotate_brat("T1\tPerson 0 400\tJohn\n", &options).is_err());
+    assert!(doc2.annotate_brat("R1\tLivesIn Arg1:T8 Arg2:T9\n", &options).is_err());
+    //nothing is added if any annotation is invalid
+    let before = doc2.to_brat(&options).expect("brat export");
+    assert!(doc2.annotate_brat("T1\tPerson 0 4\tJohn\nR1\tLivesIn Arg1:T1 Arg2:T9\n", &options).is_err());
+    assert_eq!(doc2.to_brat(&options).expect("brat export"), before);
+
+    //relations that are not binary are reported
+    let mut doc3 = Document::from_text("example", text, &TokeniserOptions::default(), DocumentProperties::default()).expect("from_text");
+    let keys = doc3.annotate_brat("T1\tPerson 0 4\tJohn\nT2\tLocation 20 28\tNew York\nT3\tLocation 40 45\tParis\nR1\tTravels Arg1:T1 Arg2:T2 Arg3:T3\n", &options).expect("brat import");
+    let brat = doc3.to_brat(&options).expect("brat export");
+    assert_eq!(brat.skipped, vec![keys[3]]);
+    assert!(!brat.annotations.contains("Travels"));
+
+    //types can't contain whitespace
+    doc3.annotate_span(ElementData::new(ElementType::Entity)
+                        .with_attrib(Attribute::Set("brat".to_string()))
+                        .with_attrib(Attribute::Class("Big City".to_string()))
+                        .with_span(&["example.p.1.s.1.w.5", "example.p.1.s.1.w.6"])).expect("annotating entity");
+    assert!(doc3.to_brat(&options).is_err());
+}
+
+#[test]
+fn test041_snapshot() {
+    match Document::from_str(str::from_utf8(EXAMPLE).expect("conversion from utf-8 of example"), DocumentProperties::default()) {
+        Ok(doc) => {
+            let data = doc.to_snapshot().expect("to snapshot");
+            assert!(data.starts_with(SNAPSHOT_MAGIC));
+            let doc2 = Document::from_snapshot(&data).expect("from snapshot");
+            assert_eq!(doc2.id, "example");
+            assert_eq!(doc2.xml(0,4).expect("serialising"), doc.xml(0,4).expect("serialising"));
+            let word = doc2.get_element_by_id("example.p.1.s.2.w.4").expect("word");
+            let pos = word.get_annotation(AnnotationType::POS, Cmp::Any, Recursion::No).expect("pos");
+            assert_eq!(pos.class(), Some("noun"));
+            assert_eq!(pos.processor(), Some("p2"));
+            assert_eq!(doc2.get_element_by_id("example.p.1.s.2.chunk.1").expect("chunk").span_words().len(), 2);
+
+            //snapshots from other format or library versions are rejected
+            let mut wrongformat = data.clone();
+            wrongformat[SNAPSHOT_MAGIC.len()] += 1;
+            assert!(Document::from_snapshot(&wrongformat).is_err());
+            let mut wronglibrary = data.clone();
+            let last = SNAPSHOT_MAGIC.len() + 6 + SNAPSHOT_LIBRARY_VERSION.len() - 1;
+            wronglibrary[last] = b'x';
+            assert!(Document::from_snapshot(&wronglibrary).is_err());
+            assert!(Document::from_snapshot(b"<FoLiA/>").is_err());
+        },
+        Err(err) => {
+            assert!(false, format!("Instantiation failed with error: {}",err));
+        }
+    }
+}
+
+#[test]
+fn test042_syntax_errors() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<FoLiA xmlns="http://ilk.uvt.nl/folia" version="2.0" xml:id="example">
+  <metadata>
+      <annotations>
+          <text-annotation set="https://raw.githubusercontent.com/proycon/folia/master/setdefinitions/text.foliaset.ttl" />
+          <token-annotation />
+          <sentence-annotation />
+          <syntax-annotation set="penn" />
+      </annotations>
+  </metadata>
+  <text xml:id="example.text">
+    <s xml:id="example.s.1">
+      <w xml:id="example.s.1.w.1"><t>New York</t></w>
+      <w xml:id="example.s.1.w.2"><t>sleeps</t></w>
+      <syntax>
+        <su xml:id="example.s.1.su.1" class="S">
+          <su xml:id="example.s.1.su.1_1" class="NP"><wref id="example.s.1.w.1" /></su>
+          <su xml:id="example.s.1.su.1_2" class="VP"><wref id="example.s.1.w.2" /></su>
+        </su>
+      </syntax>
+    </s>
+  </text>
+</FoLiA>"#;
+    match Document::from_str(xml, DocumentProperties::default()) {
+        Ok(mut doc) => {
+            //an unresolvable word reference (here left behind by a failed addition) is reported with its id
+            let su_key = doc.get_element_key_by_id("example.s.1.su.1").expect("su");
+            let su = ElementData::new(ElementType::SyntacticUnit).with_attrib(Attribute::Class("PP".to_string()))
+                .add_element(ElementData::new(ElementType::WordReference).with_attrib(Attribute::Idref("example.s.1.w.3".to_string())));
+            assert!(doc.add_element_to(su_key, su).is_err());
+            let sentence = doc.get_element_by_id("example.s.1").expect("sentence");
+            match sentence.syntax_tree(Cmp::Any) {
+                Err(err) => assert!(format!("{}", err).contains("example.s.1.w.3"), "{}", err),
+                Ok(_) => assert!(false, "expected an error for an unresolvable word reference"),
+            }
+            //words containing whitespace can not be matched in a bracketed string
+            let sentence_key = doc.get_element_key_by_id("example.s.1").expect("sentence");
+            assert!(doc.annotate_syntax(sentence_key, "(S (NP New_York) (VP sleeps))", Some("penn")).is_err());
+        },
+        Err(err) => {
+            assert!(false, "Instantiation failed with error: {}", err);
+        }
+    }
+}